@@ -0,0 +1,52 @@
+/* The C API of rdel, built with:
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * See src/ffi.rs for the details of each function.
+ */
+#ifndef RDEL_H
+#define RDEL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Flags for rdel_new. */
+#define RDEL_TRASH 1u
+#define RDEL_RECURSIVE 2u
+
+/* Outcomes passed to the progress callback. */
+#define RDEL_REMOVED 0
+#define RDEL_WOULD_REMOVE 1
+#define RDEL_SKIPPED 2
+#define RDEL_FAILED 3
+
+typedef struct RdelOptions RdelOptions;
+
+typedef struct RdelSummary {
+    uint64_t files_examined;
+    uint64_t files_removed;
+    uint64_t files_failed;
+    uint64_t bytes_removed;
+    uint64_t bytes_freed;
+} RdelSummary;
+
+typedef void (*RdelProgress)(void *user_data, const char *path, uint64_t size, int outcome);
+
+RdelOptions *rdel_new(const char *filter, uint32_t flags);
+void rdel_set_progress(RdelOptions *options, RdelProgress callback, void *user_data);
+int rdel_plan(const RdelOptions *options, const char *const *targets, size_t count,
+              RdelSummary *summary);
+int rdel_execute(const RdelOptions *options, const char *const *targets, size_t count,
+                 RdelSummary *summary);
+const char *rdel_last_error(void);
+void rdel_free(RdelOptions *options);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RDEL_H */
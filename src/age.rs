@@ -0,0 +1,55 @@
+use std::time::{Duration, SystemTime};
+
+/// Which of a file's timestamps the age filters use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeField {
+    /// When the contents were last modified.
+    Modified,
+
+    /// When the file was last read.
+    Accessed,
+
+    /// When the file's metadata last changed (Unix only).
+    Changed,
+
+    /// When the file was created, where the file system records it.
+    Created,
+}
+
+impl TimeField {
+    /// Converts the command line value into a `TimeField`. Unknown values use the modification
+    /// time.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "atime" => Self::Accessed,
+            "ctime" => Self::Changed,
+            "btime" => Self::Created,
+            _ => Self::Modified,
+        }
+    }
+
+    /// The name used on the command line and in messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Modified => "mtime",
+            Self::Accessed => "atime",
+            Self::Changed => "ctime",
+            Self::Created => "btime",
+        }
+    }
+}
+
+/// Selects files based on how old they are.
+#[derive(Debug, Clone, Copy)]
+pub struct AgeFilter {
+    pub older_than: Duration,
+    pub field: TimeField,
+}
+
+impl AgeFilter {
+    /// Returns true if `time` is further in the past than the age limit, as of `now`.
+    pub fn is_old_enough(&self, time: SystemTime, now: SystemTime) -> bool {
+        now.duration_since(time)
+            .is_ok_and(|age| age > self.older_than)
+    }
+}
@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The zstd compression level used for archives.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// A zstd-compressed tar archive that files are copied into before they are removed.
+pub struct Archive {
+    path: PathBuf,
+    builder: tar::Builder<zstd::Encoder<'static, BufWriter<File>>>,
+}
+
+impl Archive {
+    /// Creates the archive, replacing it if it already exists.
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(path)
+            .map_err(|err| format!("Unable to create archive {}: {err}", path.display()))?;
+        let encoder = zstd::Encoder::new(BufWriter::new(file), COMPRESSION_LEVEL)?;
+
+        let mut builder = tar::Builder::new(encoder);
+        builder.follow_symlinks(false);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            builder,
+        })
+    }
+
+    /// Adds a local file to the archive, keeping its permissions and timestamps.
+    pub fn add_file(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
+        self.builder
+            .append_path_with_name(filename, archive_name(filename))?;
+        Ok(())
+    }
+
+    /// Adds a local directory and everything in it to the archive.
+    pub fn add_dir(&mut self, dirname: &str) -> Result<(), Box<dyn Error>> {
+        self.builder
+            .append_dir_all(archive_name(dirname), dirname)?;
+        Ok(())
+    }
+
+    /// Adds `size` bytes read from `reader` to the archive under `name`.
+    /// Used for files that aren't on the local file system.
+    pub fn add_reader(
+        &mut self,
+        name: &str,
+        size: u64,
+        reader: &mut dyn Read,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+        );
+
+        self.builder
+            .append_data(&mut header, archive_name(name), reader)?;
+        Ok(())
+    }
+
+    /// Finishes writing the archive and returns its size in bytes.
+    pub fn finish(self) -> Result<u64, Box<dyn Error>> {
+        let encoder = self.builder.into_inner()?;
+        encoder.finish()?;
+        Ok(fs::metadata(&self.path)?.len())
+    }
+}
+
+/// Works out the name of a file inside the archive: any `scheme://` prefix and
+/// leading root, `.` and `..` components are removed.
+fn archive_name(name: &str) -> PathBuf {
+    let name = name.split_once("://").map_or(name, |(_, rest)| rest);
+    Path::new(name)
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
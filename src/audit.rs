@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::{error::Error, path::Path};
+
+use crate::security::Security;
+
+/// A single entry in the audit log, describing one file that was removed or quarantined.
+#[derive(Debug, Serialize)]
+pub struct AuditRecord<'a> {
+    /// When the file was removed, in RFC 3339 format, in local time or in UTC with `--utc`.
+    pub timestamp: String,
+
+    /// The path of the file as supplied on the command line.
+    pub path: &'a str,
+
+    /// The size of the file in bytes at the time it was removed.
+    pub size: u64,
+
+    /// The checksum algorithm used, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum_algorithm: Option<&'a str>,
+
+    /// The checksum of the file contents, if calculated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<&'a str>,
+
+    /// Where the file was moved to, if it was quarantined rather than deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quarantined_to: Option<&'a str>,
+
+    /// Where the file was moved to, if it was moved elsewhere rather than deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<&'a str>,
+
+    /// Whether the file was moved to the trash rather than deleted.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub trashed: bool,
+
+    /// The method used to overwrite the file's data before it was deleted, if it was shredded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shred_method: Option<&'a str>,
+
+    /// The ownership, permissions, ACLs and security context the file had, if the backend
+    /// can tell.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security: Option<&'a Security>,
+
+    /// Set on the record written for `--audit-sync` before the file is removed. The record of
+    /// what was done with it follows once it has been removed; if none does, the run was cut
+    /// short or the removal failed.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub intended: bool,
+}
+
+impl<'a> AuditRecord<'a> {
+    /// Creates a new record for `path` timestamped with the current time.
+    pub fn new(path: &'a str, size: u64) -> Self {
+        Self {
+            timestamp: crate::timestamp::now_rfc3339(),
+            path,
+            size,
+            checksum_algorithm: None,
+            checksum: None,
+            quarantined_to: None,
+            moved_to: None,
+            trashed: false,
+            shred_method: None,
+            security: None,
+            intended: false,
+        }
+    }
+}
+
+/// An append-only audit log with one JSON record per line.
+pub struct AuditLog {
+    writer: BufWriter<File>,
+}
+
+impl AuditLog {
+    /// Opens the audit log for appending, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Appends a record to the log.
+    pub fn record(&mut self, record: &AuditRecord) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flushes the records written so far to the disk, so they survive a crash or a power cut.
+    pub fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        Ok(())
+    }
+}
+
+/// A record read back from the audit log, for the `history` command.
+#[derive(Debug, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub path: String,
+    pub size: u64,
+    pub quarantined_to: Option<String>,
+    pub moved_to: Option<String>,
+    #[serde(default)]
+    pub trashed: bool,
+    pub shred_method: Option<String>,
+    #[serde(default)]
+    pub intended: bool,
+}
+
+impl HistoryEntry {
+    /// Describes what was done with the file, e.g. `trashed` or `moved to /archive/a.log`.
+    pub fn action(&self) -> String {
+        if let Some(target) = &self.quarantined_to {
+            format!("quarantined to {target}")
+        } else if let Some(target) = &self.moved_to {
+            format!("moved to {target}")
+        } else if self.trashed {
+            "trashed".to_string()
+        } else if let Some(method) = &self.shred_method {
+            format!("shredded ({method})")
+        } else if self.intended {
+            "about to be removed, not confirmed".to_string()
+        } else {
+            "deleted".to_string()
+        }
+    }
+}
+
+/// Lists the files recorded in the audit log whose paths match any of the patterns (or all of
+/// them, if there are none), oldest first. Only the last `last` are listed, if given. A record
+/// written by `--audit-sync` before a file was removed is left out once the file's removal is
+/// on record too.
+pub fn history(
+    path: &Path,
+    patterns: &[glob::Pattern],
+    last: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::open(path)
+        .map_err(|err| format!("Unable to open audit log {}: {err}", path.display()))?;
+
+    let mut entries = Vec::new();
+    let mut intended = HashMap::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: HistoryEntry = serde_json::from_str(&line).map_err(|err| {
+            format!(
+                "Unable to read line {} of audit log {}: {err}",
+                number + 1,
+                path.display()
+            )
+        })?;
+        if patterns.is_empty() || patterns.iter().any(|pattern| pattern.matches(&entry.path)) {
+            if entry.intended {
+                intended.insert(entry.path.clone(), entries.len());
+            } else if let Some(index) = intended.remove(&entry.path) {
+                entries[index] = None;
+            }
+            entries.push(Some(entry));
+        }
+    } // for line
+
+    let entries: Vec<HistoryEntry> = entries.into_iter().flatten().collect();
+    let skip = last.map_or(0, |last| entries.len().saturating_sub(last));
+    for entry in entries.iter().skip(skip) {
+        log::info!(
+            "{}  {:>15}  {}  {}",
+            crate::timestamp::reformat(&entry.timestamp),
+            crate::utils::thousand_separated(entry.size),
+            entry.path,
+            entry.action()
+        );
+    } // for entry
+
+    Ok(())
+}
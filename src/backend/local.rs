@@ -0,0 +1,1044 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use super::{Backend, Entry, FileType, HardLinks, RemoveResults, Removed, Stamp};
+use crate::age::TimeField;
+use crate::archive::Archive;
+use crate::confirm::Interactive;
+use crate::deleter::Settings;
+use crate::hazard::{self, Hazard};
+use crate::in_use::{Holder, OpenFiles};
+use crate::mounts;
+use crate::quarantine::Quarantine;
+use crate::security::{self, Security};
+use crate::transfer::{self, Copied};
+use crate::walker::{self, DirLinks, Special, TreeStats};
+use crate::{shred, timeout};
+
+/// How many threads look up the details of the files before the scan.
+const PREFETCH_THREADS: usize = 16;
+
+/// How many threads look up the details of the files when some are on a network file system,
+/// so the server isn't swamped.
+const NETWORK_PREFETCH_THREADS: usize = 4;
+
+/// How many times longer `--file-timeout` is for files on a network file system.
+const NETWORK_TIMEOUT_FACTOR: u32 = 4;
+
+/// How many times a removal is tried again when a network file system reports a stale file
+/// handle, and how long to wait before the first retry. The wait doubles with each retry.
+const STALE_RETRIES: u32 = 3;
+const STALE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// How many files are removed together when they can be removed relative to the directory they
+/// are in, so each directory is only opened once for all of them.
+const GROUP_SIZE: usize = 256;
+
+/// What the local backend does with the files it removes.
+#[derive(Debug)]
+pub enum Disposal {
+    /// Delete them.
+    Delete,
+
+    /// Move them into the quarantine.
+    Quarantine(Quarantine),
+
+    /// Move them to the trash (recycle bin) of the desktop environment.
+    Trash,
+
+    /// Move them into the given directory.
+    MoveTo(PathBuf),
+}
+
+/// What a file was found to be, for `--paranoid`.
+#[cfg(unix)]
+type Identity = super::paranoid::Identity;
+#[cfg(not(unix))]
+type Identity = ();
+
+/// How the files are removed. This is shared with the threads removals run on for
+/// `--file-timeout`.
+#[derive(Debug)]
+struct Remover {
+    disposal: Disposal,
+
+    /// Whether directories are removed along with everything in them.
+    recursive: bool,
+
+    /// What is done with the special files in the directory trees removed.
+    special: Special,
+
+    /// What is done with the links to directories in the directory trees removed.
+    dir_links: DirLinks,
+
+    /// Whether btrfs subvolumes are deleted with the subvolume ioctl.
+    #[cfg(target_os = "linux")]
+    allow_subvolumes: bool,
+
+    /// Whether the data in files is overwritten before they are deleted.
+    shred: Option<shred::Method>,
+
+    /// Whether the last shred pass is read back and checked.
+    shred_verify: bool,
+
+    /// Whether files are renamed to random names before they are deleted.
+    scramble_name: bool,
+
+    /// Whether the immutable and append-only flags are cleared from files that have them.
+    force_immutable: bool,
+
+    /// What had to be copied to another file system to dispose of each entry, until it's taken
+    /// for the summary.
+    copied: Mutex<HashMap<String, Copied>>,
+
+    /// How many directories of each tree removed were left, as special files were kept in them,
+    /// until it's taken for the summary.
+    dirs_kept: Mutex<HashMap<String, usize>>,
+}
+
+/// Files on the local file system. Files are deleted, or otherwise disposed of as configured.
+pub struct Local {
+    remover: Arc<Remover>,
+
+    /// How long a single file system operation may take, for `--file-timeout`.
+    file_timeout: Option<Duration>,
+
+    /// Whether each file is checked to still be the one that was found before it's removed.
+    paranoid: bool,
+
+    /// Whether the files are removed in batches, relative to the directories they are in,
+    /// rather than one at a time by their full paths.
+    grouped: bool,
+
+    /// Whether the directories files were removed from are synced to disk at the end.
+    sync: bool,
+
+    /// The directories with removals that haven't been synced yet, for `--sync`.
+    unsynced: BTreeSet<PathBuf>,
+
+    /// Whether the unused blocks of the file systems files were deleted from are trimmed at the
+    /// end.
+    #[cfg(target_os = "linux")]
+    discard: bool,
+
+    /// The mount points of the file systems files were deleted from, for `--discard`.
+    #[cfg(target_os = "linux")]
+    untrimmed: BTreeSet<PathBuf>,
+
+    /// The identities of the files found, for `--paranoid`.
+    #[cfg(unix)]
+    identities: HashMap<String, super::paranoid::Identity>,
+
+    /// The processes with files open, for `--skip-in-use`.
+    open_files: OpenFiles,
+
+    /// The metadata of the files, looked up in parallel before the scan and used until the
+    /// removals start.
+    prefetched: HashMap<String, fs::Metadata>,
+
+    /// What is in each directory tree found, for the summary.
+    trees: HashMap<String, TreeStats>,
+
+    /// The mount points of the file systems files were found on, so each is only logged once.
+    filesystems: BTreeSet<PathBuf>,
+}
+
+impl Local {
+    /// Creates the backend, disposing of the files it removes as given. With `--recursive`,
+    /// directories are removed with their contents; otherwise only empty ones can be removed.
+    pub fn new(disposal: Disposal, settings: &Settings) -> Self {
+        // Files have to be removed one at a time when something is done with each on its own
+        // just before it's removed, or the run may have to stop or ask right after one fails
+        let grouped = cfg!(unix)
+            && matches!(disposal, Disposal::Delete)
+            && settings.shred.is_none()
+            && !settings.scramble_name
+            && !settings.paranoid
+            && !settings.recheck
+            && settings.file_timeout.is_none()
+            && settings.max_errors.is_none()
+            && !settings.prompt_on_error
+            && settings.interactive != Interactive::Always;
+
+        Self {
+            remover: Arc::new(Remover {
+                disposal,
+                recursive: settings.recursive,
+                special: settings.special,
+                dir_links: settings.dir_links,
+                #[cfg(target_os = "linux")]
+                allow_subvolumes: settings.allow_subvolumes,
+                shred: settings.shred,
+                shred_verify: settings.shred_verify,
+                scramble_name: settings.scramble_name,
+                force_immutable: settings.force_immutable,
+                copied: Mutex::new(HashMap::new()),
+                dirs_kept: Mutex::new(HashMap::new()),
+            }),
+            file_timeout: settings.file_timeout,
+            paranoid: settings.paranoid,
+            grouped,
+            sync: settings.sync,
+            unsynced: BTreeSet::new(),
+            #[cfg(target_os = "linux")]
+            discard: settings.discard,
+            #[cfg(target_os = "linux")]
+            untrimmed: BTreeSet::new(),
+            #[cfg(unix)]
+            identities: HashMap::new(),
+            open_files: OpenFiles::default(),
+            prefetched: HashMap::new(),
+            trees: HashMap::new(),
+            filesystems: BTreeSet::new(),
+        }
+    }
+
+    /// Logs the type of the file system a file is on, the first time a file on it is found.
+    fn note_filesystem(&mut self, filename: &str) {
+        let Some(filesystem) = mounts::filesystem_of(Path::new(filename)) else {
+            return;
+        };
+        if !self.filesystems.insert(filesystem.mount.clone()) {
+            return;
+        }
+        if filesystem.is_network() {
+            log::debug!(
+                "{} is a network file system ({}). Using fewer threads and longer timeouts, retrying stale file handles and not syncing.",
+                filesystem.mount.display(),
+                filesystem.fs_type
+            );
+        } else {
+            log::debug!(
+                "The file system at {} is {}.",
+                filesystem.mount.display(),
+                filesystem.fs_type
+            );
+        }
+    }
+
+    /// Returns the identity recorded for a file when it was found, if `--paranoid` is set.
+    fn identity(&self, filename: &str) -> Result<Option<Identity>, Box<dyn Error>> {
+        #[cfg(unix)]
+        if self.paranoid {
+            return self
+                .identities
+                .get(filename)
+                .copied()
+                .map(Some)
+                .ok_or_else(|| format!("{filename} wasn't examined before being removed.").into());
+        }
+        #[cfg(not(unix))]
+        let _ = filename;
+        Ok(None)
+    }
+
+    /// Returns true if the entry is a directory tree to be removed as a whole.
+    fn is_tree(&self, filename: &str) -> bool {
+        self.remover.recursive
+            && self
+                .metadata(filename)
+                .is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    /// Returns the metadata of a file, without following a symbolic link. The metadata looked up
+    /// before the scan is used if there is any.
+    fn metadata(&self, filename: &str) -> Result<fs::Metadata, Box<dyn Error>> {
+        match self.prefetched.get(filename) {
+            Some(metadata) => Ok(metadata.clone()),
+            None => lookup(filename, timeout_for(filename, self.file_timeout)),
+        }
+    }
+
+    /// Returns the canonical form of a path, with `.`, `..` and symbolic links in the directories
+    /// resolved, so the same file always has the same name. The file name itself is kept, so a
+    /// symbolic link given as a target is still removed rather than the file it points to.
+    /// Paths that can't be resolved are returned as they are.
+    pub fn canonical(filename: &str) -> String {
+        let path = Path::new(filename.strip_prefix("file://").unwrap_or(filename));
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return filename.to_string();
+        };
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+
+        match fs::canonicalize(parent) {
+            Ok(dir) => dir
+                .join(name)
+                .to_str()
+                .map_or_else(|| filename.to_string(), str::to_string),
+            Err(_) => filename.to_string(),
+        }
+    }
+
+    /// Creates an entry for a local file, using the size on disk. A symbolic link is sized as
+    /// the link itself, since removing it doesn't free the space used by its target. On macOS,
+    /// the extended attributes (including the resource fork) are counted too.
+    fn entry(&self, filename: &str) -> Result<Entry, Box<dyn Error>> {
+        let size = self.metadata(filename)?.len();
+        #[cfg(target_os = "macos")]
+        let size = size + crate::xattr::size(Path::new(filename)).unwrap_or_default();
+        Ok(Entry {
+            name: filename.to_string(),
+            size,
+        })
+    }
+}
+
+impl Backend for Local {
+    fn action(&self) -> &'static str {
+        match self.remover.disposal {
+            Disposal::Delete if self.remover.shred.is_some() => "Shredding",
+            Disposal::Delete => "Deleting",
+            Disposal::Quarantine(_) => "Quarantining",
+            Disposal::Trash => "Trashing",
+            Disposal::MoveTo(_) => "Moving",
+        }
+    }
+
+    fn batch_size(&self) -> usize {
+        if self.grouped {
+            GROUP_SIZE
+        } else {
+            1
+        }
+    }
+
+    fn resolve(&mut self, target: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let filename = target.strip_prefix("file://").unwrap_or(target);
+        self.note_filesystem(filename);
+        #[cfg(unix)]
+        if self.paranoid {
+            self.identities
+                .insert(filename.to_string(), super::paranoid::identify(filename)?);
+        }
+
+        if self.is_tree(filename) {
+            // A directory tree is sized as everything in it
+            let stats = walker::measure(Path::new(filename), self.remover.dir_links)?;
+            log::debug!(
+                "{filename} contains {} files in {} directories.",
+                stats.file_count,
+                stats.dir_count
+            );
+            self.trees.insert(filename.to_string(), stats);
+            return Ok(vec![Entry {
+                name: filename.to_string(),
+                size: stats.size,
+            }]);
+        }
+        Ok(vec![self.entry(filename)?])
+    }
+
+    fn prefetch(&mut self, targets: &[&str]) {
+        let filenames: Vec<&str> = targets
+            .iter()
+            .map(|target| target.strip_prefix("file://").unwrap_or(target))
+            .collect();
+        let threads = if filenames.iter().any(|filename| is_on_network(filename)) {
+            NETWORK_PREFETCH_THREADS
+        } else {
+            PREFETCH_THREADS
+        };
+        let chunk_size = filenames.len().div_ceil(threads).max(1);
+        let file_timeout = self.file_timeout;
+
+        // Files that can't be looked up are left out, and fail again when they are resolved
+        self.prefetched = std::thread::scope(|scope| {
+            let workers: Vec<_> = filenames
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|filename| {
+                                let limit = timeout_for(filename, file_timeout);
+                                let metadata = lookup(filename, limit).ok()?;
+                                Some((filename.to_string(), metadata))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_default())
+                .collect()
+        });
+    }
+
+    fn clear_prefetched(&mut self) {
+        self.prefetched.clear();
+    }
+
+    fn file_type(&mut self, entry: &Entry) -> Result<Option<FileType>, Box<dyn Error>> {
+        let file_type = self.metadata(&entry.name)?.file_type();
+
+        Ok(if file_type.is_symlink() {
+            Some(FileType::Symlink)
+        } else if file_type.is_dir() {
+            Some(FileType::Dir)
+        } else if file_type.is_file() {
+            Some(FileType::File)
+        } else {
+            None
+        })
+    }
+
+    fn time(
+        &mut self,
+        entry: &Entry,
+        field: TimeField,
+    ) -> Result<Option<SystemTime>, Box<dyn Error>> {
+        let metadata = self.metadata(&entry.name)?;
+
+        Ok(match field {
+            TimeField::Modified => metadata.modified().ok(),
+            TimeField::Accessed => metadata.accessed().ok(),
+            TimeField::Created => metadata.created().ok(),
+            #[cfg(unix)]
+            TimeField::Changed => {
+                use std::os::unix::fs::MetadataExt;
+                let changed = std::time::Duration::new(
+                    u64::try_from(metadata.ctime()).unwrap_or_default(),
+                    u32::try_from(metadata.ctime_nsec()).unwrap_or_default(),
+                );
+                SystemTime::UNIX_EPOCH.checked_add(changed)
+            }
+            #[cfg(not(unix))]
+            TimeField::Changed => None,
+        })
+    }
+
+    fn shared_size(&mut self, entry: &Entry) -> Result<u64, Box<dyn Error>> {
+        let metadata = fs::symlink_metadata(&entry.name)?;
+        if !metadata.is_file() {
+            return Ok(0);
+        }
+
+        #[cfg(target_os = "linux")]
+        return Ok(crate::extents::shared_size(Path::new(&entry.name))?.min(metadata.len()));
+        #[cfg(not(target_os = "linux"))]
+        Ok(0)
+    }
+
+    fn hard_links(&mut self, entry: &Entry) -> Option<HardLinks> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = fs::symlink_metadata(&entry.name).ok()?;
+            (metadata.is_file() && metadata.nlink() > 1).then(|| HardLinks {
+                id: (metadata.dev(), metadata.ino()),
+                count: metadata.nlink(),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = entry;
+            None
+        }
+    }
+
+    fn stamp(&mut self, entry: &Entry) -> Result<Option<Stamp>, Box<dyn Error>> {
+        // For a directory tree, this is the top directory itself, which changes when entries
+        // are added to or removed from it but not when anything further down changes
+        let metadata = self.metadata(&entry.name)?;
+        #[cfg(unix)]
+        let id = {
+            use std::os::unix::fs::MetadataExt;
+            Some((metadata.dev(), metadata.ino()))
+        };
+        #[cfg(not(unix))]
+        let id = None;
+
+        Ok(Some(Stamp {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            id,
+        }))
+    }
+
+    fn exists(&mut self, entry: &Entry) -> Result<Option<bool>, Box<dyn Error>> {
+        // What was prefetched is from before the removal
+        match lookup(&entry.name, timeout_for(&entry.name, self.file_timeout)) {
+            Ok(_) => Ok(Some(true)),
+            Err(err)
+                if err
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|err| err.kind() == ErrorKind::NotFound) =>
+            {
+                Ok(Some(false))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn tree_stats(&mut self, entry: &Entry) -> Option<TreeStats> {
+        self.trees.get(&entry.name).copied()
+    }
+
+    fn copied(&mut self, entry: &Entry) -> Option<Copied> {
+        self.remover.copied.lock().ok()?.remove(&entry.name)
+    }
+
+    fn dirs_kept(&mut self, entry: &Entry) -> usize {
+        self.remover
+            .dirs_kept
+            .lock()
+            .ok()
+            .and_then(|mut all| all.remove(&entry.name))
+            .unwrap_or_default()
+    }
+
+    fn security(&mut self, entry: &Entry) -> Option<Security> {
+        Some(security::capture(Path::new(&entry.name)))
+    }
+
+    fn filesystem_type(&mut self, entry: &Entry) -> Option<&'static str> {
+        mounts::filesystem_of(Path::new(&entry.name)).map(|filesystem| filesystem.fs_type.as_str())
+    }
+
+    fn hazard(&mut self, entry: &Entry) -> Option<Hazard> {
+        hazard::check(Path::new(&entry.name))
+    }
+
+    fn holders(&mut self, entry: &Entry) -> Result<Vec<Holder>, Box<dyn Error>> {
+        // Directory trees are removed as a whole, so only files are looked for
+        if fs::symlink_metadata(&entry.name)?.is_dir() {
+            return Ok(Vec::new());
+        }
+        self.open_files.holders(Path::new(&entry.name))
+    }
+
+    fn allocated_size(&mut self, entry: &Entry) -> Result<Option<u64>, Box<dyn Error>> {
+        #[cfg(unix)]
+        {
+            let metadata = fs::symlink_metadata(&entry.name)?;
+            // Blocks are counted in 512-byte units, whatever the file system's block size
+            Ok(metadata
+                .is_file()
+                .then(|| std::os::unix::fs::MetadataExt::blocks(&metadata) * 512))
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = entry;
+            Ok(None)
+        }
+    }
+
+    fn open(&mut self, entry: &Entry) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        Ok(Box::new(BufReader::new(File::open(&entry.name)?)))
+    }
+
+    fn archive(&mut self, entry: &Entry, archive: &mut Archive) -> Result<(), Box<dyn Error>> {
+        if self.is_tree(&entry.name) {
+            archive.add_dir(&entry.name)
+        } else {
+            archive.add_file(&entry.name)
+        }
+    }
+
+    fn remove(&mut self, entries: &[Entry]) -> Result<RemoveResults, Box<dyn Error>> {
+        #[cfg(unix)]
+        let grouped = self
+            .grouped
+            .then(|| self.remover.remove_by_directory(entries));
+        #[cfg(not(unix))]
+        let grouped = None;
+
+        let results: RemoveResults = match grouped {
+            Some(results) => results,
+            None => entries
+                .iter()
+                .map(|entry| {
+                    let identity = self.identity(&entry.name)?;
+                    let remover = Arc::clone(&self.remover);
+                    let entry = entry.clone();
+                    timeout::run(timeout_for(&entry.name, self.file_timeout), move || {
+                        remover.remove_unlocking(&entry, identity)
+                    })
+                })
+                .collect(),
+        };
+
+        if self.sync {
+            for (entry, result) in entries.iter().zip(&results) {
+                let Ok(removed) = result else {
+                    continue;
+                };
+                self.unsynced.extend(parent_of(Path::new(&entry.name)));
+                if let Removed::Quarantined(target) | Removed::Moved(target) = removed {
+                    self.unsynced.extend(parent_of(target));
+                }
+            } // for entry
+        }
+
+        #[cfg(target_os = "linux")]
+        if self.discard {
+            for (entry, result) in entries.iter().zip(&results) {
+                if !matches!(result, Ok(Removed::Deleted)) {
+                    continue;
+                }
+                let Some(dir) = parent_of(Path::new(&entry.name)) else {
+                    continue;
+                };
+                match crate::discard::mount_point(&dir) {
+                    Ok(mount) => {
+                        self.untrimmed.insert(mount);
+                    }
+                    Err(err) => {
+                        log::debug!("Unable to find the mount point of {}: {err}", dir.display())
+                    }
+                }
+            } // for entry
+        }
+
+        Ok(results)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        // Each directory is synced once, however many files were removed from it
+        for dir in std::mem::take(&mut self.unsynced) {
+            // The server decides when the data is written, and syncing only waits on it
+            if let Some(filesystem) =
+                mounts::filesystem_of(&dir).filter(|filesystem| filesystem.is_network())
+            {
+                log::debug!(
+                    "Not syncing {}, which is on {}",
+                    dir.display(),
+                    filesystem.fs_type
+                );
+                continue;
+            }
+            log::debug!("Syncing {}", dir.display());
+            File::open(&dir)
+                .and_then(|dir| dir.sync_all())
+                .map_err(|err| format!("Unable to sync {}: {err}", dir.display()))?;
+        } // for dir
+
+        // The files are gone either way, so a failed trim doesn't fail the run
+        #[cfg(target_os = "linux")]
+        for mount in std::mem::take(&mut self.untrimmed) {
+            match crate::discard::trim(&mount) {
+                Ok(trimmed) => log::info!("{trimmed}"),
+                Err(err) => log::warn!("{err}"),
+            }
+        } // for mount
+
+        Ok(())
+    }
+}
+
+impl Remover {
+    /// Keeps what had to be copied to dispose of an entry, if anything, for the summary.
+    fn note_copied(&self, entry: &Entry, copied: Copied) {
+        if copied.file_count == 0 {
+            return;
+        }
+        if let Ok(mut all) = self.copied.lock() {
+            all.insert(entry.name.clone(), copied);
+        }
+    }
+
+    /// Keeps how many directories of a tree removed had to be left, if any, for the summary.
+    fn note_kept(&self, entry: &Entry, stats: TreeStats) {
+        if stats.dirs_kept_count == 0 {
+            return;
+        }
+        if let Ok(mut all) = self.dirs_kept.lock() {
+            all.insert(entry.name.clone(), stats.dirs_kept_count);
+        }
+    }
+
+    /// Checks that a file found earlier hasn't been swapped for something else since,
+    /// if `--paranoid` is set.
+    fn verify(filename: &str, identity: Option<Identity>) -> Result<(), Box<dyn Error>> {
+        #[cfg(unix)]
+        if let Some(identity) = identity {
+            super::paranoid::verify(filename, identity)?;
+        }
+        #[cfg(not(unix))]
+        let _ = (filename, identity);
+        Ok(())
+    }
+
+    /// Removes a batch of entries, grouped by the directory they are in. Each directory is
+    /// opened once, and the files in it are removed relative to it, which saves looking up the
+    /// whole path of each file in large, flat directories such as mail spools. Directories,
+    /// files on NFS and files that can't be removed this way are removed by `remove_unlocking`
+    /// instead, so they are dealt with, and fail, as they would otherwise. The results are in
+    /// the order of the entries.
+    #[cfg(unix)]
+    fn remove_by_directory(&self, entries: &[Entry]) -> RemoveResults {
+        use rustix::fs::{self as rfs, AtFlags, Mode, OFlags};
+
+        let mut by_dir: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if let Some(dir) = parent_of(Path::new(&entry.name)) {
+                by_dir.entry(dir).or_default().push(index);
+            }
+        } // for entry
+
+        let mut results: Vec<Option<Result<Removed, Box<dyn Error>>>> =
+            entries.iter().map(|_| None).collect();
+        for (dir, indices) in by_dir {
+            // On NFS, the files still open are watched for after they're removed
+            let dir_fd = if mounts::filesystem_of(&dir).is_some_and(mounts::Filesystem::is_nfs) {
+                None
+            } else {
+                rfs::open(
+                    &dir,
+                    OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC,
+                    Mode::empty(),
+                )
+                .ok()
+            };
+
+            for index in indices {
+                let entry = &entries[index];
+                let unlinked = dir_fd
+                    .as_ref()
+                    .zip(Path::new(&entry.name).file_name())
+                    .is_some_and(|(dir_fd, name)| {
+                        rfs::statat(dir_fd, name, AtFlags::SYMLINK_NOFOLLOW).is_ok_and(|stat| {
+                            rfs::FileType::from_raw_mode(stat.st_mode as _)
+                                != rfs::FileType::Directory
+                        }) && rfs::unlinkat(dir_fd, name, AtFlags::empty()).is_ok()
+                    });
+                results[index] = Some(if unlinked {
+                    Ok(Removed::Deleted)
+                } else {
+                    self.remove_unlocking(entry, None)
+                });
+            } // for index
+        } // for dir
+
+        // Entries without a directory, such as `/`, are left to fail as they would otherwise
+        results
+            .into_iter()
+            .zip(entries)
+            .map(|(result, entry)| result.unwrap_or_else(|| self.remove_unlocking(entry, None)))
+            .collect()
+    }
+
+    /// Returns true if the entry is a directory tree to be removed as a whole.
+    fn is_tree(&self, filename: &str) -> bool {
+        self.recursive && fs::symlink_metadata(filename).is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    /// Removes a single entry like `remove_watched`. When that's refused because the file, or
+    /// the directory it's in, is immutable or append-only, the error says so; with
+    /// `--force-immutable`, the flags are cleared and the removal tried again. The directory's
+    /// flags are put back afterwards.
+    fn remove_unlocking(
+        &self,
+        entry: &Entry,
+        identity: Option<Identity>,
+    ) -> Result<Removed, Box<dyn Error>> {
+        let err = match self.remove_watched(entry, identity) {
+            Err(err) if is_permission_denied(&*err) => err,
+            result => return result,
+        };
+
+        #[cfg(unix)]
+        {
+            let locks = crate::immutable::locks(Path::new(&entry.name));
+            match locks.first() {
+                None => return Err(err),
+                Some(locked) if !self.force_immutable => return Err(Box::new(locked.clone())),
+                Some(_) => {}
+            }
+
+            for locked in &locks {
+                locked.flags.unlocked().write(&locked.path)?;
+                log::info!(
+                    "Cleared the {} flag of {}",
+                    locked.flag,
+                    locked.path.display()
+                );
+            } // for locked
+            let result = self.remove_watched(entry, identity);
+            for locked in locks.iter().filter(|locked| locked.path != locked.target) {
+                if let Err(err) = locked.flags.write(&locked.path) {
+                    log::warn!("{err}");
+                }
+            } // for locked
+            result
+        }
+
+        #[cfg(not(unix))]
+        Err(err)
+    }
+
+    /// Removes a single entry like `remove_retrying`. On NFS, a file that was still open is
+    /// renamed to `.nfsXXXX` rather than removed, so its space isn't freed until it's closed;
+    /// such files are looked for afterwards, by their inode.
+    fn remove_watched(
+        &self,
+        entry: &Entry,
+        identity: Option<Identity>,
+    ) -> Result<Removed, Box<dyn Error>> {
+        #[cfg(unix)]
+        if mounts::filesystem_of(Path::new(&entry.name)).is_some_and(mounts::Filesystem::is_nfs) {
+            use std::os::unix::fs::MetadataExt;
+
+            let metadata = fs::symlink_metadata(&entry.name)?;
+            if !metadata.is_dir() {
+                let removed = self.remove_retrying(entry, identity)?;
+                if removed == Removed::Deleted {
+                    if let Some(renamed) = silly_renamed(Path::new(&entry.name), metadata.ino()) {
+                        return Ok(Removed::PendingRelease(renamed));
+                    }
+                }
+                return Ok(removed);
+            }
+        }
+        self.remove_retrying(entry, identity)
+    }
+
+    /// Removes a single entry like `remove_one`, trying again a few times if a network file
+    /// system reports a stale file handle, as NFS does while a server fails over.
+    fn remove_retrying(
+        &self,
+        entry: &Entry,
+        identity: Option<Identity>,
+    ) -> Result<Removed, Box<dyn Error>> {
+        let mut delay = STALE_RETRY_DELAY;
+        for retry in 1..=STALE_RETRIES {
+            match self.remove_one(entry, identity) {
+                Err(err) if is_stale(&*err) => {
+                    log::debug!(
+                        "Stale file handle removing {}, retry {retry} of {STALE_RETRIES}",
+                        entry.name
+                    );
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        } // for retry
+        self.remove_one(entry, identity)
+    }
+
+    /// Removes a single entry, shredding it first if requested.
+    fn remove_one(
+        &self,
+        entry: &Entry,
+        identity: Option<Identity>,
+    ) -> Result<Removed, Box<dyn Error>> {
+        // Shredding overwrites the data in regular files before they are deleted
+        let shred = match self.shred {
+            Some(method)
+                if matches!(self.disposal, Disposal::Delete)
+                    && fs::symlink_metadata(&entry.name)?.is_file() =>
+            {
+                Some(method)
+            }
+            _ => None,
+        };
+        if let Some(method) = shred {
+            Self::verify(&entry.name, identity)?;
+            shred::shred(Path::new(&entry.name), method, self.shred_verify)?;
+        }
+
+        // Scrambling the name renames the file before it's deleted, so it's deleted here
+        if self.scramble_name
+            && matches!(self.disposal, Disposal::Delete)
+            && !fs::symlink_metadata(&entry.name)?.is_dir()
+        {
+            Self::verify(&entry.name, identity)?;
+            fs::remove_file(shred::scramble_name(Path::new(&entry.name))?)?;
+            return Ok(shred.map_or(Removed::Deleted, Removed::Shredded));
+        }
+
+        match (self.dispose_of(entry, identity)?, shred) {
+            (Removed::Deleted, Some(method)) => Ok(Removed::Shredded(method)),
+            (removed, _) => Ok(removed),
+        }
+    }
+
+    /// Removes a single entry, disposing of it as configured.
+    fn dispose_of(
+        &self,
+        entry: &Entry,
+        identity: Option<Identity>,
+    ) -> Result<Removed, Box<dyn Error>> {
+        match (&self.disposal, identity) {
+            #[cfg(target_os = "linux")]
+            (Disposal::Delete, _)
+                if self.is_tree(&entry.name)
+                    && crate::btrfs::is_subvolume(Path::new(&entry.name))? =>
+            {
+                // Recursing into a subvolume would be slow, and fail at the end
+                if !self.allow_subvolumes {
+                    return Err(format!(
+                        "{} is a btrfs subvolume. Use --allow-subvolumes to delete it.",
+                        entry.name
+                    )
+                    .into());
+                }
+                Self::verify(&entry.name, identity)?;
+                crate::btrfs::delete_subvolume(Path::new(&entry.name))?;
+                Ok(Removed::Deleted)
+            }
+            #[cfg(unix)]
+            (Disposal::Delete, Some(identity)) => {
+                let removed =
+                    super::paranoid::remove(&entry.name, identity, self.recursive, self.special)?;
+                if let Some(stats) = removed {
+                    self.note_kept(entry, stats);
+                }
+                Ok(Removed::Deleted)
+            }
+            (Disposal::Delete, _) => {
+                // Without --recursive, directories can only be removed when empty,
+                // e.g. when selected with --type d
+                if self.is_tree(&entry.name) {
+                    let stats =
+                        walker::remove_tree(Path::new(&entry.name), self.special, self.dir_links)?;
+                    log::debug!(
+                        "Removed {} files and {} directories below {}",
+                        stats.file_count,
+                        stats.dir_count,
+                        entry.name
+                    );
+                    self.note_kept(entry, stats);
+                } else {
+                    // A link to a directory is removed like one, leaving what it points to
+                    let metadata = fs::symlink_metadata(&entry.name)?;
+                    if metadata.is_dir() || walker::is_dir_link(&metadata) {
+                        fs::remove_dir(&entry.name)?;
+                    } else {
+                        fs::remove_file(&entry.name)?;
+                    }
+                }
+                Ok(Removed::Deleted)
+            }
+            (Disposal::Quarantine(quarantine), _) => {
+                Self::verify(&entry.name, identity)?;
+                let (target, copied) = quarantine.move_file(&entry.name)?;
+                self.note_copied(entry, copied);
+                Ok(Removed::Quarantined(target))
+            }
+            (Disposal::Trash, _) => {
+                Self::verify(&entry.name, identity)?;
+                #[cfg(all(unix, not(target_os = "macos")))]
+                {
+                    let (trashed, copied) = crate::trash::freedesktop::delete(&entry.name)?;
+                    log::debug!("Moved {} to {}", entry.name, trashed.display());
+                    self.note_copied(entry, copied);
+                    Ok(Removed::Trashed(Some(trashed)))
+                }
+                #[cfg(target_os = "macos")]
+                {
+                    crate::trash::macos::delete(&entry.name)?;
+                    Ok(Removed::Trashed(None))
+                }
+                #[cfg(not(unix))]
+                {
+                    trash::delete(&entry.name)?;
+                    Ok(Removed::Trashed(None))
+                }
+            }
+            (Disposal::MoveTo(dir), _) => {
+                Self::verify(&entry.name, identity)?;
+                let (target, copied) = move_into(&entry.name, dir)?;
+                self.note_copied(entry, copied);
+                Ok(Removed::Moved(target))
+            }
+        }
+    }
+}
+
+/// Looks up the metadata of a file without following a symbolic link, giving up after
+/// `--file-timeout`.
+fn lookup(filename: &str, limit: Option<Duration>) -> Result<fs::Metadata, Box<dyn Error>> {
+    let filename = filename.to_string();
+    timeout::run(limit, move || Ok(fs::symlink_metadata(filename)?))
+}
+
+/// Returns how long a file system operation on a file may take: `--file-timeout`, made longer
+/// for files on a network file system.
+fn timeout_for(filename: &str, limit: Option<Duration>) -> Option<Duration> {
+    match limit {
+        Some(limit) if is_on_network(filename) => Some(limit * NETWORK_TIMEOUT_FACTOR),
+        limit => limit,
+    }
+}
+
+/// Tells whether a file is on a network file system.
+fn is_on_network(filename: &str) -> bool {
+    mounts::filesystem_of(Path::new(filename)).is_some_and(mounts::Filesystem::is_network)
+}
+
+/// Returns the `.nfs` file the NFS client renamed a deleted file to, found by its inode in the
+/// directory the file was in.
+#[cfg(unix)]
+fn silly_renamed(path: &Path, inode: u64) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::read_dir(parent_of(path)?)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|dir_entry| dir_entry.file_name().to_string_lossy().starts_with(".nfs"))
+        .find(|dir_entry| {
+            dir_entry
+                .metadata()
+                .is_ok_and(|metadata| metadata.ino() == inode)
+        })
+        .map(|dir_entry| dir_entry.path())
+}
+
+/// Tells whether an error is a refusal to change a file, as when it's immutable.
+fn is_permission_denied(err: &(dyn Error + 'static)) -> bool {
+    #[cfg(unix)]
+    if let Some(errno) = err.downcast_ref::<rustix::io::Errno>() {
+        return matches!(*errno, rustix::io::Errno::PERM | rustix::io::Errno::ACCESS);
+    }
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|err| err.kind() == ErrorKind::PermissionDenied)
+}
+
+/// Tells whether an error is a stale file handle (`ESTALE`) from a network file system.
+fn is_stale(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|err| err.kind() == ErrorKind::StaleNetworkFileHandle)
+}
+
+/// Returns the directory a file is in, as a path that can be opened.
+fn parent_of(path: &Path) -> Option<PathBuf> {
+    match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Some(PathBuf::from(".")),
+        parent => parent.map(Path::to_path_buf),
+    }
+}
+
+/// Moves a file into `dir`, creating the directory if needed. An existing file with the same
+/// name is never replaced. Files on another file system are copied, checked and then deleted.
+/// Returns where the file ended up, and what had to be copied.
+fn move_into(filename: &str, dir: &Path) -> Result<(PathBuf, Copied), Box<dyn Error>> {
+    let name = Path::new(filename)
+        .file_name()
+        .ok_or_else(|| format!("Unable to move {filename}: no file name."))?;
+    let target = dir.join(name);
+    if target.symlink_metadata().is_ok() {
+        return Err(format!(
+            "Unable to move {filename}: {} already exists.",
+            target.display()
+        )
+        .into());
+    }
+
+    fs::create_dir_all(dir)?;
+    let copied = transfer::move_across(Path::new(filename), &target)?;
+
+    Ok((target, copied))
+}
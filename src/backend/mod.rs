@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::age::TimeField;
+use crate::archive::Archive;
+use crate::deleter::Settings;
+use crate::hazard::Hazard;
+use crate::in_use::Holder;
+use crate::quarantine::Quarantine;
+use crate::security::Security;
+use crate::shred::Method;
+use crate::transfer::Copied;
+use crate::walker::TreeStats;
+
+mod local;
+#[cfg(unix)]
+mod paranoid;
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "sftp")]
+mod sftp;
+
+pub use local::{Disposal, Local};
+
+/// Something a backend has found to remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The name of the entry as shown in the output, the report and the audit log.
+    /// For local files this is the path; for remote targets it is the full URI.
+    pub name: String,
+
+    /// The size of the entry in bytes.
+    pub size: u64,
+}
+
+/// An entry's size and modification time as seen at one moment, used to spot entries that
+/// change between being found and being removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+
+    /// The device and inode number of the entry, where the backend knows them, so an entry
+    /// replaced by another one of the same size and time can be told apart.
+    pub id: Option<(u64, u64)>,
+}
+
+/// A file with more than one hard link, so that its bytes are only freed once the last of them
+/// is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HardLinks {
+    /// The device and inode number of the file.
+    pub id: (u64, u64),
+
+    /// The number of links the file has.
+    pub count: u64,
+}
+
+/// The kind of an entry, as used by `--type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl FileType {
+    /// The letter `find` uses for this kind of entry.
+    pub fn letter(self) -> &'static str {
+        match self {
+            Self::File => "f",
+            Self::Dir => "d",
+            Self::Symlink => "l",
+        }
+    }
+}
+
+/// What a backend did with an entry it removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Removed {
+    /// The entry is gone.
+    Deleted,
+
+    /// The entry was moved into the quarantine, to the given location.
+    Quarantined(PathBuf),
+
+    /// The entry was moved to the given location instead of being deleted.
+    Moved(PathBuf),
+
+    /// The entry was moved to the trash, to the given location where rdel manages the trash
+    /// itself rather than handing the entry to the system.
+    Trashed(Option<PathBuf>),
+
+    /// The entry's data was overwritten using the method before it was deleted.
+    Shredded(Method),
+
+    /// The entry was deleted while still open somewhere, so the NFS client kept it as the given
+    /// `.nfs` file, which holds on to its space until it's closed.
+    PendingRelease(PathBuf),
+}
+
+/// The outcome for each entry in a batch passed to `Backend::remove`.
+pub type RemoveResults = Vec<Result<Removed, Box<dyn Error>>>;
+
+/// A place files can be removed from.
+///
+/// The deleter resolves targets into entries using the backend, applies the filters and checksums
+/// (reading the contents through the backend where needed), and then asks the backend to remove
+/// the entries in batches. Counting, output, the report and the audit log are handled by the
+/// deleter.
+pub trait Backend: Send {
+    /// The verb used in the detail output, e.g. "Deleting".
+    fn action(&self) -> &'static str {
+        "Deleting"
+    }
+
+    /// How many entries the backend wants to remove at once.
+    fn batch_size(&self) -> usize {
+        1
+    }
+
+    /// Works out which entries a target refers to. A target may expand to many entries,
+    /// or none at all.
+    fn resolve(&mut self, target: &str) -> Result<Vec<Entry>, Box<dyn Error>>;
+
+    /// Looks up what is needed to resolve and filter the targets ahead of time, e.g. in parallel,
+    /// where that is quicker than one at a time. By default nothing is looked up.
+    fn prefetch(&mut self, _targets: &[&str]) {}
+
+    /// Forgets what `prefetch` looked up, so the details checked just before removal are current.
+    fn clear_prefetched(&mut self) {}
+
+    /// Returns the kind of the entry. By default everything is a regular file, as most backends
+    /// only find files.
+    fn file_type(&mut self, _entry: &Entry) -> Result<Option<FileType>, Box<dyn Error>> {
+        Ok(Some(FileType::File))
+    }
+
+    /// Returns one of the entry's timestamps, or `None` if the backend doesn't know it.
+    fn time(
+        &mut self,
+        _entry: &Entry,
+        _field: TimeField,
+    ) -> Result<Option<SystemTime>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Looks up the entry's size and modification time as they are now, rather than as they were
+    /// when it was found. `None` if the backend can't look them up again, e.g. when it only knows
+    /// them from a listing.
+    fn stamp(&mut self, _entry: &Entry) -> Result<Option<Stamp>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Tells whether the entry is still there, for `--verify`. `None` if the backend can't
+    /// tell.
+    fn exists(&mut self, _entry: &Entry) -> Result<Option<bool>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Returns how many of the entry's bytes are shared with other files through reflinked
+    /// extents, and so won't be freed when it's removed. By default nothing is shared.
+    fn shared_size(&mut self, _entry: &Entry) -> Result<u64, Box<dyn Error>> {
+        Ok(0)
+    }
+
+    /// Returns the entry's hard links if it has more than one. By default no entry has.
+    fn hard_links(&mut self, _entry: &Entry) -> Option<HardLinks> {
+        None
+    }
+
+    /// Returns how much space the entry takes up on the disk, if the backend knows. This is less
+    /// than the size for sparse files.
+    fn allocated_size(&mut self, _entry: &Entry) -> Result<Option<u64>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Returns what is in the entry, if it's a directory tree removed as a whole. By default
+    /// entries are never trees.
+    fn tree_stats(&mut self, _entry: &Entry) -> Option<TreeStats> {
+        None
+    }
+
+    /// Returns what had to be copied to another file system, rather than moved, to dispose of
+    /// the entry, e.g. into a trash on another file system. By default nothing is.
+    fn copied(&mut self, _entry: &Entry) -> Option<Copied> {
+        None
+    }
+
+    /// Returns how many directories of a directory tree removed had to be left, top of the tree
+    /// included, as special files were kept in them. By default none are.
+    fn dirs_kept(&mut self, _entry: &Entry) -> usize {
+        0
+    }
+
+    /// Returns the type of file system the entry is on, e.g. `ext4` or `nfs4`, if the backend
+    /// knows. By default it doesn't.
+    fn filesystem_type(&mut self, _entry: &Entry) -> Option<&'static str> {
+        None
+    }
+
+    /// Returns who could get at the entry, for the audit log. By default the backend can't tell.
+    fn security(&mut self, _entry: &Entry) -> Option<Security> {
+        None
+    }
+
+    /// Returns what makes removing the entry costlier than usual, such as it being a setuid
+    /// program, so it can be warned about. By default the backend can't tell.
+    fn hazard(&mut self, _entry: &Entry) -> Option<Hazard> {
+        None
+    }
+
+    /// Returns the processes that have the entry open, for `--skip-in-use`. By default no
+    /// entry is in use, as remote backends can't tell.
+    fn holders(&mut self, _entry: &Entry) -> Result<Vec<Holder>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Opens an entry for reading, for checksums, content filters and archiving.
+    fn open(&mut self, entry: &Entry) -> Result<Box<dyn Read + '_>, Box<dyn Error>>;
+
+    /// Copies an entry into the archive. By default the contents are read using `open`.
+    fn archive(&mut self, entry: &Entry, archive: &mut Archive) -> Result<(), Box<dyn Error>> {
+        let mut reader = self.open(entry)?;
+        archive.add_reader(&entry.name, entry.size, &mut reader)
+    }
+
+    /// Removes a batch of entries, returning one result per entry in the same order.
+    /// An outer error means the whole batch failed and the run should stop.
+    fn remove(&mut self, entries: &[Entry]) -> Result<RemoveResults, Box<dyn Error>>;
+
+    /// Called once at the end of the run, e.g. to make the removals durable.
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Returns the scheme of a target, e.g. `s3` for `s3://bucket/key`. Plain paths are `file`.
+pub fn scheme_of(target: &str) -> &str {
+    match target.split_once("://") {
+        Some((scheme, _))
+            if scheme.len() > 1 && scheme.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            scheme
+        }
+        _ => "file",
+    }
+}
+
+/// Returns the canonical form of a target, used to spot the same target given more than once.
+/// Local paths are made absolute with symbolic links resolved; other targets are used as they are.
+pub fn canonical(target: &str) -> String {
+    match scheme_of(target) {
+        "file" => Local::canonical(target),
+        _ => target.to_string(),
+    }
+}
+
+/// Creates the backend that handles targets with the given scheme. `now` is the time the run
+/// goes by, from the deleter's clock, which names the quarantine folder.
+pub fn create(
+    scheme: &str,
+    settings: &Settings,
+    now: SystemTime,
+) -> Result<Box<dyn Backend>, Box<dyn Error>> {
+    if scheme != "file" && settings.quarantine.is_some() {
+        return Err(format!("--quarantine can't be used with {scheme}:// targets.").into());
+    }
+    if scheme != "file" && settings.trash {
+        return Err(format!("--trash can't be used with {scheme}:// targets.").into());
+    }
+
+    match scheme {
+        "file" => Ok(Box::new(Local::new(
+            match &settings.quarantine {
+                Some(dir) => Disposal::Quarantine(Quarantine::new(dir, now)),
+                None if settings.trash => Disposal::Trash,
+                None => Disposal::Delete,
+            },
+            settings,
+        ))),
+
+        #[cfg(feature = "s3")]
+        "s3" => Ok(Box::new(s3::S3::from_env()?)),
+        #[cfg(not(feature = "s3"))]
+        "s3" => Err("S3 targets require rdel to be built with the s3 feature.".into()),
+
+        #[cfg(feature = "sftp")]
+        "sftp" => Ok(Box::new(sftp::Sftp::new(
+            settings.normalize,
+            settings.ignore_case,
+        ))),
+        #[cfg(not(feature = "sftp"))]
+        "sftp" => Err("SFTP targets require rdel to be built with the sftp feature.".into()),
+
+        _ => Err(format!("Unsupported target type: {scheme}://").into()),
+    }
+}
@@ -0,0 +1,125 @@
+use rustix::fs::{self as rfs, AtFlags, Mode, OFlags, Stat};
+use rustix::io::Errno;
+use std::error::Error;
+use std::os::fd::OwnedFd;
+use std::path::Path;
+
+use crate::walker::{self, Special, TreeStats};
+
+/// The device and inode numbers of a file and of the directory it is in, recorded when the file
+/// is found. They are checked again just before the file is removed, so a file or directory
+/// swapped for something else (e.g. a symbolic link into `/etc`) in the meantime is left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    parent: (u64, u64),
+    file: (u64, u64),
+}
+
+/// Records the identity of a file before anything is done to it.
+pub fn identify(filename: &str) -> Result<Identity, Box<dyn Error>> {
+    let (parent, name) = split(filename)?;
+    let parent_fd = open_parent(parent)?;
+
+    Ok(Identity {
+        parent: id_of(&rfs::fstat(&parent_fd)?),
+        file: id_of(&stat_nofollow(&parent_fd, name)?),
+    })
+}
+
+/// Checks that the file, and the directory it is in, are still the ones that were found.
+/// Returns the open directory and the metadata of the file.
+pub fn verify(filename: &str, identity: Identity) -> Result<(OwnedFd, Stat), Box<dyn Error>> {
+    let (parent, name) = split(filename)?;
+    let parent_fd = open_parent(parent)?;
+    if id_of(&rfs::fstat(&parent_fd)?) != identity.parent {
+        return Err(format!(
+            "{} was replaced after {filename} was found. Not removing it.",
+            parent.display()
+        )
+        .into());
+    }
+
+    let stat = stat_nofollow(&parent_fd, name)?;
+    if id_of(&stat) != identity.file {
+        return Err(format!("{filename} was replaced after it was found. Not removing it.").into());
+    }
+
+    Ok((parent_fd, stat))
+}
+
+/// Checks that the file is still the one that was found, and removes it. The file is opened
+/// without following symbolic links and removed relative to the directory it was verified in,
+/// so nothing outside that directory can be affected. Special files in a tree are dealt with as
+/// `special` says. Returns what was removed from the tree, if the file is one.
+pub fn remove(
+    filename: &str,
+    identity: Identity,
+    recursive: bool,
+    special: Special,
+) -> Result<Option<TreeStats>, Box<dyn Error>> {
+    let (parent_fd, stat) = verify(filename, identity)?;
+    let (_, name) = split(filename)?;
+
+    if rfs::FileType::from_raw_mode(stat.st_mode as _) != rfs::FileType::Directory {
+        rfs::unlinkat(&parent_fd, name, AtFlags::empty())?;
+    } else if recursive {
+        return Ok(Some(walker::remove_tree_at(
+            std::os::fd::AsFd::as_fd(&parent_fd),
+            name,
+            Path::new(filename),
+            special,
+        )?));
+    } else {
+        rfs::unlinkat(&parent_fd, name, AtFlags::REMOVEDIR)?;
+    }
+
+    Ok(None)
+}
+
+/// Splits a path into the directory it is in and its name.
+fn split(filename: &str) -> Result<(&Path, &Path), Box<dyn Error>> {
+    let path = Path::new(filename);
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Unable to remove {filename}: no file name."))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    Ok((parent, Path::new(name)))
+}
+
+/// Opens the directory a file is in.
+fn open_parent(parent: &Path) -> Result<OwnedFd, Box<dyn Error>> {
+    rfs::open(
+        parent,
+        OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )
+    .map_err(|err| format!("Unable to open {}: {err}", parent.display()).into())
+}
+
+/// Opens a file with O_NOFOLLOW and returns its metadata. A symbolic link is described
+/// itself, as it's the link that gets removed.
+fn stat_nofollow(parent_fd: &OwnedFd, name: &Path) -> Result<Stat, Box<dyn Error>> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = OFlags::PATH | OFlags::NOFOLLOW | OFlags::CLOEXEC;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags =
+        OFlags::RDONLY | OFlags::NOFOLLOW | OFlags::NONBLOCK | OFlags::NOCTTY | OFlags::CLOEXEC;
+
+    match rfs::openat(parent_fd, name, flags, Mode::empty()) {
+        Ok(fd) => Ok(rfs::fstat(&fd)?),
+        // Without O_PATH, opening a symbolic link with O_NOFOLLOW fails
+        Err(Errno::LOOP) => Ok(rfs::statat(parent_fd, name, AtFlags::SYMLINK_NOFOLLOW)?),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns the device and inode numbers from a file's metadata.
+// The field types differ between platforms
+#[allow(clippy::unnecessary_cast)]
+fn id_of(stat: &Stat) -> (u64, u64) {
+    (stat.st_dev as u64, stat.st_ino as u64)
+}
@@ -0,0 +1,257 @@
+use rusty_s3::actions::{DeleteObjectsResponse, ListObjectsV2, ObjectIdentifier};
+use rusty_s3::{Bucket, Credentials, S3Action, UrlStyle};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::error::Error;
+use std::io::Read;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+use super::{Backend, Entry, RemoveResults, Removed};
+use crate::age::TimeField;
+
+/// The URI scheme used for S3 targets.
+const SCHEME: &str = "s3://";
+
+/// The maximum number of keys S3 accepts in a single DeleteObjects request.
+const BATCH_SIZE: usize = 1000;
+
+/// How long signed request URLs stay valid.
+const SIGNATURE_LIFETIME: Duration = Duration::from_secs(15 * 60);
+
+/// An object to delete, with its size as reported by S3.
+#[derive(Debug)]
+struct Object {
+    key: String,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Connection details for S3, read from the standard AWS environment variables.
+struct Client {
+    credentials: Credentials,
+    endpoint: Url,
+    region: String,
+    url_style: UrlStyle,
+}
+
+impl Client {
+    /// Reads `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_SESSION_TOKEN`, `AWS_REGION`
+    /// (or `AWS_DEFAULT_REGION`) and `AWS_ENDPOINT_URL`. A custom endpoint implies path-style URLs,
+    /// as used by most S3-compatible services.
+    fn from_env() -> Result<Self, Box<dyn Error>> {
+        let credentials = Credentials::from_env()
+            .ok_or("AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set to delete from S3.")?;
+        let region = env::var("AWS_REGION")
+            .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|_| "us-east-1".to_string());
+
+        let (endpoint, url_style) = match env::var("AWS_ENDPOINT_URL") {
+            Ok(endpoint) => (endpoint, UrlStyle::Path),
+            Err(_) => (
+                format!("https://s3.{region}.amazonaws.com"),
+                UrlStyle::VirtualHost,
+            ),
+        };
+
+        Ok(Self {
+            credentials,
+            endpoint: endpoint.parse()?,
+            region,
+            url_style,
+        })
+    }
+
+    /// Returns a handle for the named bucket.
+    fn bucket(&self, name: &str) -> Result<Bucket, Box<dyn Error>> {
+        Ok(Bucket::new(
+            self.endpoint.clone(),
+            self.url_style,
+            name.to_string(),
+            self.region.clone(),
+        )?)
+    }
+
+    /// Finds the objects whose keys start with `prefix`. A key without a trailing `/` only
+    /// matches itself, so `s3://bucket/logs` doesn't also pick up `s3://bucket/logs-old`.
+    fn list(&self, bucket: &Bucket, prefix: &str) -> Result<Vec<Object>, Box<dyn Error>> {
+        let mut objects = Vec::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut action = ListObjectsV2::new(bucket, Some(&self.credentials));
+            action.with_prefix(prefix);
+            if let Some(token) = &continuation_token {
+                action.with_continuation_token(token.clone());
+            }
+
+            let url = action.sign(SIGNATURE_LIFETIME);
+            let body = ureq::get(url.as_str())
+                .call()?
+                .body_mut()
+                .read_to_string()?;
+            let response = ListObjectsV2::parse_response(&body)?;
+
+            objects.extend(
+                response
+                    .contents
+                    .into_iter()
+                    .filter(|content| prefix.ends_with('/') || content.key == prefix)
+                    .map(|content| Object {
+                        modified: humantime::parse_rfc3339_weak(&content.last_modified).ok(),
+                        key: content.key,
+                        size: content.size,
+                    }),
+            );
+
+            match response.next_continuation_token {
+                Some(token) => continuation_token = Some(token),
+                None => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
+    /// Deletes a batch of objects. Returns the keys that S3 reported as failed along with the
+    /// reason.
+    fn delete(
+        &self,
+        bucket: &Bucket,
+        keys: &[ObjectIdentifier],
+    ) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let mut action = bucket.delete_objects(Some(&self.credentials), keys.iter());
+        action.set_quiet(true);
+        let url = action.sign(SIGNATURE_LIFETIME);
+        let (body, content_md5) = action.body_with_md5();
+
+        let response = ureq::post(url.as_str())
+            .header("Content-MD5", &content_md5)
+            .send(body)?
+            .body_mut()
+            .read_to_string()?;
+        let response = DeleteObjectsResponse::parse(&response)?;
+
+        Ok(response
+            .errors
+            .into_iter()
+            .map(|error| (error.key, format!("{}: {}", error.code, error.message)))
+            .collect())
+    }
+}
+
+/// Splits `s3://bucket/key` into the bucket name and the key.
+fn split_target(target: &str) -> Result<(&str, &str), Box<dyn Error>> {
+    target
+        .strip_prefix(SCHEME)
+        .and_then(|rest| rest.split_once('/'))
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or_else(|| format!("Invalid S3 target {target}. Expected s3://bucket/key.").into())
+}
+
+/// Objects in S3 buckets. A target ending in `/` refers to every object below that prefix.
+/// Objects are removed in batches using DeleteObjects.
+pub struct S3 {
+    client: Client,
+
+    /// The last modified times from the listings, by entry name.
+    modified: HashMap<String, SystemTime>,
+}
+
+impl S3 {
+    /// Sets up the backend using the standard AWS environment variables.
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            client: Client::from_env()?,
+            modified: HashMap::new(),
+        })
+    }
+}
+
+impl Backend for S3 {
+    fn batch_size(&self) -> usize {
+        BATCH_SIZE
+    }
+
+    fn resolve(&mut self, target: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let (bucket_name, key) = split_target(target)?;
+        let bucket = self.client.bucket(bucket_name)?;
+        let objects = self.client.list(&bucket, key)?;
+        if objects.is_empty() {
+            log::warn!("No objects found for {target}.");
+        }
+
+        let mut entries = Vec::with_capacity(objects.len());
+        for object in objects {
+            let name = format!("{SCHEME}{bucket_name}/{}", object.key);
+            if let Some(modified) = object.modified {
+                self.modified.insert(name.clone(), modified);
+            }
+            entries.push(Entry {
+                name,
+                size: object.size,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn time(
+        &mut self,
+        entry: &Entry,
+        field: TimeField,
+    ) -> Result<Option<SystemTime>, Box<dyn Error>> {
+        // S3 only records when an object was last written
+        Ok(match field {
+            TimeField::Modified => self.modified.get(&entry.name).copied(),
+            _ => None,
+        })
+    }
+
+    fn exists(&mut self, entry: &Entry) -> Result<Option<bool>, Box<dyn Error>> {
+        // Listing just the key works with the same permissions as finding it did
+        let (bucket_name, key) = split_target(&entry.name)?;
+        let bucket = self.client.bucket(bucket_name)?;
+        Ok(Some(!self.client.list(&bucket, key)?.is_empty()))
+    }
+
+    fn open(&mut self, entry: &Entry) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        let (bucket_name, key) = split_target(&entry.name)?;
+        let bucket = self.client.bucket(bucket_name)?;
+        let url = bucket
+            .get_object(Some(&self.client.credentials), key)
+            .sign(SIGNATURE_LIFETIME);
+
+        Ok(Box::new(
+            ureq::get(url.as_str()).call()?.into_body().into_reader(),
+        ))
+    }
+
+    fn remove(&mut self, entries: &[Entry]) -> Result<RemoveResults, Box<dyn Error>> {
+        // Each DeleteObjects request goes to a single bucket
+        let mut by_bucket: BTreeMap<&str, Vec<ObjectIdentifier>> = BTreeMap::new();
+        for entry in entries {
+            let (bucket_name, key) = split_target(&entry.name)?;
+            by_bucket
+                .entry(bucket_name)
+                .or_default()
+                .push(ObjectIdentifier::new(key.to_string()));
+        }
+
+        let mut failed: BTreeMap<String, String> = BTreeMap::new();
+        for (bucket_name, keys) in by_bucket {
+            let bucket = self.client.bucket(bucket_name)?;
+            for (key, reason) in self.client.delete(&bucket, &keys)? {
+                failed.insert(format!("{SCHEME}{bucket_name}/{key}"), reason);
+            }
+        }
+
+        Ok(entries
+            .iter()
+            .map(|entry| match failed.remove(&entry.name) {
+                Some(reason) => Err(reason.into()),
+                None => Ok(Removed::Deleted),
+            })
+            .collect())
+    }
+}
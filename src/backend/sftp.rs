@@ -0,0 +1,326 @@
+use glob::{MatchOptions, Pattern};
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Session};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::io::Read;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::age::TimeField;
+use crate::name;
+use crate::normalize::{self, Normalization};
+
+use super::{Backend, Entry, RemoveResults, Removed, Stamp};
+
+/// The URI scheme used for SFTP targets.
+const SCHEME: &str = "sftp://";
+
+/// The default SSH port.
+const DEFAULT_PORT: u16 = 22;
+
+/// The SFTP status code for a file that doesn't exist (`SSH_FX_NO_SUCH_FILE`).
+const NO_SUCH_FILE: i32 = 2;
+
+/// A parsed `sftp://[user@]host[:port]/path` target.
+#[derive(Debug, PartialEq, Eq)]
+struct Target {
+    user: String,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Target {
+    /// Parses a target. The user defaults to the current user and the port to 22.
+    fn parse(target: &str) -> Result<Self, Box<dyn Error>> {
+        let invalid = || format!("Invalid SFTP target {target}. Expected sftp://user@host/path.");
+
+        let rest = target.strip_prefix(SCHEME).ok_or_else(invalid)?;
+        let (authority, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (user.to_string(), host_port),
+            None => (
+                env::var("USER")
+                    .or_else(|_| env::var("USERNAME"))
+                    .map_err(|_| invalid())?,
+                authority,
+            ),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (host_port, DEFAULT_PORT),
+        };
+
+        if host.is_empty() || path.is_empty() {
+            return Err(invalid().into());
+        }
+
+        Ok(Self {
+            user,
+            host: host.to_string(),
+            port,
+            path: format!("/{path}"),
+        })
+    }
+
+    /// The `user@host:port` part, used to group targets on the same server.
+    fn server(&self) -> String {
+        format!("{}@{}:{}", self.user, self.host, self.port)
+    }
+
+    /// Formats a remote path on the same server as a target. The port is only included
+    /// if it is not the default, so the result can be parsed again.
+    fn display(&self, path: &Path) -> String {
+        if self.port == DEFAULT_PORT {
+            format!("{SCHEME}{}@{}{}", self.user, self.host, path.display())
+        } else {
+            format!(
+                "{SCHEME}{}@{}:{}{}",
+                self.user,
+                self.host,
+                self.port,
+                path.display()
+            )
+        }
+    }
+}
+
+/// Connects to the server, verifies its host key against `~/.ssh/known_hosts` and
+/// authenticates using the SSH agent, falling back to the default key files.
+fn connect(target: &Target) -> Result<ssh2::Sftp, Box<dyn Error>> {
+    let tcp = TcpStream::connect((target.host.as_str(), target.port))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+
+    let home = PathBuf::from(env::var("HOME").or_else(|_| env::var("USERPROFILE"))?);
+    let ssh_dir = home.join(".ssh");
+
+    let mut known_hosts = session.known_hosts()?;
+    known_hosts
+        .read_file(&ssh_dir.join("known_hosts"), KnownHostFileKind::OpenSSH)
+        .map_err(|err| format!("Unable to read known_hosts: {err}"))?;
+    let (key, _) = session
+        .host_key()
+        .ok_or("The server did not send a host key.")?;
+    match known_hosts.check_port(&target.host, target.port, key) {
+        CheckResult::Match => {}
+        CheckResult::NotFound => {
+            return Err(format!(
+                "Host {} is not in known_hosts. Connect with ssh once to add it.",
+                target.host
+            )
+            .into())
+        }
+        CheckResult::Mismatch => {
+            return Err(format!("Host key for {} does not match known_hosts.", target.host).into())
+        }
+        CheckResult::Failure => return Err("Unable to check the host key.".into()),
+    }
+
+    if session.userauth_agent(&target.user).is_err() {
+        for key_file in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+            let key_path = ssh_dir.join(key_file);
+            if key_path.exists()
+                && session
+                    .userauth_pubkey_file(&target.user, None, &key_path, None)
+                    .is_ok()
+            {
+                break;
+            }
+        }
+    }
+    if !session.authenticated() {
+        return Err(format!(
+            "Unable to authenticate as {} on {}.",
+            target.user, target.host
+        )
+        .into());
+    }
+
+    Ok(session.sftp()?)
+}
+
+/// Returns true if the path component contains glob characters.
+fn is_glob(component: &str) -> bool {
+    component.contains(['*', '?', '['])
+}
+
+/// Adds `dir` and every directory below it on the server to `dirs`.
+fn collect_dirs(sftp: &ssh2::Sftp, dir: &Path, dirs: &mut Vec<PathBuf>) {
+    dirs.push(dir.to_path_buf());
+    if let Ok(entries) = sftp.readdir(dir) {
+        for (path, stat) in entries {
+            if stat.is_dir() {
+                collect_dirs(sftp, &path, dirs);
+            }
+        }
+    }
+}
+
+/// Expands the glob characters in a remote path using the directory listings on the server.
+/// `**` matches any number of directories. Names on the server are normalized before they are
+/// matched against the glob, if requested. Returns the regular files found, with their sizes.
+fn resolve(
+    sftp: &ssh2::Sftp,
+    path: &str,
+    normalization: Option<Normalization>,
+    options: MatchOptions,
+) -> Result<Vec<(PathBuf, u64)>, Box<dyn Error>> {
+    let mut candidates = vec![PathBuf::from("/")];
+
+    for component in path.split('/').filter(|component| !component.is_empty()) {
+        let mut next = Vec::new();
+
+        if component == "**" {
+            for dir in &candidates {
+                collect_dirs(sftp, dir, &mut next);
+            }
+        } else if is_glob(component) {
+            let pattern = Pattern::new(component)?;
+            for dir in &candidates {
+                let Ok(entries) = sftp.readdir(dir) else {
+                    continue;
+                };
+                next.extend(entries.into_iter().map(|(path, _)| path).filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| {
+                            pattern
+                                .matches_with(&normalize::normalize(name, normalization), options)
+                        })
+                }));
+            }
+        } else {
+            next.extend(candidates.iter().map(|dir| dir.join(component)));
+        }
+
+        candidates = next;
+    }
+
+    let mut files = Vec::new();
+    for candidate in candidates {
+        match sftp.lstat(&candidate) {
+            Ok(stat) if stat.is_file() => files.push((candidate, stat.size.unwrap_or_default())),
+            Ok(_) => log::debug!("Skipping {}: not a regular file.", candidate.display()),
+            Err(_) if !is_glob(path) => {
+                log::warn!("{} not found on the server.", candidate.display());
+            }
+            Err(_) => {}
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    Ok(files)
+}
+
+/// Files on SFTP servers. Globs in the path are resolved on the server, and one connection
+/// is made per server and kept for the rest of the run.
+pub struct Sftp {
+    connections: BTreeMap<String, ssh2::Sftp>,
+    normalization: Option<Normalization>,
+    options: MatchOptions,
+}
+
+impl Sftp {
+    /// Creates the backend. No connections are made until a target on a server is resolved.
+    pub fn new(normalization: Option<Normalization>, ignore_case: bool) -> Self {
+        Self {
+            connections: BTreeMap::new(),
+            normalization,
+            options: name::match_options(ignore_case),
+        }
+    }
+
+    /// Returns the connection to the target's server, connecting first if needed.
+    fn connection(&mut self, target: &Target) -> Result<&ssh2::Sftp, Box<dyn Error>> {
+        let server = target.server();
+        if !self.connections.contains_key(&server) {
+            log::debug!("Connecting to {server}");
+            self.connections.insert(server.clone(), connect(target)?);
+        }
+
+        Ok(&self.connections[&server])
+    }
+}
+
+impl Backend for Sftp {
+    fn resolve(&mut self, target: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let target = Target::parse(target)?;
+        let (normalization, options) = (self.normalization, self.options);
+        let sftp = self.connection(&target)?;
+
+        Ok(resolve(sftp, &target.path, normalization, options)?
+            .into_iter()
+            .map(|(path, size)| Entry {
+                name: target.display(&path),
+                size,
+            })
+            .collect())
+    }
+
+    fn time(
+        &mut self,
+        entry: &Entry,
+        field: TimeField,
+    ) -> Result<Option<SystemTime>, Box<dyn Error>> {
+        let target = Target::parse(&entry.name)?;
+        let stat = self.connection(&target)?.lstat(Path::new(&target.path))?;
+
+        // SFTP only reports the modification and access times
+        let seconds = match field {
+            TimeField::Modified => stat.mtime,
+            TimeField::Accessed => stat.atime,
+            _ => None,
+        };
+        Ok(seconds
+            .and_then(|seconds| SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds))))
+    }
+
+    fn stamp(&mut self, entry: &Entry) -> Result<Option<Stamp>, Box<dyn Error>> {
+        let target = Target::parse(&entry.name)?;
+        let stat = self.connection(&target)?.lstat(Path::new(&target.path))?;
+
+        Ok(Some(Stamp {
+            size: stat.size.unwrap_or_default(),
+            modified: stat.mtime.and_then(|seconds| {
+                SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+            }),
+            id: None,
+        }))
+    }
+
+    fn exists(&mut self, entry: &Entry) -> Result<Option<bool>, Box<dyn Error>> {
+        let target = Target::parse(&entry.name)?;
+        match self.connection(&target)?.lstat(Path::new(&target.path)) {
+            Ok(_) => Ok(Some(true)),
+            Err(err) if err.code() == ErrorCode::SFTP(NO_SUCH_FILE) => Ok(Some(false)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn open(&mut self, entry: &Entry) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+        let target = Target::parse(&entry.name)?;
+        let sftp = self.connection(&target)?;
+
+        Ok(Box::new(sftp.open(Path::new(&target.path))?))
+    }
+
+    fn remove(&mut self, entries: &[Entry]) -> Result<RemoveResults, Box<dyn Error>> {
+        let mut results = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let target = Target::parse(&entry.name)?;
+            let sftp = self.connection(&target)?;
+            results.push(
+                sftp.unlink(Path::new(&target.path))
+                    .map(|()| Removed::Deleted)
+                    .map_err(|err| err.into()),
+            );
+        }
+
+        Ok(results)
+    }
+}
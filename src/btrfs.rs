@@ -0,0 +1,79 @@
+use rustix::fs::{self as rfs, Mode, OFlags};
+use rustix::ioctl::{self, opcode, Opcode, Setter};
+use std::error::Error;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// The file system type of btrfs, as reported by `statfs`.
+const BTRFS_SUPER_MAGIC: i64 = 0x9123_683E;
+
+/// The inode number of the top directory of every btrfs subvolume.
+const SUBVOLUME_INODE: u64 = 256;
+
+/// The longest subvolume name the kernel accepts.
+const VOL_NAME_MAX: usize = 4087;
+
+/// `struct btrfs_ioctl_vol_args` from `linux/btrfs.h`.
+#[repr(C)]
+struct VolArgs {
+    fd: i64,
+    name: [u8; VOL_NAME_MAX + 1],
+}
+
+/// `BTRFS_IOC_SNAP_DESTROY`, which deletes a subvolume (or snapshot) by name from its parent.
+const SNAP_DESTROY: Opcode = opcode::write::<VolArgs>(0x94, 15);
+
+/// Returns true if `path` is the top directory of a btrfs subvolume, such as the ones Docker
+/// and snapper create.
+pub fn is_subvolume(path: &Path) -> Result<bool, Box<dyn Error>> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() || metadata.ino() != SUBVOLUME_INODE {
+        return Ok(false);
+    }
+
+    let fd = rfs::open(
+        path,
+        OFlags::RDONLY | OFlags::DIRECTORY | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+    // The type of f_type differs between platforms
+    #[allow(clippy::unnecessary_cast)]
+    let is_btrfs = rfs::fstatfs(&fd)?.f_type as i64 == BTRFS_SUPER_MAGIC;
+
+    Ok(is_btrfs)
+}
+
+/// Deletes a btrfs subvolume along with everything in it, in one go. This needs CAP_SYS_ADMIN,
+/// or the file system to be mounted with `user_subvol_rm_allowed`.
+pub fn delete_subvolume(path: &Path) -> Result<(), Box<dyn Error>> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Unable to delete {}: no name.", path.display()))?
+        .as_bytes();
+    if name.len() > VOL_NAME_MAX {
+        return Err(format!("Unable to delete {}: the name is too long.", path.display()).into());
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent_fd = rfs::open(
+        parent,
+        OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+
+    let mut args = VolArgs {
+        fd: 0,
+        name: [0; VOL_NAME_MAX + 1],
+    };
+    args.name[..name.len()].copy_from_slice(name);
+
+    // SAFETY: BTRFS_IOC_SNAP_DESTROY takes a btrfs_ioctl_vol_args, which VolArgs matches
+    unsafe { ioctl::ioctl(&parent_fd, Setter::<SNAP_DESTROY, VolArgs>::new(args)) }
+        .map_err(|err| format!("Unable to delete the subvolume {}: {err}", path.display()))?;
+
+    Ok(())
+}
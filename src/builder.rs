@@ -0,0 +1,193 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::age::{AgeFilter, TimeField};
+use crate::clock::Clock;
+use crate::deleter::{Deleter, Settings};
+use crate::expr::Expr;
+use crate::name::NameFilter;
+use crate::observer::Observer;
+
+/// Configures a `Deleter` in code, for programs using rdel as a library:
+///
+/// ```no_run
+/// # use rdel::deleter::Deleter;
+/// let mut deleter = Deleter::builder()
+///     .dry_run(true)
+///     .trash(true)
+///     .filter("size > 1M")
+///     .build()?;
+/// deleter.run(["/tmp/build".to_string()].map(Ok))?;
+/// deleter.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// Anything not set is as if the command line option wasn't given. Name patterns and filter
+/// expressions are checked when the deleter is built.
+#[derive(Default)]
+pub struct DeleterBuilder {
+    settings: Settings,
+    names: Vec<String>,
+    filters: Vec<String>,
+    clock: Option<Box<dyn Clock>>,
+    observers: Vec<Box<dyn Observer>>,
+}
+
+impl DeleterBuilder {
+    /// Starts from settings made elsewhere, e.g. read from the command line.
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Whether to only show what would be removed, like `--dry-run`.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.settings.dry_run = dry_run;
+        self
+    }
+
+    /// Whether to move files to the trash instead of deleting them, like `--trash`.
+    pub fn trash(mut self, trash: bool) -> Self {
+        self.settings.trash = trash;
+        self
+    }
+
+    /// Keeps the trash within `max_size` bytes and `max_age` after moving files to it, like the
+    /// `trash` settings in the config file.
+    pub fn trash_limits(mut self, max_size: Option<u64>, max_age: Option<Duration>) -> Self {
+        self.settings.trash_max_size = max_size;
+        self.settings.trash_max_age = max_age;
+        self
+    }
+
+    /// Moves files into dated folders below `dir` instead of deleting them, like `--quarantine`.
+    pub fn quarantine(mut self, dir: impl AsRef<Path>) -> Self {
+        self.settings.quarantine = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Whether directories are removed along with everything in them, like `--recursive`.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.settings.recursive = recursive;
+        self
+    }
+
+    /// Only removes files whose names match the glob pattern, like `--name`. Files matching
+    /// any of the patterns given are removed.
+    pub fn name(mut self, pattern: &str) -> Self {
+        self.names.push(pattern.to_string());
+        self
+    }
+
+    /// Only removes files matching the filter expression, like `--where`. Files must match
+    /// every expression given.
+    pub fn filter(mut self, expr: &str) -> Self {
+        self.filters.push(expr.to_string());
+        self
+    }
+
+    /// Asks the program `command` which files may be removed, like `--filter-cmd`. It's started
+    /// when the deleter is built.
+    pub fn filter_cmd(mut self, command: &str) -> Self {
+        self.settings.filter_cmd = Some(command.to_string());
+        self
+    }
+
+    /// Only removes files last modified longer ago than `age`, like `--older-than`.
+    pub fn older_than(mut self, age: Duration) -> Self {
+        self.settings.age = Some(AgeFilter {
+            older_than: age,
+            field: TimeField::Modified,
+        });
+        self
+    }
+
+    /// Leaves files modified within `age` alone, like `--min-age`.
+    pub fn min_age(mut self, age: Duration) -> Self {
+        self.settings.min_age = Some(age);
+        self
+    }
+
+    /// Whether names and expressions are matched regardless of case, like `--ignore-case`.
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.settings.ignore_case = ignore_case;
+        self
+    }
+
+    /// Stops once `max` files have been removed, like `--max-delete`.
+    pub fn max_delete(mut self, max: usize) -> Self {
+        self.settings.max_delete = Some(max);
+        self
+    }
+
+    /// Stops before removing more than `max` bytes, like `--max-free`.
+    pub fn max_free(mut self, max: u64) -> Self {
+        self.settings.max_free = Some(max);
+        self
+    }
+
+    /// Stops once more than `max` files couldn't be removed, like `--max-errors`.
+    pub fn max_errors(mut self, max: usize) -> Self {
+        self.settings.max_errors = Some(max);
+        self
+    }
+
+    /// Removes the files in bursts of `chunk`, resting for `pause` after each, like `--chunk`
+    /// and `--pause`.
+    pub fn pause(mut self, chunk: usize, pause: Duration) -> Self {
+        self.settings.pause = Some((chunk.max(1), pause));
+        self
+    }
+
+    /// Writes each batch to the journal at `path` before removing it, like `--journal`.
+    pub fn journal(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings.journal_file = Some(path.into());
+        self
+    }
+
+    /// Writes a JSON report of every file to `path` when the run finishes, like `--report`.
+    pub fn report(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings.report_file = Some(path.into());
+        self
+    }
+
+    /// Takes the current time from `clock` instead of the system's clock.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Tells `observer` about each file as the run goes, and about the summary at the end.
+    /// Several observers can be given.
+    pub fn observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Creates the deleter.
+    pub fn build(mut self) -> Result<Deleter, Box<dyn Error>> {
+        let ignore_case = self.settings.ignore_case;
+        if !self.names.is_empty() {
+            let patterns: Vec<&str> = self.names.iter().map(String::as_str).collect();
+            self.settings.names = Some(NameFilter::new(
+                &patterns,
+                ignore_case,
+                self.settings.normalize,
+            )?);
+        }
+        for filter in &self.filters {
+            let expr = Expr::parse(filter, ignore_case)?;
+            self.settings.where_expr = Expr::and(self.settings.where_expr.take(), Some(expr));
+        }
+
+        let mut deleter = match self.clock {
+            Some(clock) => Deleter::new_with_clock(self.settings, clock)?,
+            None => Deleter::new(self.settings)?,
+        };
+        for observer in self.observers {
+            deleter = deleter.with_observer(observer);
+        }
+        Ok(deleter)
+    }
+}
@@ -0,0 +1,43 @@
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::{error::Error, path::Path};
+
+/// The size of the buffer used when reading files for hashing.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Calculates the SHA-256 digest of a file and returns it as a lowercase hex string.
+///
+/// The file is read in chunks so that large files don't have to fit in memory.
+pub fn sha256(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut reader = BufReader::with_capacity(BUFFER_SIZE, File::open(path)?);
+    sha256_reader(&mut reader)
+}
+
+/// Calculates the SHA-256 digest of everything read from `reader`.
+pub fn sha256_reader(reader: &mut dyn Read) -> Result<String, Box<dyn Error>> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0_u8; BUFFER_SIZE];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Formats a byte slice as a lowercase hex string.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut acc, b| {
+            let _ = write!(acc, "{b:02x}");
+            acc
+        })
+}
@@ -0,0 +1,129 @@
+use serde::Serialize;
+use std::io::{self, Read};
+
+/// How much of a file is read to tell what kind it is. Enough for the tar header's magic.
+const SAMPLE_SIZE: usize = 512;
+
+/// What a file holds, told from its first bytes, for `--classify`. The kinds are named in the
+/// plural in the `--report` summary, as they count files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Kind {
+    #[serde(rename = "images")]
+    Image,
+    #[serde(rename = "archives")]
+    Archive,
+    #[serde(rename = "executables")]
+    Executable,
+    #[serde(rename = "documents")]
+    Document,
+    #[serde(rename = "logs")]
+    Log,
+    #[serde(rename = "text")]
+    Text,
+    #[serde(rename = "empty")]
+    Empty,
+    #[serde(rename = "other")]
+    Other,
+}
+
+impl Kind {
+    /// The label the kind is shown with in the summary.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Image => "Images:",
+            Self::Archive => "Archives:",
+            Self::Executable => "Executables:",
+            Self::Document => "Documents:",
+            Self::Log => "Logs:",
+            Self::Text => "Text:",
+            Self::Empty => "Empty files:",
+            Self::Other => "Other data:",
+        }
+    }
+}
+
+/// Reads the start of a file and tells what kind it is.
+pub fn sample(reader: &mut dyn Read) -> io::Result<Kind> {
+    let mut header = Vec::with_capacity(SAMPLE_SIZE);
+    reader.take(SAMPLE_SIZE as u64).read_to_end(&mut header)?;
+    Ok(classify(&header))
+}
+
+/// Tells what kind of file starts with `header`, from the magic numbers of the common formats.
+/// Text is taken for a log when its first line starts with a timestamp.
+pub fn classify(header: &[u8]) -> Kind {
+    const IMAGES: &[&[u8]] = &[
+        b"\x89PNG",
+        b"\xFF\xD8\xFF",
+        b"GIF8",
+        b"BM",
+        b"II*\0",
+        b"MM\0*",
+    ];
+    const ARCHIVES: &[&[u8]] = &[
+        b"PK\x03\x04",
+        b"\x1F\x8B",
+        b"BZh",
+        b"\xFD7zXZ\0",
+        b"\x28\xB5\x2F\xFD",
+        b"7z\xBC\xAF\x27\x1C",
+        b"Rar!",
+    ];
+    const EXECUTABLES: &[&[u8]] = &[
+        b"\x7FELF",
+        b"MZ",
+        b"\xFE\xED\xFA\xCE",
+        b"\xFE\xED\xFA\xCF",
+        b"\xCE\xFA\xED\xFE",
+        b"\xCF\xFA\xED\xFE",
+        b"\xCA\xFE\xBA\xBE",
+        b"#!",
+    ];
+
+    let starts_with = |magics: &[&[u8]]| magics.iter().any(|magic| header.starts_with(magic));
+    if header.is_empty() {
+        Kind::Empty
+    } else if starts_with(IMAGES)
+        || (header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP"))
+    {
+        Kind::Image
+    } else if starts_with(ARCHIVES) || header.get(257..262) == Some(b"ustar") {
+        Kind::Archive
+    } else if starts_with(EXECUTABLES) {
+        Kind::Executable
+    } else if header.starts_with(b"%PDF") {
+        Kind::Document
+    } else if header.contains(&0) || !is_utf8(header) {
+        Kind::Other
+    } else if starts_with_timestamp(header) {
+        Kind::Log
+    } else {
+        Kind::Text
+    }
+}
+
+/// Tells whether the start of a file is UTF-8, allowing for a character cut short at the end.
+fn is_utf8(header: &[u8]) -> bool {
+    match std::str::from_utf8(header) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+/// Tells whether text starts with something like `2024-05-01`, `[01/May/2024` or `May  1 13:45`,
+/// as the lines of most logs do.
+fn starts_with_timestamp(text: &[u8]) -> bool {
+    const MONTHS: &[&[u8]] = &[
+        b"Jan ", b"Feb ", b"Mar ", b"Apr ", b"May ", b"Jun ", b"Jul ", b"Aug ", b"Sep ", b"Oct ",
+        b"Nov ", b"Dec ",
+    ];
+
+    let text = text.strip_prefix(b"[").unwrap_or(text);
+    let digits = text.iter().take_while(|byte| byte.is_ascii_digit()).count();
+    let date = (digits == 4 || digits == 2)
+        && text
+            .get(digits)
+            .is_some_and(|separator| matches!(separator, b'-' | b'/' | b'.'))
+        && text.get(digits + 1).is_some_and(u8::is_ascii_digit);
+    date || MONTHS.iter().any(|month| text.starts_with(month))
+}
@@ -1,18 +1,227 @@
 use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+/// How deeply response files may refer to other response files, to catch loops.
+const MAX_RESPONSE_FILE_DEPTH: usize = 10;
 
 pub fn build() -> ArgMatches {
+    // Read the arguments in any @file response files first
+    let args = expand_response_files(env::args_os().collect(), 0)
+        .unwrap_or_else(|err| clap::Error::raw(clap::error::ErrorKind::Io, err + "\n").exit());
+
+    // Run as rm (e.g. through a symlink), rdel behaves like it
+    let mut args = args;
+    let invoked_as_rm = args
+        .first()
+        .is_some_and(|arg0| Path::new(arg0).file_stem().is_some_and(|stem| stem == "rm"));
+    if invoked_as_rm {
+        args.insert(1, OsString::from("--rm-compat"));
+    }
+    let rm_compat = args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--rm-compat");
+
     Command::new(clap::crate_name!())
         .about(clap::crate_description!())
         .version(clap::crate_version!())
         .author(clap::crate_authors!("\n"))
         .long_about("Recursively delete files.")
-        .arg(
-            Arg::new("files")
-                .value_name("FILE(S)")
-                .help("One or more file(s) to process. Wildcards and multiple_occurrences files (e.g. 2019*.pdf 2020*.pdf) are supported. Use the ** glob to recurse (eg. **/*.log). Note: Case sensitive.")
-                .required(true)
-                .num_args(1..)
-                .action(ArgAction::Append)
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .arg( // Bare paths are deleted, as with the delete command
+            files_arg().required_unless_present_any(["git-ignored", "files-from", "ignore-missing"])
+        )
+        .arg( // Remove directories with their contents
+            Arg::new("recursive")
+                .short('R')
+                .short_aliases(rm_compat.then_some('r'))
+                .long("recursive")
+                .help("Remove directories given as targets along with everything in them. What is in each directory is shown first, and the removal asked about, unless --yes is given.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Remove the directories given without asking
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .help("Remove the directories given as targets with --recursive without asking first, e.g. in scripts. What is in them is still shown. Like rm, --rm-compat never asks.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Special files
+            Arg::new("special")
+                .long("special")
+                .value_name("POLICY")
+                .help("What to do with devices, sockets and named pipes, given as targets or found in directory trees: skip them with a warning (the default, leaving the directories they are in), or delete them like other files. With --rm-compat, they are deleted by default.")
+                .num_args(1)
+                .value_parser(["skip", "delete"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // What to do with directory links in recursive removals
+            Arg::new("dir-links")
+                .long("dir-links")
+                .value_name("POLICY")
+                .help("What to do with the directory symbolic links and junctions found in directory trees: remove the link and leave what it points to alone (the default), skip it with a warning (leaving the directories it's in), or follow it and remove what is in the directory it points to as well. Windows only; elsewhere symbolic links are always removed, never followed.")
+                .num_args(1)
+                .value_parser(["remove", "skip", "follow"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Delete btrfs subvolumes
+            Arg::new("allow-subvolumes")
+                .long("allow-subvolumes")
+                .help("With --recursive, delete directories that are btrfs subvolumes (e.g. from Docker or snapper) using the subvolume ioctl. Linux only.")
+                .num_args(0)
+                .requires("recursive")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Overwrite files before deleting them
+            Arg::new("shred")
+                .long("shred")
+                .help("Overwrite the data in each file with zeros before deleting it, so it can't be recovered. Holes in sparse files are skipped.")
+                .num_args(0)
+                .conflicts_with_all(["quarantine", "trash", "recursive"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // How to shred files
+            Arg::new("shred-method")
+                .long("shred-method")
+                .value_name("METHOD")
+                .help("How --shred overwrites files: one pass of zeros, one pass of random data, DoD 5220.22-M (zeros, ones, random) or the 27 fixed Gutmann patterns.")
+                .num_args(1)
+                .default_value("zeros")
+                .value_parser(["zeros", "random", "dod", "gutmann"])
+                .requires("shred")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Check the shredded data
+            Arg::new("shred-verify")
+                .long("shred-verify")
+                .help("Read back the last pass written by --shred and check it before deleting the file.")
+                .num_args(0)
+                .requires("shred")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Hide the file names before deleting
+            Arg::new("scramble-name")
+                .long("scramble-name")
+                .help("Rename each file to random, ever shorter names before deleting it, so its name can't easily be recovered from the directory.")
+                .num_args(0)
+                .conflicts_with_all(["quarantine", "trash", "recursive"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Leave files other processes have open alone
+            Arg::new("skip-in-use")
+                .long("skip-in-use")
+                .help("Skip files that another process has open, and list the processes holding them in the report. Linux and Windows only.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Look at each file once more just before removing it
+            Arg::new("recheck")
+                .long("recheck")
+                .help("Look up the size, modification time and inode of each file again just before removing it, and skip it if it grew, shrank or was replaced since it was found.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Check that files haven't been swapped before removing them
+            Arg::new("paranoid")
+                .long("paranoid")
+                .help("Check that each file, and the directory it is in, are still the ones that were found before removing it, without following symbolic links. Unix only.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Make the removals durable
+            Arg::new("sync")
+                .long("sync")
+                .help("Sync the directories files were removed from to disk before reporting, so the removals survive a crash. Each directory is synced once at the end of the run. Unix only.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Time each removal
+            Arg::new("timings")
+                .long("timings")
+                .help("Log how long each removal takes, and warn about those that take much longer than the rest, e.g. on a slow network mount. Also done with trace-level logs (-dd).")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Return the freed space to the device
+            Arg::new("discard")
+                .long("discard")
+                .help("Run fstrim on the file systems files were deleted from at the end of the run, so the space freed is returned to SSDs and thin-provisioned volumes. Linux only.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Files ignored by git
+            Arg::new("git-ignored")
+                .long("git-ignored")
+                .value_name("REPO")
+                .help("Delete the files in the git work tree at REPO that are ignored by its .gitignore rules, like git clean -X. Nested repositories are left alone.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Files listed in a plan
+            Arg::new("files-from")
+                .long("files-from")
+                .value_name("PLAN")
+                .help("Process the files listed in a JSON plan, or a CSV plan if its name ends in .csv. Each entry can give the expected size and mtime, and whether to delete, trash or move the file. Files that no longer match are skipped as changed since listed.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Reject listed paths that climb up with ..
+            Arg::new("no-parent-traversal")
+                .long("no-parent-traversal")
+                .help("Skip the entries of a --files-from plan or an apply --verify manifest whose paths contain .. (once . and repeated slashes are ignored), so a list made elsewhere can't steer the removals out of the tree it's meant for.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Protect files tracked by git
+            Arg::new("protect-tracked")
+                .long("protect-tracked")
+                .help("Skip files that are tracked in the index of the git work tree they are in.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
         )
         .arg( // Hidden debug parameter
             Arg::new("debug")
@@ -22,15 +231,57 @@ pub fn build() -> ArgMatches {
                 .hide(true)
                 .env("RDEL_DEBUG")
                 .num_args(0)
+                .global(true)
                 .action(ArgAction::Count)
         )
+        .arg( // Language of the messages
+            Arg::new("lang")
+                .long("lang")
+                .value_name("LANGUAGE")
+                .help("The language of the messages and prompts. By default, the language of the locale (LC_ALL, LC_MESSAGES or LANG) is used, falling back to English.")
+                .num_args(1)
+                .value_parser(["en", "de", "nb"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Timestamp format
+            Arg::new("time-format")
+                .long("time-format")
+                .value_name("FORMAT")
+                .help("How timestamps are written in the log messages and reports, as a strftime-style format such as '%Y-%m-%d %H:%M:%S'. The default is RFC 3339. The audit trail, journal and trash records are always RFC 3339.")
+                .num_args(1)
+                .value_parser(rdel::timestamp::parse_format)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Timestamps in UTC
+            Arg::new("utc")
+                .long("utc")
+                .help("Write timestamps in UTC rather than local time.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
         .arg( // Dry-run
             Arg::new("dry-run")
-                .short('r')
+                .short((!rm_compat).then_some('r'))
                 .long("dry-run")
                 .help("Iterate through the files and produce output without actually deleting anything.")
                 .num_args(0)
                 .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Only scan, to see how long a run would take
+            Arg::new("scan-only")
+                .long("scan-only")
+                .help("Find, filter and add up the files as a real run would, but don't remove anything or list the files, and print how many files per second were scanned. Shows how long a run would take on the storage.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
                 .action(ArgAction::SetTrue)
         )
         .arg( // Stop on error
@@ -40,6 +291,96 @@ pub fn build() -> ArgMatches {
                 .help("If set, the program will stop if it encounters an error. If not, the program will attempt to continue if errors occur.")
                 .num_args(0)
                 .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Stop after too many errors
+            Arg::new("max-errors")
+                .long("max-errors")
+                .value_name("N")
+                .help("Stop the run once more than N files couldn't be removed, as that suggests something is systematically wrong (e.g. a read-only mount). A few transient failures are tolerated. --stop-on-error is the same as --max-errors 0.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("stop")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Cap the number of files removed
+            Arg::new("max-delete")
+                .long("max-delete")
+                .value_name("N")
+                .help("Stop the run once N files have been removed (e.g. 10000 or 10000files), in case the patterns match far more than expected.")
+                .num_args(1)
+                .value_parser(rdel::utils::parse_file_count)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Cap the space freed
+            Arg::new("max-free")
+                .long("max-free")
+                .value_name("SIZE")
+                .help("Stop the run before removing a file would free more than SIZE (e.g. 100G), in case the patterns match far more than expected. Accepts suffixes such as K, M and G.")
+                .num_args(1)
+                .value_parser(rdel::utils::parse_size)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Limit how long the whole run takes
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("DURATION")
+                .help("Stop the run before the next file once it has taken longer than DURATION (e.g. 30m). The summary is still printed.")
+                .num_args(1)
+                .value_parser(humantime::parse_duration)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Limit how long a single file operation takes
+            Arg::new("file-timeout")
+                .long("file-timeout")
+                .value_name("DURATION")
+                .help("Give up on a local file if looking it up or removing it takes longer than DURATION (e.g. 30s), such as on a hung NFS server. The file is counted as timed out and the run carries on.")
+                .num_args(1)
+                .value_parser(humantime::parse_duration)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Rest between bursts of removals
+            Arg::new("pause")
+                .long("pause")
+                .value_name("DURATION")
+                .help("Rest for DURATION (e.g. 2s) after each chunk of files given with --chunk, so storage that snapshots or replicates the changes can keep up.")
+                .num_args(1)
+                .value_parser(humantime::parse_duration)
+                .requires("chunk")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // How many files to remove between rests
+            Arg::new("chunk")
+                .long("chunk")
+                .value_name("N")
+                .help("Remove files in bursts of N, resting for the time given with --pause in between.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .requires("pause")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Check again at the end whether files held open on NFS have been released
+            Arg::new("recheck-pending")
+                .long("recheck-pending")
+                .help("At the end of the run, check again whether the files deleted while still open on NFS (kept as .nfsXXXX files until they're closed) have been released, and count the space of those that have as freed.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
                 .action(ArgAction::SetTrue)
         )
         .arg( // Don't print any information
@@ -49,6 +390,7 @@ pub fn build() -> ArgMatches {
                 .help("Don't produce any output except errors while working.")
                 .num_args(0)
                 .hide(false)
+                .global(true)
                 .action(ArgAction::SetTrue)
         )
         .arg( // Print summary information
@@ -58,6 +400,7 @@ pub fn build() -> ArgMatches {
                 .help("Print summary detail.")
                 .num_args(0)
                 .hide(false)
+                .global(true)
                 .action(ArgAction::SetTrue)
         )
         .arg( // Don't export detail information
@@ -67,7 +410,836 @@ pub fn build() -> ArgMatches {
                 .help("Don't export detailed information about each file processed.")
                 .num_args(0)
                 .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Classify the files removed
+            Arg::new("classify")
+                .long("classify")
+                .help("Read the first bytes of each file removed and break the files down in the summary by what they hold: images, archives, executables, documents, logs, other text or other data.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Checksum files before deletion
+            Arg::new("checksum")
+                .short('c')
+                .long("checksum")
+                .value_name("ALGORITHM")
+                .help("Calculate a checksum of each file before it is removed. The digest is shown in the detail output and recorded in the audit log.")
+                .num_args(1)
+                .value_parser(["sha256"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Audit log
+            Arg::new("audit-log")
+                .short('a')
+                .long("audit-log")
+                .value_name("FILE")
+                .help("Append a JSON record for each file removed to the specified audit log.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Make the audit log crash-safe
+            Arg::new("audit-sync")
+                .long("audit-sync")
+                .help("Write an audit record for each batch of files, and flush it to the disk, before removing any of them, so that no removal can be missing from the audit log after a crash or a power cut. The record of what was done follows as usual.")
+                .num_args(0)
+                .requires("audit-log")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Journal
+            Arg::new("journal")
+                .long("journal")
+                .value_name("FILE")
+                .help("Write each batch of files to FILE, flushed to the disk, before removing any of them, and mark them as done once removed, so that after a crash `rdel recover FILE` can tell which files were removed and which weren't.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Report
+            Arg::new("report")
+                .long("report")
+                .value_name("FILE")
+                .help("Write a JSON report of every file processed, what happened to it and why, along with the summary.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Failure list
+            Arg::new("failed-list")
+                .long("failed-list")
+                .value_name("FILE")
+                .help("Write the paths of the files that couldn't be removed to FILE, one per line, so they can be retried in a follow-up run.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // List the files removed
+            Arg::new("print-deleted")
+                .long("print-deleted")
+                .value_name("FILE")
+                .help("Write the paths of the files removed (or that would be, in a dry-run) to FILE as the run goes, one per line, or to standard output if FILE is -.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // List the files left alone
+            Arg::new("print-skipped")
+                .long("print-skipped")
+                .value_name("FILE")
+                .help("Write the paths of the files left alone or that couldn't be removed to FILE as the run goes, one per line, or to standard error if FILE is -.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Progress records for front-ends
+            Arg::new("progress-fd")
+                .long("progress-fd")
+                .value_name("FD")
+                .help("Write progress records to the open file descriptor FD as lines of JSON, for graphical front-ends to show a progress bar: the files found and dealt with so far, their sizes, and the file being worked on. Unix only.")
+                .num_args(1)
+                .value_parser(clap::value_parser!(i32).range(0..))
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Full-screen dashboard
+            Arg::new("tui")
+                .long("tui")
+                .help("Show a full-screen dashboard instead of the scrolling log while files are removed: the overall progress, the file being removed, the bytes freed so far and the latest warnings and errors. The summary is printed as usual at the end. Plain output is used when standard output isn't a terminal.")
+                .num_args(0)
+                .conflicts_with_all(["interactive", "interactive-once", "confirm-hazardous"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Check the removals once the run is over. Not global, as apply has a --verify of its own
+            Arg::new("verify-removals")
+                .long("verify")
+                .help("Once the run is over, check that each file counted as removed is gone, and that the files moved to the trash, the quarantine or (by a --files-from plan) another directory are there, in case a backend got it wrong or another process put them back. Each discrepancy is warned about and counted under \"Failed verification\" in the summary, and the run fails. Not available with the subcommands, as the --verify of apply names its manifest.")
+                .num_args(0)
+                .hide(false)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // NUL-separated lists
+            Arg::new("null")
+                .short('0')
+                .long("null")
+                .help("Separate the paths read from standard input (given as -) and written to --failed-list, --print-deleted and --print-skipped with NUL characters instead of newlines, for use with find -print0 and xargs -0.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // How paths are shown
+            Arg::new("resolve-paths")
+                .long("resolve-paths")
+                .value_name("FORM")
+                .help("How the paths of local files are shown in the log messages and the --report: as they were given (once ~ and environment variables are expanded), made absolute, or made absolute with symbolic links resolved (canonical). Without it, the targets are shown by their canonical paths and the entries of plans and manifests as they are listed.")
+                .num_args(1)
+                .value_parser(["given", "absolute", "canonical"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Show paths relative to a directory
+            Arg::new("relative-to")
+                .long("relative-to")
+                .value_name("DIR")
+                .help("Show the paths of local files relative to DIR in the log messages and the --report, climbing out of it with .. where needed, so reports stay the same on machines that mount the data in different places.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Name filter
+            Arg::new("name")
+                .short('n')
+                .long("name")
+                .value_name("GLOB")
+                .help("Only delete files whose names match the glob pattern (e.g. '*.log'). Can be given more than once; a file matching any of the patterns is deleted.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Age filter
+            Arg::new("older-than")
+                .long("older-than")
+                .value_name("AGE")
+                .help("Only delete files older than this (e.g. 30d, 12h), based on the time selected with --time-field.")
+                .num_args(1)
+                .value_parser(humantime::parse_duration)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Time field for the age filter
+            Arg::new("time-field")
+                .long("time-field")
+                .value_name("FIELD")
+                .help("Which timestamp --older-than uses: modification (mtime), last access (atime), metadata change (ctime) or creation (btime). Files without the timestamp are skipped.")
+                .num_args(1)
+                .default_value("mtime")
+                .value_parser(["mtime", "atime", "ctime", "btime"])
+                .requires("older-than")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Processing order
+            Arg::new("order")
+                .long("order")
+                .value_name("ORDER")
+                .help("The order files are processed in: by path, largest (size-desc) or smallest (size-asc) first, or least (oldest-first) or most (newest-first) recently modified first. Files are processed in the order given by default.")
+                .num_args(1)
+                .value_parser(["path", "size-desc", "size-asc", "oldest-first", "newest-first"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Filter expression
+            Arg::new("where")
+                .short('w')
+                .long("where")
+                .value_name("EXPR")
+                .help("Only delete files matching the filter expression, e.g. \"size > 100MB && mtime > 30d && ext in ('log','tmp')\". See the README for the fields and operators.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // External filter program
+            Arg::new("filter-cmd")
+                .long("filter-cmd")
+                .value_name("COMMAND")
+                .help("Ask this program which of the files that pass the other filters may be removed. It's given each path on a line of its standard input, and answers each with a line saying keep or drop (remove it). See the README.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // find-style type predicate
+            Arg::new("type")
+                .long("type")
+                .value_name("TYPE")
+                .help("Like find -type: only delete files (f), empty directories (d) or symbolic links (l). Several can be given separated by commas, e.g. f,l.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // find-style size predicate
+            Arg::new("size")
+                .long("size")
+                .value_name("[+-]N[ckMG]")
+                .help("Like find -size: only delete files larger (+) or smaller (-) than, or exactly, N. N is in 512-byte blocks unless followed by c (bytes), k, M or G. Can be given twice for a range.")
+                .num_args(1)
+                .allow_hyphen_values(true)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // find-style mtime predicate
+            Arg::new("mtime")
+                .long("mtime")
+                .value_name("[+-]N")
+                .help("Like find -mtime: only delete files modified more (+) or less (-) than, or exactly, N days ago, counting whole days.")
+                .num_args(1)
+                .allow_hyphen_values(true)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Append)
+        )
+        .arg( // Maintenance window
+            Arg::new("active-hours")
+                .long("active-hours")
+                .value_name("HH:MM-HH:MM")
+                .help("Only remove files within this window of the day, in local time, e.g. 01:00-05:00 or 22:00-02:00. Outside it, the run only reports the files found, as a dry-run does, leaving them for a run within the window.")
+                .num_args(1)
+                .value_parser(rdel::hours::ActiveHours::parse)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Safety gate for recently modified files
+            Arg::new("min-age")
+                .long("min-age")
+                .value_name("AGE")
+                .help("Refuse to delete files modified within this window (e.g. 5m), as another process may still be writing them. Use --force to delete them anyway.")
+                .num_args(1)
+                .value_parser(humantime::parse_duration)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Override the safety gates
+            Arg::new("force")
+                .short((!rm_compat).then_some('f'))
+                .long("force")
+                .help("Delete files even if they were modified within the --min-age window.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Clear the immutable and append-only flags
+            Arg::new("force-immutable")
+                .long("force-immutable")
+                .help("Clear the immutable and append-only flags (chattr +i and +a on Linux, uchg and uappnd on macOS and FreeBSD) of files that have them, or of the directories they are in, and remove the files anyway. A directory's flags are set again afterwards. Usually needs root.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Retry the removals refused with sudo
+            Arg::new("sudo-retry")
+                .long("sudo-retry")
+                .help("After the run, try again to remove the files that permission was denied for, through a single run of rdel under sudo that is fed the list of them, rather than running the whole job as root. Unix only.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Ask before removing setuid programs and files in shared directories
+            Arg::new("confirm-hazardous")
+                .long("confirm-hazardous")
+                .help("Ask before removing each setuid or setgid program, another user's file in a sticky directory such as /tmp, or a file in a world-writable directory, rather than only warning about it. Without a terminal to ask on, such files are skipped. Unix only.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Behave like rm
+            Arg::new("rm-compat")
+                .long("rm-compat")
+                .help("Behave like rm, so rdel can stand in for it (e.g. alias rm='rdel --rm-compat'): -r, -f and -i take on rm's meanings, only errors are shown unless -v, --dry-run or --print-summary is given, missing files fail without stopping the run, and the exit code is 1 if any file couldn't be removed. Used automatically when rdel is run as rm.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
                 .action(ArgAction::SetTrue)
         )
-        .get_matches()
+        .arg( // Go ahead with removals that look like mistakes
+            Arg::new("i-know-what-im-doing")
+                .long("i-know-what-im-doing")
+                .help("Go ahead with recursive removals that look like classic mistakes, such as the root or home directory, or . with a wildcard pattern that didn't match anything, without asking.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Leave missing files alone
+            Arg::new("ignore-missing")
+                .short(rm_compat.then_some('f'))
+                .long("ignore-missing")
+                .help("Ignore files that don't exist. With --rm-compat, -f also stops any questions being asked, like rm -f.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Ask before removing files
+            Arg::new("interactive")
+                .short(rm_compat.then_some('i'))
+                .long("interactive")
+                .value_name("WHEN")
+                .help("Ask before removing each file (always, the default, and -i with --rm-compat), or once before removing more than three files or any directories (once, like -I).")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("always")
+                .value_parser(["never", "once", "always"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Ask once before removing files
+            Arg::new("interactive-once")
+                .short('I')
+                .help("Ask once before removing more than three files or any directories, like rm -I. The same as --interactive=once.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Show each file removed with --rm-compat
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Show each file as it's removed with --rm-compat, which is otherwise quiet like rm. Files are always shown without it.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Case-insensitive matching
+            Arg::new("ignore-case")
+                .short((!rm_compat).then_some('i'))
+                .long("ignore-case")
+                .help("Ignore case when matching --name and --contains patterns and SFTP globs.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Content filter
+            Arg::new("contains")
+                .short('C')
+                .long("contains")
+                .value_name("REGEX")
+                .help("Only delete files whose contents match the regular expression. The first match is noted in the detail output and the report.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Content filter size limit
+            Arg::new("contains-max-size")
+                .long("contains-max-size")
+                .value_name("SIZE")
+                .help("Don't scan files larger than this with --contains. Such files are skipped. Accepts suffixes such as K, M and G.")
+                .num_args(1)
+                .default_value("100M")
+                .value_parser(rdel::utils::parse_size)
+                .requires("contains")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Unicode normalization
+            Arg::new("normalize")
+                .long("normalize")
+                .value_name("FORM")
+                .help("Convert file names to this Unicode normalization form before matching them, so names from lists generated on macOS (NFD) match names on Linux (NFC) and vice versa.")
+                .num_args(1)
+                .value_parser(["nfc", "nfd"])
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Save what a dry-run found as a manifest for apply
+            Arg::new("save-plan")
+                .long("save-plan")
+                .value_name("FILE")
+                .help("With --dry-run, write the files found to FILE as a versioned JSON manifest, with each file's path, size and modification time, its SHA-256 digest with --checksum, and the pattern it matched, for review tools and for apply to carry out later. Only the local regular files that would be deleted outright are written.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Write a script removing what a dry-run found
+            Arg::new("emit-script")
+                .long("emit-script")
+                .value_name("SHELL")
+                .help("With --dry-run, write a script to --script-file that removes the files found, using rm (sh) or Remove-Item (powershell), so the plan can be reviewed and carried out later where rdel isn't installed. Only local files are written; the rest are noted in comments.")
+                .num_args(1)
+                .value_parser(["sh", "powershell"])
+                .requires("script-file")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Where to write the script
+            Arg::new("script-file")
+                .long("script-file")
+                .value_name("FILE")
+                .help("The file --emit-script writes the script to. It is replaced if it exists.")
+                .num_args(1)
+                .requires("emit-script")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Archive before deletion
+            Arg::new("archive-to")
+                .long("archive-to")
+                .value_name("ARCHIVE")
+                .help("Copy each file into a zstd-compressed tar archive (e.g. backup.tar.zst) before removing it. An existing archive is replaced. Files that can't be archived are not removed.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Quarantine instead of deleting
+            Arg::new("quarantine")
+                .long("quarantine")
+                .value_name("DIR")
+                .help("Move files into a folder named after today's date below DIR instead of deleting them. Combine with --retain to purge old quarantine folders on each run.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Move to the trash instead of deleting
+            Arg::new("trash")
+                .long("trash")
+                .help("Move files to the trash (recycle bin) instead of deleting them, so they can be restored.")
+                .num_args(0)
+                .conflicts_with("quarantine")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Quarantine retention
+            Arg::new("retain")
+                .long("retain")
+                .value_name("AGE")
+                .help("How long to keep quarantined files before they are permanently removed (e.g. 14d, 12h).")
+                .num_args(1)
+                .value_parser(humantime::parse_duration)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Configuration file
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Read settings such as the trash limits from this TOML file instead of the default one (rdel/config.toml in the user's configuration directory).")
+                .env("RDEL_CONFIG")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Webhook notification
+            Arg::new("notify-url")
+                .long("notify-url")
+                .value_name("URL")
+                .help("POST the JSON summary to this URL when the run finishes, e.g. a Slack or ops webhook.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Include failures in the notification
+            Arg::new("notify-failures")
+                .long("notify-failures")
+                .help("Include the files that couldn't be removed, and why, in the --notify-url notification.")
+                .num_args(0)
+                .requires("notify-url")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // Desktop notification
+            Arg::new("notify")
+                .long("notify")
+                .help("Show a desktop notification with the summary when the run finishes.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .arg( // StatsD metrics
+            Arg::new("statsd")
+                .long("statsd")
+                .value_name("HOST:PORT")
+                .help("Send metrics for the run (files deleted, bytes freed, errors and duration) to a StatsD server over UDP when the run finishes.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // StatsD metric prefix
+            Arg::new("statsd-prefix")
+                .long("statsd-prefix")
+                .value_name("PREFIX")
+                .help("The prefix for the metric names sent with --statsd.")
+                .num_args(1)
+                .default_value("rdel")
+                .requires("statsd")
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Command to run before each file is removed
+            Arg::new("before-each")
+                .long("before-each")
+                .value_name("COMMAND")
+                .help("Run this shell command before removing each file, with {} replaced by the path (also in $RDEL_PATH). Not run in dry-runs.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Command to run after each file is removed
+            Arg::new("after-each")
+                .long("after-each")
+                .value_name("COMMAND")
+                .help("Run this shell command after each file has been removed or failed to be, with {} replaced by the path and {outcome} by removed or failed (also in $RDEL_PATH and $RDEL_OUTCOME), e.g. to purge it from a CDN. Not run in dry-runs.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Command to run before anything is removed
+            Arg::new("pre-run")
+                .long("pre-run")
+                .value_name("COMMAND")
+                .help("Run this shell command once before the first file is removed, e.g. to take a snapshot, with the JSON summary of the files about to be removed on its standard input. The run stops if it fails. Not run in dry-runs.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Command to run at the end of the run
+            Arg::new("post-run")
+                .long("post-run")
+                .value_name("COMMAND")
+                .help("Run this shell command once the run has finished, e.g. to send an alert, with the JSON summary on its standard input. Not run in dry-runs.")
+                .num_args(1)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::Set)
+        )
+        .arg( // Treat failed hooks as errors
+            Arg::new("fail-on-hook-error")
+                .long("fail-on-hook-error")
+                .help("Treat a failed --before-each or --after-each command like a failed removal, counting towards --max-errors, and fail the run if the --post-run command fails. A file whose --before-each command fails is left alone. Otherwise failed hooks are only warned about.")
+                .num_args(0)
+                .hide(false)
+                .global(true)
+                .action(ArgAction::SetTrue)
+        )
+        .subcommand( // Delete files
+            Command::new("delete")
+                .about("Delete files. This is what happens when files are given without a command.")
+                .arg(files_arg())
+        )
+        .subcommand( // Move files to the trash
+            Command::new("trash")
+                .about("Move files to the trash (recycle bin) instead of deleting them, as with --trash.")
+                .arg(files_arg())
+        )
+        .subcommand( // Restore files from the trash
+            Command::new("restore")
+                .about("Move files back out of the trash to where they were deleted from. Not available on macOS.")
+                .arg(
+                    Arg::new("patterns")
+                        .value_name("PATTERN(S)")
+                        .help("Restore the files whose original paths match these glob patterns (e.g. '/home/me/reports/**'). Quote them, so the shell doesn't expand them.")
+                        .required(true)
+                        .num_args(1..)
+                        .action(ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("on-conflict")
+                        .long("on-conflict")
+                        .value_name("STRATEGY")
+                        .help("What to do when a file's original path has been taken: leave the file in the trash (skip), replace what's there (overwrite), restore it under a numbered name (rename), or ask whether to overwrite (ask). Without it, such files are left in the trash and counted as failed.")
+                        .num_args(1)
+                        .value_parser(["skip", "overwrite", "rename", "ask"])
+                        .action(ArgAction::Set)
+                )
+        )
+        .subcommand( // Show what was removed
+            Command::new("history")
+                .about("List the files removed by earlier runs, as recorded in the audit log given with --audit-log.")
+                .arg(
+                    Arg::new("patterns")
+                        .value_name("PATTERN(S)")
+                        .help("Only list the files whose paths match these glob patterns.")
+                        .num_args(0..)
+                        .action(ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("last")
+                        .long("last")
+                        .value_name("COUNT")
+                        .help("Only list the last COUNT files.")
+                        .num_args(1)
+                        .value_parser(rdel::utils::parse_file_count)
+                        .action(ArgAction::Set)
+                )
+        )
+        .subcommand( // Tell what a run cut short removed
+            Command::new("recover")
+                .about("Read a journal written with --journal, e.g. after a crash, and tell which of the files the run was about to remove were removed and which are still there.")
+                .arg(
+                    Arg::new("journal-file")
+                        .value_name("JOURNAL")
+                        .help("The journal written by the run.")
+                        .required(true)
+                        .num_args(1)
+                        .action(ArgAction::Set)
+                )
+        )
+        .subcommand( // Purge the quarantine or the trash
+            Command::new("purge")
+                .visible_alias("purge-quarantine")
+                .about("Permanently remove the quarantine folders in DIR, or the files in the trash, older than the retention period given with --retain.")
+                .arg(
+                    Arg::new("dir")
+                        .value_name("DIR")
+                        .help("The quarantine directory. Without it, the files deleted to the trash longer ago than --retain are removed from it (not available on macOS).")
+                        .num_args(1)
+                        .action(ArgAction::Set)
+                )
+        )
+        .subcommand( // Delete the files listed in a manifest
+            Command::new("apply")
+                .about("Delete the files listed in a manifest, skipping any that have changed since the manifest was generated.")
+                .arg(
+                    Arg::new("verify")
+                        .long("verify")
+                        .value_name("MANIFEST")
+                        .help("A JSON manifest listing the path and expected size of each file to delete, and optionally its modification time and SHA-256 digest, e.g. one written by --save-plan.")
+                        .required(true)
+                        .num_args(1)
+                        .action(ArgAction::Set)
+                )
+        )
+        .subcommand( // Compare two manifests
+            Command::new("diff")
+                .about("Show which files were added to, dropped from or changed size between two manifests, e.g. plans saved with --save-plan before and after changing a filter.")
+                .arg(
+                    Arg::new("old")
+                        .value_name("OLD")
+                        .help("The earlier manifest.")
+                        .required(true)
+                        .num_args(1)
+                        .action(ArgAction::Set)
+                )
+                .arg(
+                    Arg::new("new")
+                        .value_name("NEW")
+                        .help("The later manifest.")
+                        .required(true)
+                        .num_args(1)
+                        .action(ArgAction::Set)
+                )
+        )
+        .subcommand( // Delete duplicate files
+            Command::new("dedupe")
+                .about("Find files with identical contents below a directory, keep one copy of each and delete the rest.")
+                .arg(
+                    Arg::new("dir")
+                        .value_name("DIR")
+                        .help("The directory to search for duplicates. Subdirectories are included.")
+                        .required(true)
+                        .num_args(1)
+                        .action(ArgAction::Set)
+                )
+                .arg(
+                    Arg::new("keep")
+                        .short('k')
+                        .long("keep")
+                        .value_name("WHICH")
+                        .help("Which copy of each duplicate to keep.")
+                        .num_args(1)
+                        .default_value("oldest")
+                        .value_parser(["oldest", "newest", "shortest-path"])
+                        .action(ArgAction::Set)
+                )
+        )
+        .args(rm_compat_args(rm_compat))
+        .args(fault_injection_args())
+        .get_matches_from(args)
+}
+
+/// The files to delete, given either on their own or to the `delete` and `trash` commands.
+fn files_arg() -> Arg {
+    Arg::new("files")
+        .value_name("FILE(S)")
+        .help("One or more file(s) to process. Wildcards and multiple_occurrences files (e.g. 2019*.pdf 2020*.pdf) are supported. Use the ** glob to recurse (eg. **/*.log). Use - to read the files from standard input, one per line. Note: Case sensitive.")
+        .num_args(1..)
+        .action(ArgAction::Append)
+}
+
+/// The rm options that rdel accepts with --rm-compat, so scripts using them keep working.
+/// --preserve-root is what rdel does anyway.
+fn rm_compat_args(rm_compat: bool) -> Vec<Arg> {
+    if !rm_compat {
+        return Vec::new();
+    }
+    vec![
+        Arg::new("preserve-root") // Accepted from rm
+            .long("preserve-root")
+            .value_name("all")
+            .help("Accepted for compatibility with rm.")
+            .num_args(0..=1)
+            .require_equals(true)
+            .value_parser(["all"])
+            .hide(true)
+            .global(true)
+            .action(ArgAction::Set),
+        Arg::new("no-preserve-root") // Allow removing the root directory, as rm does
+            .long("no-preserve-root")
+            .help("Remove the root directory without asking, like rm.")
+            .num_args(0)
+            .hide(true)
+            .global(true)
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+/// The hidden arguments for testing the automation around rdel, only there when built with the
+/// `fault-injection` feature.
+fn fault_injection_args() -> Vec<Arg> {
+    #[cfg(feature = "fault-injection")]
+    return vec![
+        Arg::new("inject-failures") // Make removals fail on purpose
+            .long("inject-failures")
+            .value_name("RATE|PATTERN")
+            .help("Make a fraction of the removals (e.g. 0.1 or 10%), or those of the files matching a glob pattern (e.g. *.log), fail without touching the files. For testing how automation handles partial failures.")
+            .num_args(1)
+            .value_parser(rdel::fault::FaultInjection::parse)
+            .hide(true)
+            .global(true)
+            .action(ArgAction::Set),
+    ];
+    #[cfg(not(feature = "fault-injection"))]
+    Vec::new()
+}
+
+/// Replaces each `@file` argument with the arguments in the file, one per line, so long lists of
+/// paths can be passed where the command line is limited (e.g. on Windows). Blank lines are
+/// ignored, and response files may refer to other response files. Arguments after `--` are
+/// left alone, so `-- @name` refers to a file whose name starts with `@`.
+fn expand_response_files(args: Vec<OsString>, depth: usize) -> Result<Vec<OsString>, String> {
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            expanded.push(arg);
+            expanded.extend(args.by_ref());
+            break;
+        }
+
+        let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix('@')) else {
+            expanded.push(arg);
+            continue;
+        };
+        if depth >= MAX_RESPONSE_FILE_DEPTH {
+            return Err(format!(
+                "Response file {path} is nested more than {MAX_RESPONSE_FILE_DEPTH} levels deep."
+            ));
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read response file {path}: {err}"))?;
+        let file_args = contents
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.trim().is_empty())
+            .map(OsString::from)
+            .collect();
+        expanded.extend(expand_response_files(file_args, depth + 1)?);
+    } // while arg
+
+    Ok(expanded)
 }
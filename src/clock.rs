@@ -0,0 +1,35 @@
+use std::fmt;
+use std::time::SystemTime;
+
+/// Where the current time comes from for the age filters and the quarantine retention, so
+/// "older than 30d" can be worked out against a fixed time, e.g. in tests or when embedding.
+pub trait Clock: fmt::Debug + Send {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The system's clock, used unless another is given.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock stopped at a given time.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
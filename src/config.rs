@@ -0,0 +1,109 @@
+use serde::{Deserialize, Deserializer};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{expand, utils};
+
+/// The settings read from the configuration file, for things that are set once for a machine or
+/// a user rather than on every command line:
+///
+/// ```toml
+/// [trash]
+/// max_size = "20G"
+/// max_age = "30d"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub trash: TrashConfig,
+}
+
+/// The limits kept to in the trash at the end of every run that moves files to it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    /// The most the trash may hold. The oldest files are purged until it holds no more.
+    #[serde(deserialize_with = "size")]
+    pub max_size: Option<u64>,
+
+    /// How long files are kept in the trash before they are purged.
+    #[serde(deserialize_with = "duration")]
+    pub max_age: Option<Duration>,
+}
+
+impl Config {
+    /// Reads the configuration from `path`, or from the default file if no path is given. The
+    /// default file doesn't have to exist, but a file that was asked for does.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if !required && err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(err) => {
+                return Err(
+                    format!("Unable to read the config file {}: {err}", path.display()).into(),
+                )
+            }
+        };
+        log::debug!("Reading the settings in {}", path.display());
+
+        toml::from_str(&text)
+            .map_err(|err| format!("Invalid config file {}: {err}", path.display()).into())
+    }
+}
+
+/// Returns where the configuration file is looked for when none is given:
+/// `$XDG_CONFIG_HOME/rdel/config.toml`, `~/.config/rdel/config.toml`, or
+/// `%APPDATA%\rdel\config.toml` on Windows.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ if cfg!(windows) => PathBuf::from(env::var_os("APPDATA")?),
+        _ => expand::home_dir()?.join(".config"),
+    };
+    Some(dir.join("rdel").join("config.toml"))
+}
+
+/// Reads a size such as `"20G"`, or a number of bytes.
+fn size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(Some(bytes)),
+        Size::Text(text) => utils::parse_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Reads a duration such as `"30d"` or `"12h"`.
+fn duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    humantime::parse_duration(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,61 @@
+use std::io::{self, BufRead, Write};
+
+use crate::i18n;
+
+/// When to ask before removing files, as with rm's `-i` and `-I`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Interactive {
+    /// Never ask.
+    #[default]
+    Never,
+
+    /// Ask once, before removing more than three files or removing directories recursively.
+    Once,
+
+    /// Ask before removing each file.
+    Always,
+}
+
+impl Interactive {
+    /// The number of files that can be removed without asking first, with `Once`.
+    pub const ONCE_LIMIT: usize = 3;
+
+    /// Converts the command line value into a setting.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "once" => Self::Once,
+            "always" => Self::Always,
+            _ => Self::Never,
+        }
+    }
+}
+
+/// Asks a yes or no question. As with rm, any answer starting with `y` (or with the word for
+/// yes in the language in use, e.g. `j`) is a yes, and anything else (including the end of
+/// standard input) is a no.
+pub fn ask(question: &str) -> io::Result<bool> {
+    Ok(answer(question)?.is_some_and(|answer| {
+        let answer = answer.trim_start().to_lowercase();
+        answer.starts_with('y') || answer.starts_with(i18n::t("y"))
+    }))
+}
+
+/// Asks a question that must be answered with `expected` in full, for things that are hard to
+/// undo. Anything else is a no.
+pub fn ask_for(question: &str, expected: &str) -> io::Result<bool> {
+    Ok(answer(question)?.is_some_and(|answer| answer.trim() == expected))
+}
+
+/// Asks a question and reads the answer, or `None` if standard input ends.
+fn answer(question: &str) -> io::Result<Option<String>> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{question} ")?;
+    stdout.flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        writeln!(stdout)?;
+        return Ok(None);
+    }
+    Ok(Some(answer))
+}
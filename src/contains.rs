@@ -0,0 +1,45 @@
+use regex::bytes::{Regex, RegexBuilder};
+use std::error::Error;
+use std::io::Read;
+
+/// The longest matched text shown in the output and the report.
+const MAX_SNIPPET_LENGTH: usize = 60;
+
+/// Selects files based on their contents.
+#[derive(Debug)]
+pub struct ContentFilter {
+    regex: Regex,
+    max_size: u64,
+}
+
+impl ContentFilter {
+    /// Creates a filter from a regular expression. Files larger than `max_size` bytes are not
+    /// scanned.
+    pub fn new(pattern: &str, max_size: u64, ignore_case: bool) -> Result<Self, Box<dyn Error>> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| format!("Invalid --contains pattern: {err}"))?;
+        Ok(Self { regex, max_size })
+    }
+
+    /// Returns true if a file of this size shouldn't be scanned.
+    pub fn too_large(&self, size: u64) -> bool {
+        size > self.max_size
+    }
+
+    /// Scans the contents for the pattern, reading at most the size limit.
+    /// Returns a description of the first match, if any.
+    pub fn scan(&self, reader: &mut dyn Read) -> Result<Option<String>, Box<dyn Error>> {
+        let mut contents = Vec::new();
+        reader.take(self.max_size).read_to_end(&mut contents)?;
+
+        Ok(self.regex.find(&contents).map(|found| {
+            let text: String = String::from_utf8_lossy(found.as_bytes())
+                .chars()
+                .take(MAX_SNIPPET_LENGTH)
+                .collect();
+            format!("\"{text}\" at byte {}", found.start())
+        }))
+    }
+}
@@ -0,0 +1,216 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{expand, mounts};
+
+/// A recursive removal that looks like one of the classic mistakes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Danger {
+    /// Everything on the system, e.g. `rm -rf /` or `rm -rf $DIR/` with `DIR` unset.
+    Root(String),
+
+    /// The home directory, e.g. `rm -rf ~` or `rm -rf .` run from it.
+    Home(String),
+
+    /// The current directory along with a wildcard pattern that didn't match anything, so the
+    /// shell passed it on as it was, e.g. `rm -r *.tmp .` instead of `rm -r ./*.tmp`.
+    UnmatchedPattern { pattern: String, dir: String },
+
+    /// A directory that is, or holds, the mount point of another file system, e.g. a backup
+    /// volume mounted inside a cache directory.
+    Mount { target: String, mount: String },
+}
+
+impl fmt::Display for Danger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Root(target) => write!(
+                f,
+                "{target} is the root directory: this would remove everything on the system."
+            ),
+            Self::Home(target) => write!(
+                f,
+                "{target} is the home directory: this would remove all of its files."
+            ),
+            Self::UnmatchedPattern { pattern, dir } => write!(
+                f,
+                "{pattern} didn't match any files, and the whole of the current directory {dir} is also to be removed."
+            ),
+            Self::Mount { target, mount } if target == mount => write!(
+                f,
+                "{target} is a mount point: this would remove everything on the file system mounted there."
+            ),
+            Self::Mount { target, mount } => write!(
+                f,
+                "{mount} is mounted inside {target}: this would remove everything on the file system mounted there too."
+            ),
+        }
+    }
+}
+
+/// Checks the canonical targets of a recursive removal for the classic mistakes, and for other
+/// file systems mounted in the way. Returns the first one found, if any.
+pub fn check(targets: &[String]) -> Option<Danger> {
+    let home = expand::home_dir().and_then(|home| fs::canonicalize(home).ok());
+    let cwd = env::current_dir()
+        .ok()
+        .and_then(|cwd| fs::canonicalize(cwd).ok());
+
+    for target in targets {
+        let path = resolve(target);
+        if path.has_root() && path.parent().is_none() {
+            return Some(Danger::Root(target.clone()));
+        }
+        if home.as_ref() == Some(&path) {
+            return Some(Danger::Home(target.clone()));
+        }
+    } // for target
+
+    for target in targets {
+        let path = resolve(target);
+        if !path
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            continue;
+        }
+        if let Some(mount) = mounts::mounts_in(&path).first() {
+            return Some(Danger::Mount {
+                target: target.clone(),
+                mount: mount.display().to_string(),
+            });
+        }
+    } // for target
+
+    let cwd = cwd?;
+    if !targets.iter().any(|target| resolve(target) == cwd) {
+        return None;
+    }
+    targets
+        .iter()
+        .find(|target| {
+            target.contains(['*', '?', '[']) && Path::new(target).symlink_metadata().is_err()
+        })
+        .map(|pattern| Danger::UnmatchedPattern {
+            pattern: pattern.clone(),
+            dir: cwd.display().to_string(),
+        })
+}
+
+/// Returns the directory a target such as `.`, `..` or `/` refers to. Other targets are already
+/// canonical, and are left alone so a symbolic link (which is removed, not what it points to)
+/// isn't mistaken for its target.
+fn resolve(target: &str) -> PathBuf {
+    let path = Path::new(target.strip_prefix("file://").unwrap_or(target));
+    match path.file_name() {
+        Some(_) => path.to_path_buf(),
+        None => fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(targets: &[&str]) -> Vec<String> {
+        targets.iter().map(|target| target.to_string()).collect()
+    }
+
+    fn cwd() -> PathBuf {
+        fs::canonicalize(env::current_dir().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn root_is_found_however_it_is_written() {
+        for target in ["/", "file:///", "/.."] {
+            assert_eq!(
+                check(&targets(&[target])),
+                Some(Danger::Root(target.to_string())),
+                "{target}"
+            );
+        }
+        assert_eq!(
+            check(&targets(&["/tmp/rdel-no-such-dir", "/"])),
+            Some(Danger::Root("/".to_string()))
+        );
+    }
+
+    #[test]
+    fn home_is_found() {
+        let Some(home) = expand::home_dir().and_then(|home| fs::canonicalize(home).ok()) else {
+            return;
+        };
+        if home.parent().is_none() {
+            return;
+        }
+        let home = home.display().to_string();
+        assert_eq!(check(&targets(&[&home])), Some(Danger::Home(home.clone())));
+        assert_eq!(
+            Danger::Home(home.clone()).to_string(),
+            format!("{home} is the home directory: this would remove all of its files.")
+        );
+    }
+
+    #[test]
+    fn dots_are_the_directories_they_refer_to() {
+        let cwd = cwd();
+        assert_eq!(resolve("."), cwd);
+        assert_eq!(resolve(".."), cwd.parent().unwrap());
+        let home = expand::home_dir().and_then(|home| fs::canonicalize(home).ok());
+        if home.as_deref() == cwd.parent() {
+            assert_eq!(
+                check(&targets(&[".."])),
+                Some(Danger::Home("..".to_string()))
+            );
+        }
+        // Anything else is already canonical
+        assert_eq!(resolve("file:///tmp/link"), Path::new("/tmp/link"));
+    }
+
+    #[test]
+    fn unmatched_pattern_with_the_current_directory() {
+        let cwd = cwd();
+        let pattern = "*.rdel-test-tmp";
+        assert_eq!(
+            check(&targets(&[pattern, "."])),
+            Some(Danger::UnmatchedPattern {
+                pattern: pattern.to_string(),
+                dir: cwd.display().to_string(),
+            })
+        );
+        // Without the current directory, or once the pattern matched, it's fine
+        assert_eq!(check(&targets(&[pattern])), None);
+        assert_eq!(check(&targets(&["Cargo.toml", "src/*.rs"])), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn mount_points_are_found() {
+        if !mounts::mounts_in(Path::new("/proc")).contains(&PathBuf::from("/proc")) {
+            return;
+        }
+        let danger = check(&targets(&["/proc"])).unwrap();
+        assert!(
+            matches!(&danger, Danger::Mount { target, .. } if target == "/proc"),
+            "{danger:?}"
+        );
+        assert_eq!(
+            Danger::Mount {
+                target: "/proc".to_string(),
+                mount: "/proc".to_string(),
+            }
+            .to_string(),
+            "/proc is a mount point: this would remove everything on the file system mounted there."
+        );
+        assert_eq!(
+            Danger::Mount {
+                target: "/var/cache".to_string(),
+                mount: "/var/cache/backup".to_string(),
+            }
+            .to_string(),
+            "/var/cache/backup is mounted inside /var/cache: this would remove everything on the file system mounted there too."
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::checksum;
+use crate::deleter::Deleter;
+use crate::utils;
+
+/// Which file in a group of duplicates to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Oldest,
+    Newest,
+    ShortestPath,
+}
+
+impl Keep {
+    /// Converts the command line value into a `Keep`. Unknown values keep the oldest file.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "newest" => Self::Newest,
+            "shortest-path" => Self::ShortestPath,
+            _ => Self::Oldest,
+        }
+    }
+}
+
+/// A file found while scanning for duplicates.
+#[derive(Debug)]
+struct Candidate {
+    path: String,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Finds all regular files below `dir`, without following symbolic links.
+fn collect_files(dir: &Path, files: &mut Vec<Candidate>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            collect_files(&path, files)?;
+        } else if file_type.is_file() {
+            let Some(path_str) = path.to_str() else {
+                log::warn!("Skipping {}: not a valid UTF-8 path.", path.display());
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            files.push(Candidate {
+                path: path_str.to_string(),
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Groups the files found below `dir` by content, keeps one file from each group and deletes
+/// the rest.
+///
+/// Files are first grouped by size so that only files that could possibly be duplicates are
+/// hashed.
+pub fn dedupe(dir: &Path, keep: Keep, deleter: &mut Deleter) -> Result<(), Box<dyn Error>> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    log::debug!("Found {} files in {}", files.len(), dir.display());
+
+    let mut by_size: BTreeMap<u64, Vec<Candidate>> = BTreeMap::new();
+    for file in files {
+        by_size.entry(file.size).or_default().push(file);
+    }
+
+    let mut by_digest: BTreeMap<String, Vec<Candidate>> = BTreeMap::new();
+    for file in by_size
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .flatten()
+    {
+        let digest = checksum::sha256(Path::new(&file.path))?;
+        by_digest.entry(digest).or_default().push(file);
+    }
+
+    for (digest, mut group) in by_digest.into_iter().filter(|(_, group)| group.len() > 1) {
+        deleter.summary.duplicate_group_count += 1;
+
+        // Put the file to keep first; ties are broken by path to keep the choice deterministic.
+        match keep {
+            Keep::Oldest => {
+                group.sort_by(|a, b| a.modified.cmp(&b.modified).then(a.path.cmp(&b.path)))
+            }
+            Keep::Newest => {
+                group.sort_by(|a, b| b.modified.cmp(&a.modified).then(a.path.cmp(&b.path)))
+            }
+            Keep::ShortestPath => {
+                group.sort_by(|a, b| a.path.len().cmp(&b.path.len()).then(a.path.cmp(&b.path)))
+            }
+        }
+
+        let kept = &group[0];
+        let duplicates = &group[1..];
+        log::info!("Keeping: {} ({} duplicates)", kept.path, duplicates.len());
+
+        let reclaimed: u64 = duplicates.iter().map(|file| file.size).sum();
+        for file in duplicates {
+            deleter.remove(&file.path, file.size, Some(&digest))?;
+        }
+
+        log::info!(
+            "Duplicate group {digest}: {} bytes reclaimed.",
+            utils::thousand_separated(reclaimed)
+        );
+    } // for group
+
+    Ok(())
+}
@@ -0,0 +1,3464 @@
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Component, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use std::{error::Error, path::Path};
+
+use crate::age::{AgeFilter, TimeField};
+use crate::archive::Archive;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::backend::{self, Backend, Disposal, Entry, FileType, HardLinks, Local, Removed, Stamp};
+use crate::builder::DeleterBuilder;
+use crate::classify::{self, Kind};
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::confirm::{self, Interactive};
+use crate::contains::ContentFilter;
+use crate::danger::{self, Danger};
+use crate::expr::{Expr, Subject};
+#[cfg(feature = "fault-injection")]
+use crate::fault::FaultInjection;
+use crate::git::TrackedFiles;
+use crate::hook::Hook;
+use crate::hours::ActiveHours;
+use crate::i18n;
+use crate::journal::Journal;
+use crate::lists::OutcomeLists;
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::name::NameFilter;
+use crate::normalize::{self, Normalization};
+use crate::notify::{self, Failure};
+use crate::observer::Observer;
+use crate::order::{self, Order};
+use crate::paths::{self, Resolve};
+use crate::plugin::{Decision, FilterCommand};
+#[cfg(unix)]
+use crate::progress::Progress;
+use crate::quarantine;
+use crate::recovery::{self, Recovery};
+use crate::report::{FileReport, Outcome, Report};
+use crate::script::{Removal, Script, Shell};
+use crate::security::Security;
+use crate::stream::{self, Stream};
+use crate::timeout::TimedOut;
+use crate::timings::Timings;
+use crate::trash;
+use crate::tui::{self, Dashboard};
+use crate::walker::{DirLinks, Special, TreeStats};
+use crate::{checksum, expand, find, metrics, shred, utils};
+
+/// How many targets are scanned at a time before the files found are removed.
+const SCAN_CHUNK_SIZE: usize = 10_000;
+
+/// The default limit for files scanned by `--contains`.
+const DEFAULT_CONTAINS_MAX_SIZE: u64 = 100 << 20;
+
+/// The options controlling how files are removed.
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub max_errors: Option<usize>,
+    pub prompt_on_error: bool,
+    pub interactive: Interactive,
+    pub ignore_missing: bool,
+    pub rm_compat: bool,
+    pub i_know_what_im_doing: bool,
+    pub no_preserve_root: bool,
+    pub max_delete: Option<usize>,
+    pub max_free: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub file_timeout: Option<Duration>,
+
+    /// How many files are removed in each burst, and how long to rest after it, for `--chunk`
+    /// and `--pause`.
+    pub pause: Option<(usize, Duration)>,
+    pub timings: bool,
+    #[cfg(feature = "fault-injection")]
+    pub inject_failures: Option<FaultInjection>,
+    pub show_detail_info: bool,
+    pub dry_run: bool,
+    pub scan_only: bool,
+    pub print_summary: bool,
+    pub checksum_algorithm: Option<String>,
+    pub classify: bool,
+    pub recheck_pending: bool,
+    pub names: Option<NameFilter>,
+    pub age: Option<AgeFilter>,
+    pub where_expr: Option<Expr>,
+    pub filter_cmd: Option<String>,
+    pub contains: Option<ContentFilter>,
+    pub ignore_case: bool,
+    pub normalize: Option<Normalization>,
+    pub audit_file: Option<PathBuf>,
+    pub audit_sync: bool,
+    pub journal_file: Option<PathBuf>,
+    pub report_file: Option<PathBuf>,
+    pub failed_list: Option<PathBuf>,
+    pub print_deleted: Option<PathBuf>,
+    pub print_skipped: Option<PathBuf>,
+    pub progress_fd: Option<i32>,
+    pub tui: bool,
+    pub null_separated: bool,
+    pub protect_tracked: bool,
+    pub recursive: bool,
+
+    /// Whether to ask before removing the directories given as targets, unless the files are
+    /// asked about anyway. Not asked without `--yes`.
+    pub confirm_directories: bool,
+    pub special: Special,
+    pub dir_links: DirLinks,
+    pub paranoid: bool,
+    pub shred: Option<shred::Method>,
+    pub shred_verify: bool,
+    pub scramble_name: bool,
+    pub force_immutable: bool,
+    pub sudo_retry: bool,
+    pub verify: bool,
+    pub confirm_hazardous: bool,
+    pub no_parent_traversal: bool,
+    pub resolve_paths: Option<Resolve>,
+    pub relative_to: Option<PathBuf>,
+    pub skip_in_use: bool,
+    pub recheck: bool,
+    pub order: Option<Order>,
+    pub sync: bool,
+    pub discard: bool,
+    pub allow_subvolumes: bool,
+    pub min_age: Option<Duration>,
+    pub active_hours: Option<ActiveHours>,
+    pub force: bool,
+    pub archive_to: Option<PathBuf>,
+    pub emit_script: Option<(Shell, PathBuf)>,
+    pub save_plan: Option<PathBuf>,
+    pub quarantine: Option<PathBuf>,
+    pub trash: bool,
+    pub trash_max_size: Option<u64>,
+    pub trash_max_age: Option<Duration>,
+    pub retain: Option<Duration>,
+    pub notify_url: Option<String>,
+    pub notify_failures: bool,
+    pub notify_desktop: bool,
+    pub statsd: Option<String>,
+    pub statsd_prefix: String,
+    pub before_each: Option<Hook>,
+    pub after_each: Option<Hook>,
+    pub pre_run: Option<Hook>,
+    pub post_run: Option<Hook>,
+    pub fail_on_hook_error: bool,
+}
+
+impl Settings {
+    /// Reads the settings from the command line arguments.
+    pub fn from_args(cli_args: &ArgMatches) -> Result<Self, Box<dyn Error>> {
+        let ignore_case = cli_args.value_source("ignore-case") == Some(ValueSource::CommandLine);
+        let normalize = cli_args
+            .get_one::<String>("normalize")
+            .map(|value| Normalization::from_arg(value));
+
+        let paranoid = cli_args.value_source("paranoid") == Some(ValueSource::CommandLine);
+        if paranoid && !cfg!(unix) {
+            return Err("--paranoid is only supported on Unix.".into());
+        }
+
+        let sync = cli_args.value_source("sync") == Some(ValueSource::CommandLine);
+        if sync && !cfg!(unix) {
+            return Err("--sync is only supported on Unix.".into());
+        }
+
+        let discard = cli_args.value_source("discard") == Some(ValueSource::CommandLine);
+        if discard && !cfg!(target_os = "linux") {
+            return Err("--discard is only supported on Linux.".into());
+        }
+
+        let allow_subvolumes =
+            cli_args.value_source("allow-subvolumes") == Some(ValueSource::CommandLine);
+        if allow_subvolumes && !cfg!(target_os = "linux") {
+            return Err("--allow-subvolumes is only supported on Linux.".into());
+        }
+
+        let skip_in_use = cli_args.value_source("skip-in-use") == Some(ValueSource::CommandLine);
+        if skip_in_use && !cfg!(any(target_os = "linux", windows)) {
+            return Err("--skip-in-use is only supported on Linux and Windows.".into());
+        }
+
+        // The trash command is --trash, and so can't be combined with the other disposals
+        let trash = cli_args.value_source("trash") == Some(ValueSource::CommandLine)
+            || cli_args.subcommand_name() == Some("trash");
+        if trash {
+            for other in ["quarantine", "shred", "scramble-name"] {
+                if cli_args.value_source(other) == Some(ValueSource::CommandLine) {
+                    return Err(
+                        format!("--{other} can't be used when moving files to the trash.").into(),
+                    );
+                }
+            }
+        }
+
+        // The files are removed again as they are, so only permanent removals can be retried
+        let sudo_retry = cli_args.value_source("sudo-retry") == Some(ValueSource::CommandLine);
+        if sudo_retry {
+            if !cfg!(unix) {
+                return Err("--sudo-retry is only supported on Unix.".into());
+            }
+            if trash {
+                return Err("--sudo-retry can't be used when moving files to the trash.".into());
+            }
+            for other in ["quarantine", "archive-to", "shred"] {
+                if cli_args.value_source(other) == Some(ValueSource::CommandLine) {
+                    return Err(format!("--sudo-retry can't be used with --{other}.").into());
+                }
+            }
+        }
+
+        // The script only repeats what a dry-run found, with plain removals
+        let emit_script = cli_args
+            .get_one::<String>("emit-script")
+            .map(|shell| Shell::from_arg(shell));
+        let emit_script = match (emit_script, cli_args.get_one::<String>("script-file")) {
+            (Some(shell), Some(file)) => Some((shell, PathBuf::from(file))),
+            _ => None,
+        };
+        let save_plan = cli_args.get_one::<String>("save-plan").map(PathBuf::from);
+        for (option, given) in [
+            ("emit-script", emit_script.is_some()),
+            ("save-plan", save_plan.is_some()),
+        ] {
+            if !given {
+                continue;
+            }
+            if cli_args.value_source("dry-run") != Some(ValueSource::CommandLine) {
+                return Err(format!("--{option} only writes the files found by a dry-run.").into());
+            }
+            if trash {
+                return Err(
+                    format!("--{option} can't be used when moving files to the trash.").into(),
+                );
+            }
+            for other in ["quarantine", "shred"] {
+                if cli_args.value_source(other) == Some(ValueSource::CommandLine) {
+                    return Err(format!("--{option} can't be used with --{other}.").into());
+                }
+            }
+        } // for option
+
+        let progress_fd = cli_args.get_one::<i32>("progress-fd").copied();
+        if progress_fd.is_some() && !cfg!(unix) {
+            return Err("--progress-fd is only supported on Unix.".into());
+        }
+
+        // Nothing at all is removed when only scanning, as with a dry-run
+        let scan_only = cli_args.value_source("scan-only") == Some(ValueSource::CommandLine);
+
+        let max_errors = if cli_args.value_source("stop") == Some(ValueSource::CommandLine) {
+            Some(0)
+        } else {
+            cli_args.get_one::<usize>("max-errors").copied()
+        };
+
+        let rm_compat = cli_args.value_source("rm-compat") == Some(ValueSource::CommandLine);
+        let ignore_missing =
+            cli_args.value_source("ignore-missing") == Some(ValueSource::CommandLine);
+
+        // As with rm, the last of -i, -I and -f given decides whether to ask before removing
+        let position = |id| {
+            (cli_args.value_source(id) == Some(ValueSource::CommandLine))
+                .then(|| cli_args.index_of(id))
+                .flatten()
+        };
+        let interactive = [
+            (
+                position("interactive"),
+                cli_args
+                    .get_one::<String>("interactive")
+                    .map_or(Interactive::Always, |value| Interactive::from_arg(value)),
+            ),
+            (position("interactive-once"), Interactive::Once),
+            (
+                position("ignore-missing").filter(|_| rm_compat),
+                Interactive::Never,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(position, interactive)| Some((position?, interactive)))
+        .max_by_key(|(position, _)| *position)
+        .map_or(Interactive::Never, |(_, interactive)| interactive);
+
+        // Ask what to do about failures only when someone is there to answer, and hasn't
+        // already said what should happen. rm never asks.
+        // The dashboard takes up the terminal, so nothing can be asked while it's up
+        let tui = cli_args.value_source("tui") == Some(ValueSource::CommandLine)
+            && io::stdout().is_terminal();
+        let prompt_on_error = cli_args.value_source("quiet") != Some(ValueSource::CommandLine)
+            && !rm_compat
+            && !tui
+            && max_errors.is_none()
+            && io::stdin().is_terminal()
+            && io::stdout().is_terminal();
+
+        let names = match cli_args.get_many::<String>("name") {
+            Some(patterns) => {
+                let patterns: Vec<&str> = patterns.map(String::as_str).collect();
+                Some(NameFilter::new(&patterns, ignore_case, normalize)?)
+            }
+            None => None,
+        };
+
+        let where_expr = match cli_args.get_one::<String>("where") {
+            Some(text) => Some(Expr::parse(text, ignore_case)?),
+            None => None,
+        };
+        let where_expr = Expr::and(where_expr, find::predicates(cli_args)?);
+
+        let config = Config::load(cli_args.get_one::<String>("config").map(Path::new))?;
+
+        let contains = match cli_args.get_one::<String>("contains") {
+            Some(pattern) => {
+                let max_size = cli_args
+                    .get_one::<u64>("contains-max-size")
+                    .copied()
+                    .unwrap_or(DEFAULT_CONTAINS_MAX_SIZE);
+                Some(ContentFilter::new(pattern, max_size, ignore_case)?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            max_errors,
+            prompt_on_error,
+            interactive,
+            ignore_missing,
+            rm_compat,
+            confirm_directories: !rm_compat
+                && cli_args.value_source("yes") != Some(ValueSource::CommandLine),
+            i_know_what_im_doing: cli_args.value_source("i-know-what-im-doing")
+                == Some(ValueSource::CommandLine),
+            no_preserve_root: rm_compat
+                && cli_args.value_source("no-preserve-root") == Some(ValueSource::CommandLine),
+            max_delete: cli_args.get_one::<usize>("max-delete").copied(),
+            max_free: cli_args.get_one::<u64>("max-free").copied(),
+            timeout: cli_args.get_one::<Duration>("timeout").copied(),
+            file_timeout: cli_args.get_one::<Duration>("file-timeout").copied(),
+            pause: cli_args
+                .get_one::<u64>("chunk")
+                .zip(cli_args.get_one::<Duration>("pause"))
+                .map(|(&chunk, &pause)| (usize::try_from(chunk).unwrap_or(usize::MAX), pause)),
+            #[cfg(feature = "fault-injection")]
+            inject_failures: cli_args
+                .get_one::<FaultInjection>("inject-failures")
+                .cloned(),
+            timings: cli_args.value_source("timings") == Some(ValueSource::CommandLine)
+                || (cli_args.value_source("quiet") != Some(ValueSource::CommandLine)
+                    && cli_args.get_count("debug") > 1),
+            show_detail_info: cli_args.value_source("detail-off") != Some(ValueSource::CommandLine),
+            dry_run: cli_args.value_source("dry-run") == Some(ValueSource::CommandLine)
+                || scan_only,
+            scan_only,
+            print_summary: cli_args.value_source("print-summary") == Some(ValueSource::CommandLine),
+            checksum_algorithm: cli_args.get_one::<String>("checksum").cloned(),
+            classify: cli_args.value_source("classify") == Some(ValueSource::CommandLine),
+            recheck_pending: cli_args.value_source("recheck-pending")
+                == Some(ValueSource::CommandLine),
+            names,
+            age: cli_args
+                .get_one::<Duration>("older-than")
+                .map(|&older_than| AgeFilter {
+                    older_than,
+                    field: TimeField::from_arg(
+                        cli_args
+                            .get_one::<String>("time-field")
+                            .map_or("mtime", String::as_str),
+                    ),
+                }),
+            where_expr,
+            filter_cmd: cli_args.get_one::<String>("filter-cmd").cloned(),
+            contains,
+            ignore_case,
+            normalize,
+            audit_file: cli_args.get_one::<String>("audit-log").map(PathBuf::from),
+            audit_sync: cli_args.value_source("audit-sync") == Some(ValueSource::CommandLine),
+            journal_file: cli_args.get_one::<String>("journal").map(PathBuf::from),
+            report_file: cli_args.get_one::<String>("report").map(PathBuf::from),
+            failed_list: cli_args.get_one::<String>("failed-list").map(PathBuf::from),
+            print_deleted: cli_args
+                .get_one::<String>("print-deleted")
+                .map(PathBuf::from),
+            print_skipped: cli_args
+                .get_one::<String>("print-skipped")
+                .map(PathBuf::from),
+            progress_fd,
+            tui,
+            null_separated: cli_args.value_source("null") == Some(ValueSource::CommandLine),
+            protect_tracked: cli_args.value_source("protect-tracked")
+                == Some(ValueSource::CommandLine),
+            recursive: cli_args.value_source("recursive") == Some(ValueSource::CommandLine),
+            // Like rm, --rm-compat removes special files unless told otherwise
+            special: match cli_args.get_one::<String>("special") {
+                Some(value) => Special::from_arg(value),
+                None if rm_compat => Special::Delete,
+                None => Special::Skip,
+            },
+            dir_links: cli_args
+                .get_one::<String>("dir-links")
+                .map_or(DirLinks::Remove, |value| DirLinks::from_arg(value)),
+            paranoid,
+            shred: (cli_args.value_source("shred") == Some(ValueSource::CommandLine)).then(|| {
+                shred::Method::from_arg(
+                    cli_args
+                        .get_one::<String>("shred-method")
+                        .map_or("zeros", String::as_str),
+                )
+            }),
+            shred_verify: cli_args.value_source("shred-verify") == Some(ValueSource::CommandLine),
+            scramble_name: cli_args.value_source("scramble-name") == Some(ValueSource::CommandLine),
+            force_immutable: cli_args.value_source("force-immutable")
+                == Some(ValueSource::CommandLine),
+            sudo_retry,
+            verify: cli_args.value_source("verify-removals") == Some(ValueSource::CommandLine),
+            confirm_hazardous: cli_args.value_source("confirm-hazardous")
+                == Some(ValueSource::CommandLine),
+            no_parent_traversal: cli_args.value_source("no-parent-traversal")
+                == Some(ValueSource::CommandLine),
+            relative_to: match cli_args.get_one::<String>("relative-to") {
+                Some(dir) => Some(
+                    std::path::absolute(expand::expand_path(dir)?)
+                        .map_err(|err| format!("Unable to find the directory {dir}: {err}"))?,
+                ),
+                None => None,
+            },
+            resolve_paths: cli_args
+                .get_one::<String>("resolve-paths")
+                .map(|value| Resolve::from_arg(value)),
+            skip_in_use,
+            recheck: cli_args.value_source("recheck") == Some(ValueSource::CommandLine),
+            order: cli_args
+                .get_one::<String>("order")
+                .map(|value| Order::from_arg(value)),
+            sync,
+            discard,
+            allow_subvolumes,
+            min_age: cli_args.get_one::<Duration>("min-age").copied(),
+            active_hours: cli_args.get_one::<ActiveHours>("active-hours").copied(),
+            force: cli_args.value_source("force") == Some(ValueSource::CommandLine),
+            archive_to: cli_args.get_one::<String>("archive-to").map(PathBuf::from),
+            emit_script,
+            save_plan,
+            quarantine: cli_args.get_one::<String>("quarantine").map(PathBuf::from),
+            trash,
+            trash_max_size: config.trash.max_size,
+            trash_max_age: config.trash.max_age,
+            retain: cli_args.get_one::<Duration>("retain").copied(),
+            notify_url: cli_args.get_one::<String>("notify-url").cloned(),
+            notify_failures: cli_args.value_source("notify-failures")
+                == Some(ValueSource::CommandLine),
+            notify_desktop: cli_args.value_source("notify") == Some(ValueSource::CommandLine),
+            statsd: cli_args.get_one::<String>("statsd").cloned(),
+            statsd_prefix: cli_args
+                .get_one::<String>("statsd-prefix")
+                .cloned()
+                .unwrap_or_else(|| "rdel".to_string()),
+            before_each: cli_args
+                .get_one::<String>("before-each")
+                .map(|command| Hook::new(command)),
+            after_each: cli_args
+                .get_one::<String>("after-each")
+                .map(|command| Hook::new(command)),
+            pre_run: cli_args
+                .get_one::<String>("pre-run")
+                .map(|command| Hook::new(command)),
+            post_run: cli_args
+                .get_one::<String>("post-run")
+                .map(|command| Hook::new(command)),
+            fail_on_hook_error: cli_args.value_source("fail-on-hook-error")
+                == Some(ValueSource::CommandLine),
+        })
+    }
+}
+
+/// Counters describing what happened during a run.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub total_file_count: usize,
+    pub processed_file_count: usize,
+    pub skipped_file_count: usize,
+    pub errors: ErrorCounts,
+    pub types: TypeCounts,
+    pub timed_out_file_count: usize,
+    pub changed_file_count: usize,
+    pub unmatched_file_count: usize,
+    pub protected_file_count: usize,
+
+    /// Listed files skipped as their paths climb up with `..`, for `--no-parent-traversal`.
+    pub traversal_file_count: usize,
+    pub recent_file_count: usize,
+    pub in_use_file_count: usize,
+    pub duplicate_group_count: usize,
+    pub quarantined_file_count: usize,
+    pub trashed_file_count: usize,
+    pub moved_file_count: usize,
+    pub shredded_file_count: usize,
+    pub hook_failed_count: usize,
+    pub queued_file_count: usize,
+    pub pending_release_file_count: usize,
+    pub pending_release_size: u64,
+
+    /// Files, links and directories copied to the trash, the quarantine or `--move-to` on
+    /// another file system, rather than moved there, and the bytes copied. Each took up space on
+    /// both file systems until the original was deleted.
+    pub copied_file_count: usize,
+    pub copied_size: u64,
+
+    /// The most space taken up twice at once while copying, i.e. the size of the largest entry
+    /// copied, as entries are copied one at a time.
+    pub copied_peak_size: u64,
+
+    /// Files removed by the run under `sudo`, for `--sudo-retry`. They are counted as removed too.
+    pub sudo_file_count: usize,
+
+    /// Files counted as removed that were still there at the end of the run, or missing from
+    /// where they were moved to, for `--verify`.
+    pub unverified_file_count: usize,
+    pub purged_file_count: usize,
+    pub purged_size: u64,
+    pub total_file_size: u64,
+    pub shared_file_size: u64,
+    pub unallocated_file_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_size: Option<u64>,
+
+    /// The files removed by what they hold, for `--classify`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub kinds: BTreeMap<Kind, usize>,
+
+    /// What was removed from each directory given as a target of a recursive run.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub directories: BTreeMap<String, DirectoryTotals>,
+
+    /// The types of the file systems files were removed from, e.g. `ext4` and `nfs4`.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub filesystem_types: BTreeSet<String>,
+}
+
+/// The files removed from below a directory given as a target, and their total size.
+#[derive(Debug, Default, Serialize)]
+pub struct DirectoryTotals {
+    pub file_count: usize,
+    pub size: u64,
+}
+
+/// How much was scanned, and how quickly, for `--scan-only`.
+#[derive(Debug, Default)]
+struct ScanStats {
+    targets: usize,
+    files: usize,
+    size: u64,
+    duration: Duration,
+}
+
+impl ScanStats {
+    /// Prints the amount scanned and the throughput to the log.
+    fn print(&self) {
+        let secs = self.duration.as_secs_f64().max(f64::EPSILON);
+        log::info!("{:<29}{:5}", i18n::t("Targets scanned:"), self.targets);
+        log::info!("{:<29}{:5}", i18n::t("Files found:"), self.files);
+        log::info!(
+            "{:<29}{}",
+            i18n::t("Bytes found:"),
+            utils::thousand_separated(self.size)
+        );
+        log::info!("{:<29}{:.1?}", i18n::t("Time taken:"), self.duration);
+        log::info!(
+            "{:<29}{}",
+            i18n::t("Targets per second:"),
+            utils::thousand_separated((self.targets as f64 / secs) as u64)
+        );
+        log::info!(
+            "{:<29}{}",
+            i18n::t("Files per second:"),
+            utils::thousand_separated((self.files as f64 / secs) as u64)
+        );
+    }
+}
+
+/// The files that couldn't be removed, by the cause of the error.
+#[derive(Debug, Default, Serialize)]
+pub struct ErrorCounts {
+    pub permission_denied: usize,
+    pub not_found: usize,
+    pub in_use: usize,
+    pub is_a_directory: usize,
+
+    /// Files that are immutable or append-only, or in a directory that is.
+    pub immutable: usize,
+    pub io_error: usize,
+}
+
+/// The entries removed, by their kind. The contents of the directory trees removed are counted
+/// too.
+#[derive(Debug, Default, Serialize)]
+pub struct TypeCounts {
+    pub regular_files: usize,
+    pub directories: usize,
+    pub symlinks: usize,
+
+    /// Devices, sockets and named pipes removed, with `--special delete`.
+    pub special_files: usize,
+
+    /// Devices, sockets and named pipes left alone, as they are by default.
+    pub special_files_skipped: usize,
+}
+
+impl TypeCounts {
+    /// Counts an entry removed, or everything in it if it's a directory tree. The special files
+    /// in a tree are removed or skipped as `special` says.
+    fn add(&mut self, file_type: Option<FileType>, tree: Option<&TreeStats>, special: Special) {
+        if let Some(tree) = tree {
+            self.directories += (tree.dir_count + 1).saturating_sub(tree.dirs_kept_count);
+            self.symlinks += tree.symlink_count;
+            match special {
+                Special::Skip => self.special_files_skipped += tree.special_count,
+                Special::Delete => self.special_files += tree.special_count,
+            }
+            self.regular_files += tree.file_count - tree.symlink_count - tree.special_count;
+            return;
+        }
+        match file_type {
+            Some(FileType::File) => self.regular_files += 1,
+            Some(FileType::Dir) => self.directories += 1,
+            Some(FileType::Symlink) => self.symlinks += 1,
+            None => self.special_files += 1,
+        }
+    }
+}
+
+impl ErrorCounts {
+    /// Counts an error under its cause. Errors that aren't recognized, including those that
+    /// aren't I/O errors at all, are counted as I/O errors.
+    fn add(&mut self, err: &(dyn Error + 'static)) {
+        #[cfg(unix)]
+        let errno = err
+            .downcast_ref::<rustix::io::Errno>()
+            .map(|&errno| io::Error::from(errno));
+        #[cfg(not(unix))]
+        let errno: Option<io::Error> = None;
+
+        #[cfg(unix)]
+        if err.is::<crate::immutable::Locked>() {
+            self.immutable += 1;
+            return;
+        }
+
+        let count = match err.downcast_ref::<io::Error>().or(errno.as_ref()) {
+            // Sharing and lock violations, when another process has the file open
+            Some(err) if cfg!(windows) && matches!(err.raw_os_error(), Some(32 | 33)) => {
+                &mut self.in_use
+            }
+            Some(err) => match err.kind() {
+                io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                    &mut self.permission_denied
+                }
+                io::ErrorKind::NotFound => &mut self.not_found,
+                io::ErrorKind::ResourceBusy | io::ErrorKind::ExecutableFileBusy => &mut self.in_use,
+                io::ErrorKind::IsADirectory | io::ErrorKind::DirectoryNotEmpty => {
+                    &mut self.is_a_directory
+                }
+                _ => &mut self.io_error,
+            },
+            None => &mut self.io_error,
+        };
+        *count += 1;
+    }
+}
+
+impl Summary {
+    /// The number of bytes actually returned to the file systems: the size of the files removed,
+    /// less what they shared with files that remain (hard links and reflinked extents), the
+    /// holes in sparse files and the files still held open on NFS.
+    pub fn freed_size(&self) -> u64 {
+        self.total_file_size
+            .saturating_sub(self.shared_file_size)
+            .saturating_sub(self.unallocated_file_size)
+            .saturating_sub(self.pending_release_size)
+    }
+
+    /// Prints the summary information to the log.
+    pub fn print(&self) {
+        log::info!(
+            "{:<29}{:5}",
+            i18n::t("Total files examined:"),
+            self.total_file_count
+        );
+        log::info!(
+            "{:<29}{:5}",
+            i18n::t("Files removed:"),
+            self.processed_file_count
+        );
+        let types = [
+            ("Regular files:", self.types.regular_files),
+            ("Directories:", self.types.directories),
+            ("Symbolic links:", self.types.symlinks),
+            ("Special files:", self.types.special_files),
+        ];
+        for (label, count) in types.into_iter().filter(|&(_, count)| count > 0) {
+            log::info!("  {:<27}{:5}", i18n::t(label), count);
+        } // for label
+        if self.sudo_file_count > 0 {
+            log::info!(
+                "  {:<27}{:5}",
+                i18n::t("Removed with sudo:"),
+                self.sudo_file_count
+            );
+        }
+        if self.unverified_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Failed verification:"),
+                self.unverified_file_count
+            );
+        }
+        if self.types.special_files_skipped > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Special files skipped:"),
+                self.types.special_files_skipped
+            );
+        }
+        if !self.kinds.is_empty() {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files classified:"),
+                self.kinds.values().sum::<usize>()
+            );
+            let mut kinds: Vec<_> = self.kinds.iter().collect();
+            kinds.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+            for (kind, count) in kinds {
+                log::info!("  {:<27}{:5}", i18n::t(kind.label()), count);
+            } // for kind
+        }
+        log::info!(
+            "{:<29}{:5}",
+            i18n::t("Files skipped due to errors:"),
+            self.skipped_file_count
+        );
+        if self.errors.permission_denied > 0 {
+            log::info!(
+                "  {:<27}{:5}",
+                i18n::t("Permission denied:"),
+                self.errors.permission_denied
+            );
+        }
+        if self.errors.not_found > 0 {
+            log::info!("  {:<27}{:5}", i18n::t("Not found:"), self.errors.not_found);
+        }
+        if self.errors.in_use > 0 {
+            log::info!("  {:<27}{:5}", i18n::t("In use:"), self.errors.in_use);
+        }
+        if self.errors.is_a_directory > 0 {
+            log::info!(
+                "  {:<27}{:5}",
+                i18n::t("Is a directory:"),
+                self.errors.is_a_directory
+            );
+        }
+        if self.errors.immutable > 0 {
+            log::info!(
+                "  {:<27}{:5}",
+                i18n::t("Immutable or append-only:"),
+                self.errors.immutable
+            );
+        }
+        if self.errors.io_error > 0 {
+            log::info!("  {:<27}{:5}", i18n::t("I/O errors:"), self.errors.io_error);
+        }
+        if self.timed_out_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files timed out:"),
+                self.timed_out_file_count
+            );
+        }
+        if self.changed_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files skipped as changed:"),
+                self.changed_file_count
+            );
+        }
+        if self.unmatched_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files not matching filters:"),
+                self.unmatched_file_count
+            );
+        }
+        if self.protected_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files tracked by git:"),
+                self.protected_file_count
+            );
+        }
+        if self.traversal_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files rejected for ..:"),
+                self.traversal_file_count
+            );
+        }
+        if self.recent_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files modified too recently:"),
+                self.recent_file_count
+            );
+        }
+        if self.in_use_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files in use:"),
+                self.in_use_file_count
+            );
+        }
+        if self.quarantined_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files quarantined:"),
+                self.quarantined_file_count
+            );
+        }
+        if self.trashed_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files trashed:"),
+                self.trashed_file_count
+            );
+        }
+        if self.moved_file_count > 0 {
+            log::info!("{:<29}{:5}", i18n::t("Files moved:"), self.moved_file_count);
+        }
+        if self.shredded_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files shredded:"),
+                self.shredded_file_count
+            );
+        }
+        if self.hook_failed_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Hooks failed:"),
+                self.hook_failed_count
+            );
+        }
+        if self.queued_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files left for active hours:"),
+                self.queued_file_count
+            );
+        }
+        if self.purged_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files purged:"),
+                self.purged_file_count
+            );
+            log::info!(
+                "{:<29}{:>}",
+                i18n::t("Bytes purged:"),
+                utils::thousand_separated(self.purged_size)
+            );
+        }
+        if self.duplicate_group_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Duplicate groups found:"),
+                self.duplicate_group_count
+            );
+        }
+        log::info!(
+            "{:<29}{:>}",
+            i18n::t("Bytes freed:"),
+            utils::thousand_separated(self.freed_size())
+        );
+        if self.shared_file_size > 0 {
+            log::info!(
+                "{:<29}{:>}",
+                i18n::t("Bytes shared, not freed:"),
+                utils::thousand_separated(self.shared_file_size)
+            );
+        }
+        if self.unallocated_file_size > 0 {
+            log::info!(
+                "{:<29}{:>}",
+                i18n::t("Bytes in sparse file holes:"),
+                utils::thousand_separated(self.unallocated_file_size)
+            );
+        }
+        if self.pending_release_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Files pending release:"),
+                self.pending_release_file_count
+            );
+            log::info!(
+                "{:<29}{:>}",
+                i18n::t("Bytes pending release:"),
+                utils::thousand_separated(self.pending_release_size)
+            );
+        }
+        if self.copied_file_count > 0 {
+            log::info!(
+                "{:<29}{:5}",
+                i18n::t("Copied across file systems:"),
+                self.copied_file_count
+            );
+            log::info!(
+                "{:<29}{:>}",
+                i18n::t("Bytes copied:"),
+                utils::thousand_separated(self.copied_size)
+            );
+            log::info!(
+                "{:<29}{:>}",
+                i18n::t("Most bytes stored twice:"),
+                utils::thousand_separated(self.copied_peak_size)
+            );
+        }
+        if let Some(archive_size) = self.archive_size {
+            log::info!(
+                "{:<29}{:>}",
+                i18n::t("Archive size:"),
+                utils::thousand_separated(archive_size)
+            );
+        }
+
+        // The largest directories first, as those are usually what's being looked for
+        let mut directories: Vec<_> = self.directories.iter().collect();
+        directories.sort_by_key(|(_, totals)| std::cmp::Reverse(totals.size));
+        for (dir, totals) in directories {
+            log::info!(
+                "{}",
+                i18n::tf(
+                    "{dir}: {count} files, {size} bytes.",
+                    &[
+                        ("dir", dir),
+                        ("count", &totals.file_count),
+                        ("size", &utils::thousand_separated(totals.size)),
+                    ]
+                )
+            );
+        } // for dir
+    }
+}
+
+/// An entry that passed the filters, along with what was learnt about it on the way.
+#[derive(Debug)]
+struct Candidate {
+    entry: Entry,
+    digest: Option<String>,
+    matched: Option<String>,
+
+    /// The entry's size and modification time before the filters looked at it.
+    stamp: Option<Stamp>,
+
+    /// The entry's modification time, if it's needed for `--order` or `--save-plan`.
+    modified: Option<SystemTime>,
+
+    /// The kind of the entry, for the summary. `None` for special files.
+    file_type: Option<FileType>,
+
+    /// What the file holds, for `--classify`.
+    kind: Option<Kind>,
+
+    /// The type of file system the entry is on, e.g. `nfs4`, if the backend knows.
+    filesystem: Option<&'static str>,
+}
+
+impl Candidate {
+    /// What the candidate is ordered by for `--order`.
+    fn key(&self) -> order::Key<'_> {
+        order::Key {
+            path: &self.entry.name,
+            size: self.entry.size,
+            modified: self.modified,
+        }
+    }
+}
+
+/// What of an entry's size might not be freed by removing it, measured while it's still there
+/// and counted once it's gone.
+#[derive(Debug, Default)]
+struct Unfreed {
+    /// The bytes shared with other files through reflinked extents.
+    shared: u64,
+
+    /// The entry's hard links, if it has more than one.
+    links: Option<HardLinks>,
+
+    /// The bytes in the holes of a sparse file.
+    unallocated: u64,
+}
+
+/// Removes files according to the settings, keeping track of what has been done.
+///
+/// The deleter doesn't know where the files live: each target is handed to the backend for its
+/// URI scheme, which finds the entries and removes them. Filters, checksums, the archive, the
+/// audit log, the report and the summary are handled here, the same way for every backend.
+pub struct Deleter {
+    pub settings: Settings,
+    pub summary: Summary,
+    audit_log: Option<AuditLog>,
+    journal: Option<Journal>,
+    report: Option<Report>,
+    archive: Option<Archive>,
+    script: Option<Script>,
+
+    /// The files found by a dry-run, for `--save-plan`.
+    plan: Option<Manifest>,
+    failures: Vec<Failure>,
+    tracked: Option<TrackedFiles>,
+    filter_cmd: Option<FilterCommand>,
+    started: Instant,
+    skipped_similar: HashSet<String>,
+    confirmed: Option<bool>,
+    pre_run_done: bool,
+    outside_active_hours: bool,
+    trees: HashMap<String, TreeStats>,
+
+    /// What was known about the security of the files being removed, for the audit log.
+    security: HashMap<String, Security>,
+
+    /// What removing each file of the batch might not free, counted once it's removed.
+    unfreed: HashMap<String, Unfreed>,
+
+    /// The hard links not yet removed of each file with several, by device and inode number.
+    links_left: HashMap<(u64, u64), u64>,
+
+    /// The `.nfs` files that deleted files were kept as, with their sizes, for
+    /// `--recheck-pending`.
+    pending_release: Vec<(PathBuf, u64)>,
+
+    /// The targets of the chunk being run that might have been given in an earlier one.
+    repeated: HashSet<String>,
+
+    /// The directories given as targets whose removal was confirmed, or didn't need to be, so
+    /// they may be removed again under `sudo` without asking.
+    confirmed_dirs: HashSet<String>,
+
+    /// The local files that permission to remove was denied for, for `--sudo-retry`.
+    denied: Vec<String>,
+
+    /// The entries removed and what became of them, for `--verify`.
+    removed: Vec<(String, Removed)>,
+
+    /// The files removed since the last rest, for `--chunk`.
+    removed_since_pause: usize,
+
+    /// The targets as they were supplied, by their canonical paths, for `--resolve-paths`.
+    given: HashMap<String, String>,
+
+    /// The directory the paths shown are relative to, for `--relative-to`, in the same form as
+    /// the paths.
+    base: Option<PathBuf>,
+    timings: Option<Timings>,
+    scanned: ScanStats,
+    clock: Box<dyn Clock>,
+    observers: Vec<Box<dyn Observer>>,
+    backends: BTreeMap<String, Box<dyn Backend>>,
+}
+
+impl Deleter {
+    /// Creates a new deleter. Audit records are only written, and the archive only created,
+    /// when files are actually removed.
+    pub fn new(settings: Settings) -> Result<Self, Box<dyn Error>> {
+        Self::new_with_clock(settings, SystemClock)
+    }
+
+    /// Creates a new deleter that takes the current time from `clock` instead of the system's
+    /// clock, including for whether the run is within `--active-hours`.
+    pub fn new_with_clock(
+        mut settings: Settings,
+        clock: impl Clock + 'static,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Outside the active hours, the files found are only reported, and left for a run
+        // within them
+        let outside_active_hours = settings
+            .active_hours
+            .is_some_and(|hours| !hours.contains(clock.now()));
+        if let Some(hours) = settings.active_hours.filter(|_| outside_active_hours) {
+            log::info!(
+                "Outside the active hours {hours}: the files found are only reported, not removed."
+            );
+            settings.dry_run = true;
+        }
+
+        let audit_log = match &settings.audit_file {
+            Some(audit_file) if !settings.dry_run => Some(AuditLog::open(audit_file)?),
+            _ => None,
+        };
+        let journal = match &settings.journal_file {
+            Some(journal_file) if !settings.dry_run => Some(Journal::create(journal_file)?),
+            _ => None,
+        };
+        let archive = match &settings.archive_to {
+            Some(archive_file) if !settings.dry_run => Some(Archive::create(archive_file)?),
+            _ => None,
+        };
+        let script = match &settings.emit_script {
+            Some((shell, script_file)) => Some(Script::create(script_file, *shell)?),
+            None => None,
+        };
+        let plan = settings.save_plan.is_some().then(Manifest::new);
+        let report = settings.report_file.is_some().then(Report::default);
+        // The paths are canonical unless asked for as given or absolute
+        let base = settings
+            .relative_to
+            .as_ref()
+            .map(|dir| match settings.resolve_paths {
+                Some(Resolve::Given | Resolve::Absolute) => dir.clone(),
+                _ => std::fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()),
+            });
+        let tracked = settings.protect_tracked.then(TrackedFiles::default);
+        let timings = settings.timings.then(Timings::default);
+        let mut observers: Vec<Box<dyn Observer>> = Vec::new();
+        if settings.print_deleted.is_some() || settings.print_skipped.is_some() {
+            observers.push(Box::new(OutcomeLists::open(
+                settings.print_deleted.as_deref(),
+                settings.print_skipped.as_deref(),
+                settings.null_separated,
+            )?));
+        }
+        if settings.tui {
+            observers.push(Box::new(Dashboard::new()));
+        }
+        #[cfg(unix)]
+        if let Some(fd) = settings.progress_fd {
+            observers.push(Box::new(Progress::to_fd(fd)?));
+        }
+        let filter_cmd = match &settings.filter_cmd {
+            Some(command) => Some(FilterCommand::spawn(command)?),
+            None => None,
+        };
+
+        Ok(Self {
+            settings,
+            summary: Summary::default(),
+            audit_log,
+            journal,
+            report,
+            archive,
+            script,
+            plan,
+            failures: Vec::new(),
+            tracked,
+            filter_cmd,
+            started: Instant::now(),
+            skipped_similar: HashSet::new(),
+            confirmed: None,
+            pre_run_done: false,
+            outside_active_hours,
+            trees: HashMap::new(),
+            security: HashMap::new(),
+            unfreed: HashMap::new(),
+            links_left: HashMap::new(),
+            pending_release: Vec::new(),
+            repeated: HashSet::new(),
+            confirmed_dirs: HashSet::new(),
+            denied: Vec::new(),
+            removed: Vec::new(),
+            removed_since_pause: 0,
+            given: HashMap::new(),
+            base,
+            timings,
+            scanned: ScanStats::default(),
+            clock: Box::new(clock),
+            observers,
+            backends: BTreeMap::new(),
+        })
+    }
+
+    /// Tells `observer` about each file as the run goes, and about the summary at the end.
+    pub fn with_observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Starts configuring a deleter in code, without going through the command line.
+    pub fn builder() -> DeleterBuilder {
+        DeleterBuilder::default()
+    }
+
+    /// Uses `clock` for the current time with the age filters, `--min-age`, the quarantine
+    /// folder's name and the quarantine retention, instead of the system's clock, so ages are
+    /// worked out deterministically. Whether the run is within `--active-hours` was settled when
+    /// the deleter was created; see [`Deleter::new_with_clock`].
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Removes everything the targets refer to. Each target is a local path or a URI such as
+    /// `s3://bucket/key`, and is handed to the backend for its scheme.
+    ///
+    /// `~` and environment variables are expanded, the names are normalized if requested and local
+    /// paths are canonicalized first. Targets given more than once (e.g. via different relative
+    /// paths) are only processed once.
+    ///
+    /// The targets are taken in chunks, so lists of any length (e.g. from standard input) can be
+    /// processed without holding them all in memory. Every target in a chunk is resolved and
+    /// filtered before anything in it is removed, so the totals are known up front. To keep the
+    /// memory used flat, only a hash is kept of the targets in earlier chunks. As hashes can
+    /// collide, a target matching one is still looked at, but not counted as an error if it's
+    /// gone.
+    pub fn run<I>(&mut self, targets: I) -> Result<(), Box<dyn Error>>
+    where
+        I: IntoIterator<Item = Result<String, Box<dyn Error>>>,
+    {
+        let hasher = RandomState::new();
+        let mut earlier = HashSet::new();
+        let mut targets = targets.into_iter();
+        loop {
+            let mut chunk = Vec::new();
+            let mut seen = HashSet::new();
+            for target in targets.by_ref() {
+                let target =
+                    normalize::normalize(&expand::expand_path(&target?)?, self.settings.normalize);
+                let canonical = backend::canonical(&target);
+                if !seen.insert(canonical.clone()) {
+                    log::debug!("Skipping {canonical}: given more than once.");
+                    continue;
+                }
+                if earlier.contains(&hasher.hash_one(&canonical)) {
+                    self.repeated.insert(canonical.clone());
+                }
+                if self.settings.resolve_paths.is_some() && canonical != target {
+                    self.given.insert(canonical.clone(), target);
+                }
+                let target = canonical;
+                chunk.push(target);
+                if chunk.len() == SCAN_CHUNK_SIZE {
+                    break;
+                }
+            } // for target
+
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            self.check_danger(&chunk)?;
+            self.run_chunk(&chunk)?;
+            earlier.extend(seen.iter().map(|target| hasher.hash_one(target)));
+            self.repeated.clear();
+
+            // Nothing more is removed once the question asked by -I has been answered no
+            if self.confirmed == Some(false) {
+                return Ok(());
+            }
+        } // loop
+    }
+
+    /// Stops a recursive run that looks like one of the classic mistakes, such as removing the
+    /// root or home directory, unless it's confirmed at the prompt or with
+    /// `--i-know-what-im-doing`. Dry-runs only warn about it.
+    fn check_danger(&self, targets: &[String]) -> Result<(), Box<dyn Error>> {
+        if !self.settings.recursive {
+            return Ok(());
+        }
+        let Some(danger) = danger::check(targets) else {
+            return Ok(());
+        };
+
+        // As with rm, --no-preserve-root allows removing the root directory
+        let allowed = self.settings.i_know_what_im_doing
+            || (self.settings.no_preserve_root && matches!(danger, Danger::Root(_)));
+        if allowed || self.settings.dry_run {
+            log::warn!("{danger}");
+            return Ok(());
+        }
+
+        if io::stdin().is_terminal()
+            && confirm::ask_for(
+                &format!("{danger} {}", i18n::t("Type yes to go ahead:")),
+                i18n::t("yes"),
+            )?
+        {
+            return Ok(());
+        }
+        Err(
+            format!("{danger} Stopping. Use --i-know-what-im-doing if this is really intended.")
+                .into(),
+        )
+    }
+
+    /// Scans a chunk of targets, and then removes what was found.
+    fn run_chunk(&mut self, targets: &[String]) -> Result<(), Box<dyn Error>> {
+        let scanning = Instant::now();
+        let mut found = self.scan(targets)?;
+        self.scanned.targets += targets.len();
+        self.scanned.duration += scanning.elapsed();
+        if let Some(order) = self.settings.order {
+            found.sort_by(|(_, a), (_, b)| order.compare(&a.key(), &b.key()));
+        }
+        self.confirm_directories(targets, &mut found)?;
+        if !found.is_empty() {
+            let size: u64 = found
+                .iter()
+                .map(|(_, candidate)| candidate.entry.size)
+                .sum();
+            log::info!(
+                "{}",
+                i18n::tf(
+                    "Found {count} files to remove, {size} bytes in total.",
+                    &[
+                        ("count", &found.len()),
+                        ("size", &utils::thousand_separated(size))
+                    ]
+                )
+            );
+            for observer in &mut self.observers {
+                observer.on_found(found.len(), size);
+            }
+            self.scanned.files += found.len();
+            self.scanned.size += size;
+        }
+        if self.settings.scan_only || !self.confirm_once(&found)? {
+            return Ok(());
+        }
+        self.run_pre_run(&found)?;
+
+        // Whatever was looked up during the scan is checked again as the files are removed
+        for backend in self.backends.values_mut() {
+            backend.clear_prefetched();
+        }
+
+        // Hand each run of candidates for the same backend over to it together
+        let mut found = found.into_iter().peekable();
+        while let Some((scheme, candidate)) = found.next() {
+            let mut candidates = vec![candidate];
+            while let Some((_, candidate)) = found.next_if(|(next, _)| *next == scheme) {
+                candidates.push(candidate);
+            }
+            self.with_backend(&scheme, |deleter, backend| {
+                deleter.remove_candidates(backend, candidates)
+            })?;
+        } // while scheme
+
+        Ok(())
+    }
+
+    /// Shows what is in each directory given as a target of a recursive run, and asks before
+    /// removing them, unless that's asked with `-i` or `-I` anyway or `--yes` is given. The
+    /// directories are skipped if the answer is no. Without a terminal to ask on, the run stops
+    /// before anything is removed.
+    fn confirm_directories(
+        &mut self,
+        targets: &[String],
+        found: &mut Vec<(String, Candidate)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let is_target = |name: &str| {
+            targets
+                .iter()
+                .any(|target| target.strip_prefix("file://").unwrap_or(target) == name)
+        };
+        let dirs: Vec<String> = found
+            .iter()
+            .map(|(_, candidate)| &candidate.entry.name)
+            .filter(|name| self.trees.contains_key(*name) && is_target(name))
+            .cloned()
+            .collect();
+        if dirs.is_empty() {
+            return Ok(());
+        }
+
+        for dir in &dirs {
+            let stats = self.trees[dir];
+            log::info!(
+                "{}",
+                i18n::tf(
+                    "{path}: {files} files in {dirs} directories, {size} bytes.",
+                    &[
+                        ("path", &self.shown(dir)),
+                        ("files", &utils::thousand_separated(stats.file_count)),
+                        ("dirs", &utils::thousand_separated(stats.dir_count)),
+                        ("size", &utils::thousand_separated(stats.size)),
+                    ]
+                )
+            );
+        } // for dir
+        if !self.settings.confirm_directories
+            || self.settings.interactive != Interactive::Never
+            || self.settings.dry_run
+            || self.settings.scan_only
+        {
+            // They are asked about one by one, or all at once, before they're removed
+            self.confirmed_dirs.extend(dirs);
+            return Ok(());
+        }
+
+        if !io::stdin().is_terminal() {
+            return Err(format!(
+                "Stopping before removing {} directories given as targets. Use --yes to remove them without asking.",
+                dirs.len()
+            )
+            .into());
+        }
+        let question = i18n::t("Remove {count} directories and everything in them?")
+            .replace("{count}", &dirs.len().to_string());
+        if confirm::ask(&question)? {
+            self.confirmed_dirs.extend(dirs);
+            return Ok(());
+        }
+        for (_, candidate) in found.iter() {
+            if dirs.contains(&candidate.entry.name) {
+                self.skip_unmatched(
+                    &candidate.entry.name,
+                    candidate.entry.size,
+                    "Not confirmed.",
+                );
+            }
+        } // for candidate
+        found.retain(|(_, candidate)| !dirs.contains(&candidate.entry.name));
+        Ok(())
+    }
+
+    /// Runs the `--pre-run` command before the first files are removed, with the summary of
+    /// those files (the number found and their size) on its standard input. Fails if the
+    /// command does, so nothing is removed without e.g. the snapshot it was to take.
+    fn run_pre_run(&mut self, found: &[(String, Candidate)]) -> Result<(), Box<dyn Error>> {
+        let Some(hook) = &self.settings.pre_run else {
+            return Ok(());
+        };
+        if self.pre_run_done || self.settings.dry_run || found.is_empty() {
+            return Ok(());
+        }
+        self.pre_run_done = true;
+
+        let about_to_remove = Summary {
+            total_file_count: found.len(),
+            total_file_size: found
+                .iter()
+                .map(|(_, candidate)| candidate.entry.size)
+                .sum(),
+            ..Summary::default()
+        };
+        hook.run_with_input(&serde_json::to_vec(&about_to_remove)?)
+            .map_err(|err| format!("{err}. Stopping before anything is removed.").into())
+    }
+
+    /// Asks once, with `--interactive=once`, before removing more than a few files or any
+    /// directories. Returns whether the files found can be removed.
+    fn confirm_once(&mut self, found: &[(String, Candidate)]) -> Result<bool, Box<dyn Error>> {
+        if self.settings.interactive != Interactive::Once || self.settings.dry_run {
+            return Ok(true);
+        }
+        if let Some(confirmed) = self.confirmed {
+            return Ok(confirmed);
+        }
+        if found.len() <= Interactive::ONCE_LIMIT && !self.settings.recursive {
+            return Ok(true);
+        }
+
+        let question = if self.settings.recursive {
+            i18n::t("Remove {count} files recursively?")
+        } else {
+            i18n::t("Remove {count} files?")
+        };
+        let confirmed = confirm::ask(&question.replace("{count}", &found.len().to_string()))?;
+        self.confirmed = Some(confirmed);
+        Ok(confirmed)
+    }
+
+    /// Resolves the targets using their backends and applies the filters, without removing
+    /// anything. Returns the candidates along with the scheme of the backend for each. The local
+    /// files are looked up in parallel first, which matters most on slow network file systems.
+    fn scan(&mut self, targets: &[String]) -> Result<Vec<(String, Candidate)>, Box<dyn Error>> {
+        let local: Vec<&str> = targets
+            .iter()
+            .map(String::as_str)
+            .filter(|target| backend::scheme_of(target) == "file")
+            .collect();
+        if !local.is_empty() {
+            self.with_backend("file", |_, backend| {
+                backend.prefetch(&local);
+                Ok(())
+            })?;
+        }
+
+        let mut found = Vec::new();
+        for target in targets {
+            self.check_deadline(target)?;
+            let scheme = backend::scheme_of(target);
+            self.with_backend(scheme, |deleter, backend| {
+                // A file that takes too long to look at is left for next time
+                let entries = match backend.resolve(target) {
+                    Err(err) if err.is::<TimedOut>() => {
+                        deleter.summary.total_file_count += 1;
+                        return deleter.record_failed(target, 0, None, None, &*err);
+                    }
+                    // Like rm, a file that isn't there doesn't stop the run
+                    Err(err) if is_not_found(&*err) && deleter.settings.ignore_missing => {
+                        log::debug!("Skipping {target}: it doesn't exist.");
+                        return Ok(());
+                    }
+                    Err(err) if is_not_found(&*err) && deleter.repeated.contains(target) => {
+                        log::debug!("Skipping {target}: given before, and removed already.");
+                        return Ok(());
+                    }
+                    // Without --ignore-missing it doesn't either, but it's counted as an error
+                    Err(err) if is_not_found(&*err) => {
+                        log::warn!("{} doesn't exist.", deleter.shown(target));
+                        deleter.summary.total_file_count += 1;
+                        return deleter.record_failed(target, 0, None, None, &*err);
+                    }
+                    entries => entries?,
+                };
+
+                // A directory removed recursively gets its own totals in the summary, whether
+                // it's removed as a whole or as the entries in it
+                let dir = target.strip_prefix("file://").unwrap_or(target);
+                if deleter.settings.recursive {
+                    for entry in &entries {
+                        if let Some(stats) = backend.tree_stats(entry) {
+                            deleter.trees.insert(entry.name.clone(), stats);
+                        }
+                    } // for entry
+                    let below = |entry: &Entry| {
+                        entry.name != dir && Path::new(&entry.name).starts_with(dir)
+                    };
+                    if deleter.trees.contains_key(dir) || entries.iter().any(below) {
+                        deleter
+                            .summary
+                            .directories
+                            .entry(dir.to_string())
+                            .or_default();
+                    }
+                }
+
+                for entry in entries {
+                    match deleter.select(backend, entry.clone()) {
+                        Ok(Some(candidate)) => found.push((scheme.to_string(), candidate)),
+                        Ok(None) => {}
+                        Err(err) if err.is::<TimedOut>() => {
+                            deleter.summary.total_file_count += 1;
+                            deleter.record_failed(&entry.name, entry.size, None, None, &*err)?
+                        }
+                        Err(err) => return Err(err),
+                    }
+                } // for entry
+                Ok(())
+            })?;
+        } // for target
+
+        Ok(found)
+    }
+
+    /// Removes a single local file whose size (and optionally digest) has already been determined.
+    /// The filters are not applied.
+    pub fn remove(
+        &mut self,
+        filename: &str,
+        current_file_size: u64,
+        digest: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.is_protected(filename, current_file_size)? {
+            return Ok(());
+        }
+
+        let candidate = Candidate {
+            entry: Entry {
+                name: filename.to_string(),
+                size: current_file_size,
+            },
+            digest: digest.map(str::to_string),
+            matched: None,
+            stamp: None,
+            modified: None,
+            file_type: None,
+            kind: None,
+            filesystem: None,
+        };
+
+        self.with_backend("file", |deleter, backend| {
+            deleter.remove_found(backend, candidate)
+        })
+    }
+
+    /// Removes a single local file like `remove`, but disposes of it as given instead of
+    /// using the backend configured for the run, e.g. to move it to the trash.
+    pub fn dispose(
+        &mut self,
+        filename: &str,
+        current_file_size: u64,
+        disposal: Disposal,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.is_protected(filename, current_file_size)? {
+            return Ok(());
+        }
+
+        let candidate = Candidate {
+            entry: Entry {
+                name: filename.to_string(),
+                size: current_file_size,
+            },
+            digest: None,
+            matched: None,
+            stamp: None,
+            modified: None,
+            file_type: None,
+            kind: None,
+            filesystem: None,
+        };
+
+        // Only files that were to be deleted can be deleted with sudo
+        let denied = self.denied.len();
+        let permanent = matches!(disposal, Disposal::Delete);
+        let mut backend = Local::new(disposal, &self.settings);
+        self.remove_found(&mut backend, candidate)?;
+        if !permanent {
+            self.denied.truncate(denied);
+        }
+        backend.finish()
+    }
+
+    /// Removes a file that was found without the backend, e.g. from a manifest. With --paranoid,
+    /// the backend first resolves it too, so it knows what the file is when it comes to remove it.
+    fn remove_found(
+        &mut self,
+        backend: &mut dyn Backend,
+        mut candidate: Candidate,
+    ) -> Result<(), Box<dyn Error>> {
+        candidate.file_type = backend.file_type(&candidate.entry).ok().flatten();
+        candidate.filesystem = backend.filesystem_type(&candidate.entry);
+        if self.settings.paranoid {
+            if let Err(err) = backend.resolve(&candidate.entry.name) {
+                self.summary.total_file_count += 1;
+                return self.record_failed(
+                    &candidate.entry.name,
+                    candidate.entry.size,
+                    candidate.digest.as_deref(),
+                    None,
+                    &*err,
+                );
+            }
+        }
+
+        self.remove_candidates(backend, vec![candidate])
+    }
+
+    /// Calls `f` with the backend for the scheme, creating the backend the first time it's needed.
+    fn with_backend<F>(&mut self, scheme: &str, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut Self, &mut dyn Backend) -> Result<(), Box<dyn Error>>,
+    {
+        // Take the backend out while it's in use, so both it and the deleter can be borrowed
+        let mut backend = match self.backends.remove(scheme) {
+            Some(backend) => backend,
+            None => backend::create(scheme, &self.settings, self.clock.now())?,
+        };
+        let result = f(self, &mut *backend);
+        self.backends.insert(scheme.to_string(), backend);
+
+        result
+    }
+
+    /// Checks an entry's name, age and contents if requested, and calculates its checksum.
+    /// Returns `None` if the entry doesn't match the filters.
+    fn select(
+        &mut self,
+        backend: &mut dyn Backend,
+        entry: Entry,
+    ) -> Result<Option<Candidate>, Box<dyn Error>> {
+        if self.is_protected(&entry.name, entry.size)? {
+            return Ok(None);
+        }
+
+        // Unlike rm, devices, sockets and named pipes are left alone unless asked for
+        let file_type = backend.file_type(&entry)?;
+        if file_type.is_none() && self.settings.special == Special::Skip {
+            self.summary.total_file_count += 1;
+            self.summary.types.special_files_skipped += 1;
+            let reason = "Special file (a device, socket or named pipe).";
+            log::warn!(
+                "Skipping: {}. {reason} Use --special delete to remove it.",
+                self.shown(&entry.name)
+            );
+            self.add_to_report(
+                &entry.name,
+                entry.size,
+                Outcome::Skipped,
+                Some(reason),
+                None,
+                None,
+            );
+            return Ok(None);
+        }
+
+        // Remembered so entries that change while the run goes on can be left alone
+        let stamp = backend.stamp(&entry)?;
+
+        if let Some(filter) = &self.settings.names {
+            if !filter.matches(&entry.name) {
+                self.skip_unmatched(&entry.name, entry.size, "Name doesn't match.");
+                return Ok(None);
+            }
+        }
+
+        if let Some(filter) = self.settings.age {
+            let reason = match backend.time(&entry, filter.field)? {
+                Some(time) if filter.is_old_enough(time, self.clock.now()) => None,
+                Some(_) => Some(format!(
+                    "{} is newer than {}.",
+                    filter.field.name(),
+                    humantime::format_duration(filter.older_than)
+                )),
+                None => Some(format!("No {} available.", filter.field.name())),
+            };
+
+            if let Some(reason) = reason {
+                self.skip_unmatched(&entry.name, entry.size, &reason);
+                return Ok(None);
+            }
+        }
+
+        if let Some(expr) = &self.settings.where_expr {
+            let mut subject = Subject {
+                entry: &entry,
+                backend,
+                now: self.clock.now(),
+            };
+            if !expr.matches(&mut subject, self.settings.ignore_case)? {
+                self.skip_unmatched(
+                    &entry.name,
+                    entry.size,
+                    "Doesn't match the filter expression.",
+                );
+                return Ok(None);
+            }
+        }
+
+        let mut matched = None;
+        if let Some(filter) = &self.settings.contains {
+            let reason = if filter.too_large(entry.size) {
+                log::warn!(
+                    "Not scanning {}: larger than the --contains-max-size limit.",
+                    entry.name
+                );
+                Some("Too large to scan.")
+            } else {
+                matched = filter.scan(&mut backend.open(&entry)?)?;
+                matched.is_none().then_some("Contents don't match.")
+            };
+
+            if let Some(reason) = reason {
+                self.skip_unmatched(&entry.name, entry.size, reason);
+                return Ok(None);
+            }
+        }
+
+        if let Some(filter) = self.filter_cmd.as_mut() {
+            if filter.decide(&entry.name)? == Decision::Keep {
+                self.skip_unmatched(&entry.name, entry.size, "Kept by the filter command.");
+                return Ok(None);
+            }
+        }
+
+        let digest = match self.settings.checksum_algorithm {
+            Some(_) => Some(checksum::sha256_reader(&mut backend.open(&entry)?)?),
+            None => None,
+        };
+
+        // Only regular files are looked into. A file that can't be read is left out of the
+        // breakdown rather than failing the run.
+        let kind = if self.settings.classify && file_type == Some(FileType::File) {
+            match backend
+                .open(&entry)
+                .and_then(|mut reader| Ok(classify::sample(&mut reader)?))
+            {
+                Ok(kind) => Some(kind),
+                Err(err) => {
+                    log::warn!("Unable to classify {}: {err}", entry.name);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let modified = match self.settings.order {
+            Some(order) if order.uses_time() => backend.time(&entry, TimeField::Modified)?,
+            _ if self.settings.save_plan.is_some() => backend.time(&entry, TimeField::Modified)?,
+            _ => None,
+        };
+
+        let filesystem = backend.filesystem_type(&entry);
+
+        Ok(Some(Candidate {
+            entry,
+            digest,
+            matched,
+            stamp,
+            modified,
+            file_type,
+            kind,
+            filesystem,
+        }))
+    }
+
+    /// Archives and removes the candidates, in batches of the size the backend asks for.
+    fn remove_candidates(
+        &mut self,
+        backend: &mut dyn Backend,
+        candidates: Vec<Candidate>,
+    ) -> Result<(), Box<dyn Error>> {
+        let action = backend.action();
+
+        // A batch isn't split by a rest, so with --chunk the batches are made to fill each
+        // burst evenly, e.g. two of 250 files for bursts of 500
+        let mut batch_size = backend.batch_size().max(1);
+        if let Some((chunk, _)) = self.settings.pause {
+            batch_size = chunk.div_ceil(chunk.div_ceil(batch_size));
+        }
+
+        for batch in candidates.chunks(batch_size) {
+            let mut pending = Vec::with_capacity(batch.len());
+            let mut stop = None;
+            for candidate in batch {
+                let Candidate {
+                    entry,
+                    digest,
+                    matched,
+                    stamp,
+                    ..
+                } = candidate;
+                match self.is_too_recent(backend, entry) {
+                    Ok(false) => {}
+                    Ok(true) => continue,
+                    Err(err) if err.is::<TimedOut>() => {
+                        self.summary.total_file_count += 1;
+                        self.record_failed(
+                            &entry.name,
+                            entry.size,
+                            digest.as_deref(),
+                            matched.as_deref(),
+                            &*err,
+                        )?;
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                }
+                if self.is_in_use(backend, entry) {
+                    continue;
+                }
+
+                match self.has_changed(backend, entry, *stamp) {
+                    Ok(false) => {}
+                    Ok(true) => continue,
+                    Err(err) => {
+                        self.summary.total_file_count += 1;
+                        self.record_failed(
+                            &entry.name,
+                            entry.size,
+                            digest.as_deref(),
+                            matched.as_deref(),
+                            &*err,
+                        )?;
+                        continue;
+                    }
+                }
+
+                // The entries already waiting in the batch are still removed
+                if let Err(err) = self.check_budget(entry, pending.len()) {
+                    stop = Some(err);
+                    break;
+                }
+
+                if !self.accepts_hazard(backend, entry)? {
+                    self.skip_unmatched(&entry.name, entry.size, "Not confirmed.");
+                    continue;
+                }
+
+                if self.settings.interactive == Interactive::Always
+                    && !self.settings.dry_run
+                    && !confirm::ask(&i18n::tf("Remove {path}?", &[("path", &entry.name)]))?
+                {
+                    self.skip_unmatched(&entry.name, entry.size, "Not confirmed.");
+                    continue;
+                }
+
+                let allocated = self.measure_unfreed(backend, entry);
+                self.announce(
+                    action,
+                    &entry.name,
+                    entry.size,
+                    allocated,
+                    digest.as_deref(),
+                    matched.as_deref(),
+                );
+
+                if self.settings.dry_run {
+                    self.record_dry_run(candidate, action)?;
+                    continue;
+                }
+                pending.push(candidate);
+            } // for candidate
+
+            if !pending.is_empty() {
+                self.remove_pending(backend, pending)?;
+            }
+            self.unfreed.clear();
+            if let Some(err) = stop {
+                return Err(err);
+            }
+        } // for batch
+
+        Ok(())
+    }
+
+    /// Removes a batch of candidates using the backend, and records what happened to each.
+    fn remove_pending(
+        &mut self,
+        backend: &mut dyn Backend,
+        pending: Vec<&Candidate>,
+    ) -> Result<(), Box<dyn Error>> {
+        let pending = self.recheck(backend, pending)?;
+        let pending = self.prepare(backend, pending)?;
+        #[cfg(feature = "fault-injection")]
+        let pending = self.inject_failures(pending)?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        self.rest(pending.len());
+
+        let entries: Vec<Entry> = pending
+            .iter()
+            .map(|candidate| candidate.entry.clone())
+            .collect();
+        // The security of the files can only be read while they are still there
+        if self.audit_log.is_some() {
+            for entry in &entries {
+                if let Some(security) = backend.security(entry) {
+                    self.security.insert(entry.name.clone(), security);
+                }
+            } // for entry
+        }
+
+        // The removals are on record before any of them is made
+        if self.settings.audit_sync {
+            self.record_intended(&pending)?;
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.intend(&entries)?;
+        }
+
+        let removing = Instant::now();
+        let results = backend.remove(&entries)?;
+        self.log_timing(&entries, removing.elapsed());
+
+        for (candidate, result) in pending.into_iter().zip(results) {
+            match result {
+                Ok(removed) => {
+                    self.count_copied(backend, &candidate.entry);
+                    self.count_kept(backend, &candidate.entry);
+                    self.record_removed(candidate, removed)?;
+                }
+                Err(err) => self.recover(backend, candidate, err)?,
+            }
+        } // for candidate
+        self.security.clear();
+        if let Some(audit_log) = self.audit_log.as_mut().filter(|_| self.settings.audit_sync) {
+            audit_log.sync()?;
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Measures what removing the entry might not free, while it's still there to look at.
+    /// Returns the space it takes up if it's a sparse file, which is less than its size.
+    fn measure_unfreed(&mut self, backend: &mut dyn Backend, entry: &Entry) -> Option<u64> {
+        let shared = backend.shared_size(entry).unwrap_or_else(|err| {
+            log::debug!("Unable to check {} for shared extents: {err}", entry.name);
+            0
+        });
+        let links = backend.hard_links(entry);
+        let allocated = backend
+            .allocated_size(entry)
+            .unwrap_or_default()
+            .filter(|&allocated| allocated < entry.size);
+        self.unfreed.insert(
+            entry.name.clone(),
+            Unfreed {
+                shared,
+                links,
+                unallocated: allocated.map_or(0, |allocated| entry.size - allocated),
+            },
+        );
+        allocated
+    }
+
+    /// Counts the bytes that removing the entry didn't free: those it shares with other files,
+    /// or all of them while another hard link to it remains, and the holes if it's sparse. The
+    /// last of its links to be removed frees them.
+    fn count_unfreed(&mut self, entry: &Entry) {
+        let Some(unfreed) = self.unfreed.remove(&entry.name) else {
+            return;
+        };
+        let linked = match unfreed.links {
+            Some(links) => {
+                let left = self.links_left.entry(links.id).or_insert(links.count);
+                *left = left.saturating_sub(1);
+                *left > 0
+            }
+            None => false,
+        };
+        if linked {
+            self.summary.shared_file_size += entry.size;
+        } else {
+            self.summary.shared_file_size += unfreed.shared;
+            self.summary.unallocated_file_size += unfreed.unallocated;
+        }
+    }
+
+    /// Counts what the backend had to copy to another file system to dispose of the entry, as
+    /// it took up space on both until the original was deleted.
+    fn count_copied(&mut self, backend: &mut dyn Backend, entry: &Entry) {
+        if let Some(copied) = backend.copied(entry) {
+            self.summary.copied_file_count += copied.file_count;
+            self.summary.copied_size += copied.size;
+            self.summary.copied_peak_size = self.summary.copied_peak_size.max(copied.size);
+        }
+    }
+
+    /// Notes the directories of a tree that the backend had to leave, as special files were kept
+    /// in them, so they aren't counted as removed.
+    fn count_kept(&mut self, backend: &mut dyn Backend, entry: &Entry) {
+        let kept = backend.dirs_kept(entry);
+        if let Some(stats) = self.trees.get_mut(&entry.name).filter(|_| kept > 0) {
+            stats.dirs_kept_count = kept;
+        }
+    }
+
+    /// Writes an audit record for each candidate about to be removed, marked as intended, and
+    /// flushes them to the disk, for `--audit-sync`.
+    fn record_intended(&mut self, pending: &[&Candidate]) -> Result<(), Box<dyn Error>> {
+        let Some(audit_log) = self.audit_log.as_mut() else {
+            return Ok(());
+        };
+        for candidate in pending {
+            let (filename, digest) = (candidate.entry.name.as_str(), candidate.digest.as_deref());
+            let mut record = AuditRecord::new(filename, candidate.entry.size);
+            record.checksum_algorithm = digest.map(|_| "sha256");
+            record.checksum = digest;
+            record.security = self.security.get(filename);
+            record.intended = true;
+            audit_log.record(&record)?;
+        } // for candidate
+        audit_log.sync()
+    }
+
+    /// Rests for as long as `--pause` says before removing the next `count` files, if they would
+    /// make the burst since the last rest longer than `--chunk` allows.
+    fn rest(&mut self, count: usize) {
+        let Some((chunk, pause)) = self.settings.pause else {
+            return;
+        };
+        if self.removed_since_pause > 0 && self.removed_since_pause + count > chunk {
+            log::info!(
+                "{}",
+                i18n::tf(
+                    "Resting for {pause} after removing {count} files.",
+                    &[
+                        ("pause", &humantime::format_duration(pause)),
+                        ("count", &self.removed_since_pause),
+                    ]
+                )
+            );
+            thread::sleep(pause);
+            self.removed_since_pause = 0;
+        }
+        self.removed_since_pause += count;
+    }
+
+    /// Looks at each candidate once more if `--recheck` is set, after any questions, and returns
+    /// those that are still as they were found. The ones that changed meanwhile are skipped, so
+    /// they are neither handed to the hooks nor archived.
+    fn recheck<'a>(
+        &mut self,
+        backend: &mut dyn Backend,
+        pending: Vec<&'a Candidate>,
+    ) -> Result<Vec<&'a Candidate>, Box<dyn Error>> {
+        if !self.settings.recheck {
+            return Ok(pending);
+        }
+
+        let mut remaining = Vec::with_capacity(pending.len());
+        for candidate in pending {
+            match self.has_changed(backend, &candidate.entry, candidate.stamp) {
+                Ok(false) => remaining.push(candidate),
+                Ok(true) => {
+                    // It was counted as removed when it was announced
+                    self.summary.total_file_count -= 1;
+                    self.summary.total_file_size -= candidate.entry.size;
+                }
+                Err(err) => self.record_failed(
+                    &candidate.entry.name,
+                    candidate.entry.size,
+                    candidate.digest.as_deref(),
+                    candidate.matched.as_deref(),
+                    &*err,
+                )?,
+            }
+        } // for candidate
+        Ok(remaining)
+    }
+
+    /// Runs the `--before-each` command for each candidate and copies it into the archive, if
+    /// one is being written, and returns those that are ready to be removed. The ones for which
+    /// either failed are counted as failures.
+    fn prepare<'a>(
+        &mut self,
+        backend: &mut dyn Backend,
+        pending: Vec<&'a Candidate>,
+    ) -> Result<Vec<&'a Candidate>, Box<dyn Error>> {
+        let mut ready = Vec::with_capacity(pending.len());
+        for candidate in pending {
+            let err = match self.run_before_each(&candidate.entry.name) {
+                Some(err) => err,
+                None => match self.archive_entry(backend, &candidate.entry) {
+                    Ok(()) => {
+                        ready.push(candidate);
+                        continue;
+                    }
+                    Err(err) => err,
+                },
+            };
+            self.record_failed(
+                &candidate.entry.name,
+                candidate.entry.size,
+                candidate.digest.as_deref(),
+                candidate.matched.as_deref(),
+                &*err,
+            )?;
+        } // for candidate
+        Ok(ready)
+    }
+
+    /// Fails the removals picked by `--inject-failures` without touching the files, and returns
+    /// the candidates left to remove.
+    #[cfg(feature = "fault-injection")]
+    fn inject_failures<'a>(
+        &mut self,
+        pending: Vec<&'a Candidate>,
+    ) -> Result<Vec<&'a Candidate>, Box<dyn Error>> {
+        let Some(injection) = self.settings.inject_failures.clone() else {
+            return Ok(pending);
+        };
+
+        let mut remaining = Vec::with_capacity(pending.len());
+        for candidate in pending {
+            match injection.failure(&candidate.entry.name) {
+                Some(err) => self.record_failed(
+                    &candidate.entry.name,
+                    candidate.entry.size,
+                    candidate.digest.as_deref(),
+                    candidate.matched.as_deref(),
+                    &*err,
+                )?,
+                None => remaining.push(candidate),
+            }
+        } // for candidate
+
+        Ok(remaining)
+    }
+
+    /// Logs how long removing the entries took, for `--timings`, and warns if it took much longer
+    /// than usual. Entries removed together are each given an equal share of the time.
+    fn log_timing(&mut self, entries: &[Entry], took: Duration) {
+        let Some(timings) = self.timings.as_mut() else {
+            return;
+        };
+        let Some(count) = u32::try_from(entries.len()).ok().filter(|&count| count > 0) else {
+            return;
+        };
+
+        let each = took / count;
+        let name = if count == 1 {
+            log::info!("Removed {} in {took:.1?}.", entries[0].name);
+            entries[0].name.clone()
+        } else {
+            log::info!("Removed {count} entries in {took:.1?}, {each:.1?} each.");
+            format!("{count} entries starting with {}", entries[0].name)
+        };
+
+        if let Some(average) = timings.add(each) {
+            log::warn!(
+                "Slow removal: {name} took {each:.1?}, against an average of {average:.1?}."
+            );
+        }
+    }
+
+    /// Deals with a file that couldn't be removed. When prompting, asks whether to retry it,
+    /// skip it (or every file failing the same way) or abort the run.
+    fn recover(
+        &mut self,
+        backend: &mut dyn Backend,
+        candidate: &Candidate,
+        mut err: Box<dyn Error>,
+    ) -> Result<(), Box<dyn Error>> {
+        let Candidate {
+            entry,
+            digest,
+            matched,
+            ..
+        } = candidate;
+
+        while self.settings.prompt_on_error {
+            let similarity = recovery::similarity(&*err);
+            if self.skipped_similar.contains(&similarity) {
+                break;
+            }
+
+            match recovery::ask(&entry.name, &*err)? {
+                Recovery::Retry => match backend.remove(std::slice::from_ref(entry))?.pop() {
+                    Some(Ok(removed)) => {
+                        self.count_copied(backend, entry);
+                        return self.record_removed(candidate, removed);
+                    }
+                    Some(Err(retry_err)) => err = retry_err,
+                    None => break,
+                },
+                Recovery::Skip => break,
+                Recovery::SkipSimilar => {
+                    self.skipped_similar.insert(similarity);
+                    break;
+                }
+                Recovery::Abort => {
+                    self.add_failure(
+                        &entry.name,
+                        entry.size,
+                        digest.as_deref(),
+                        matched.as_deref(),
+                        &*err,
+                    );
+                    return Err(format!(
+                        "Unable to remove file {}. Aborted.",
+                        self.shown(&entry.name)
+                    )
+                    .into());
+                }
+            }
+        } // while
+
+        self.record_failed(
+            &entry.name,
+            entry.size,
+            digest.as_deref(),
+            matched.as_deref(),
+            &*err,
+        )
+    }
+
+    /// Warns about removing an entry that is riskier to remove than most, such as a setuid
+    /// program or another user's file in `/tmp`, and with `--confirm-hazardous` asks whether to
+    /// go ahead. Returns whether to remove it. Without a terminal to ask on, it isn't removed.
+    fn accepts_hazard(
+        &mut self,
+        backend: &mut dyn Backend,
+        entry: &Entry,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(hazard) = backend.hazard(entry) else {
+            return Ok(true);
+        };
+        log::warn!("{} {hazard}.", entry.name);
+        if !self.settings.confirm_hazardous || self.settings.dry_run {
+            return Ok(true);
+        }
+
+        Ok(io::stdin().is_terminal()
+            && confirm::ask(&i18n::tf("Remove {path} anyway?", &[("path", &entry.name)]))?)
+    }
+
+    /// Returns an error, which stops the run, if removing the entry would go over `--max-delete`
+    /// or `--max-free`. `pending` is how many entries are already waiting to be removed.
+    fn check_budget(&self, entry: &Entry, pending: usize) -> Result<(), Box<dyn Error>> {
+        self.check_deadline(&entry.name)?;
+
+        if let Some(max_delete) = self.settings.max_delete {
+            if self.summary.processed_file_count + pending >= max_delete {
+                return Err(format!(
+                    "Stopping before {}: {max_delete} files have been removed, as many as --max-delete allows.",
+                    entry.name
+                )
+                .into());
+            }
+        }
+
+        if let Some(max_free) = self.settings.max_free {
+            if self.summary.total_file_size + entry.size > max_free {
+                return Err(format!(
+                    "Stopping before {}: removing it would free more than the {} bytes --max-free allows.",
+                    entry.name,
+                    utils::thousand_separated(max_free)
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns an error if the run has taken longer than `--timeout` allows, so it stops before
+    /// the next file.
+    fn check_deadline(&self, next: &str) -> Result<(), Box<dyn Error>> {
+        match self.settings.timeout {
+            Some(timeout) if self.started.elapsed() > timeout => Err(format!(
+                "Stopping before {next}: the run has taken longer than the {} --timeout allows.",
+                humantime::format_duration(timeout)
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records a file that was left alone because it no longer matches what was expected.
+    pub fn skip_changed(&mut self, filename: &str, reason: &str) {
+        self.summary.total_file_count += 1;
+        self.summary.changed_file_count += 1;
+        log::warn!("Skipping: {}. {reason}", self.shown(filename));
+        self.add_to_report(filename, 0, Outcome::Skipped, Some(reason), None, None);
+    }
+
+    /// Returns true, and records the file as skipped, if its path climbs up out of a directory
+    /// with `..` and `--no-parent-traversal` is given, as a path listed in a plan or manifest
+    /// shouldn't. The path is taken as it's written, ignoring `.` and repeated separators, so
+    /// `..` is caught however it's disguised, but symbolic links aren't followed.
+    pub fn traverses_parent(&mut self, filename: &str) -> bool {
+        if !self.settings.no_parent_traversal
+            || !Path::new(filename)
+                .components()
+                .any(|component| component == Component::ParentDir)
+        {
+            return false;
+        }
+
+        let reason = "The path climbs up out of a directory with `..`.";
+        self.summary.total_file_count += 1;
+        self.summary.traversal_file_count += 1;
+        log::warn!("Skipping: {}. {reason}", self.shown(filename));
+        self.add_to_report(filename, 0, Outcome::Skipped, Some(reason), None, None);
+        true
+    }
+
+    /// Returns true, and records the file as skipped, if it's a local file tracked by git
+    /// and tracked files are protected.
+    fn is_protected(&mut self, filename: &str, size: u64) -> Result<bool, Box<dyn Error>> {
+        let Some(tracked) = self.tracked.as_mut() else {
+            return Ok(false);
+        };
+        if backend::scheme_of(filename) != "file" {
+            return Ok(false);
+        }
+
+        let path = PathBuf::from(backend::canonical(filename));
+        let Some(root) = tracked.tracked_in(&path)? else {
+            return Ok(false);
+        };
+
+        let reason = format!("Tracked by git in {}.", root.display());
+        self.summary.total_file_count += 1;
+        self.summary.protected_file_count += 1;
+        log::warn!("Skipping: {}. {reason}", self.shown(filename));
+        self.add_to_report(filename, size, Outcome::Skipped, Some(&reason), None, None);
+
+        Ok(true)
+    }
+
+    /// Returns true, and records the entry as skipped, if it was modified within the `--min-age`
+    /// window and `--force` wasn't given. Entries without a modification time are allowed.
+    fn is_too_recent(
+        &mut self,
+        backend: &mut dyn Backend,
+        entry: &Entry,
+    ) -> Result<bool, Box<dyn Error>> {
+        let Some(min_age) = self.settings.min_age.filter(|_| !self.settings.force) else {
+            return Ok(false);
+        };
+        let Some(modified) = backend.time(entry, TimeField::Modified)? else {
+            return Ok(false);
+        };
+
+        let age = self
+            .clock
+            .now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age >= min_age {
+            return Ok(false);
+        }
+
+        let reason = format!(
+            "Modified within the last {}. Use --force to delete it anyway.",
+            humantime::format_duration(min_age)
+        );
+        self.summary.total_file_count += 1;
+        self.summary.recent_file_count += 1;
+        log::warn!("Skipping: {}. {reason}", self.shown(&entry.name));
+        self.add_to_report(
+            &entry.name,
+            entry.size,
+            Outcome::Skipped,
+            Some(&reason),
+            None,
+            None,
+        );
+
+        Ok(true)
+    }
+
+    /// Returns true, and records the entry as skipped, if its size or modification time changed
+    /// since it was found, or it was replaced by another file, e.g. a log file still being
+    /// written to that matched an age filter when the run started.
+    fn has_changed(
+        &mut self,
+        backend: &mut dyn Backend,
+        entry: &Entry,
+        found: Option<Stamp>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let (Some(found), Some(now)) = (found, backend.stamp(entry)?) else {
+            return Ok(false);
+        };
+
+        let reason = if now.size != found.size {
+            format!(
+                "Changed after it was found: the size went from {} to {} bytes.",
+                found.size, now.size
+            )
+        } else if now.modified != found.modified {
+            "Changed after it was found: it was modified again.".to_string()
+        } else if now.id != found.id {
+            "Changed after it was found: it was replaced by another file.".to_string()
+        } else {
+            return Ok(false);
+        };
+        self.skip_changed(&entry.name, &reason);
+
+        Ok(true)
+    }
+
+    /// Returns true, and records the entry as skipped along with the processes holding it, if
+    /// `--skip-in-use` is set and the entry is open in another process. If that can't be
+    /// determined, the entry is treated as not in use.
+    fn is_in_use(&mut self, backend: &mut dyn Backend, entry: &Entry) -> bool {
+        if !self.settings.skip_in_use {
+            return false;
+        }
+        let holders = match backend.holders(entry) {
+            Ok(holders) if !holders.is_empty() => holders,
+            Ok(_) => return false,
+            Err(err) => {
+                log::debug!("Unable to check whether {} is in use: {err}", entry.name);
+                return false;
+            }
+        };
+
+        let names: Vec<String> = holders.iter().map(ToString::to_string).collect();
+        let reason = format!("In use by {}.", names.join(", "));
+        self.summary.total_file_count += 1;
+        self.summary.in_use_file_count += 1;
+        let shown = self.shown(&entry.name).into_owned();
+        log::warn!("Skipping: {shown}. {reason}");
+        let mut file = FileReport::new(&shown, entry.size, Outcome::Skipped);
+        file.reason = Some(reason);
+        file.held_by = holders;
+        self.file_done(file);
+
+        true
+    }
+
+    /// Records a file that was left alone because it didn't match the filters.
+    fn skip_unmatched(&mut self, filename: &str, size: u64, reason: &str) {
+        self.summary.total_file_count += 1;
+        self.summary.unmatched_file_count += 1;
+        log::debug!("Skipping: {}. {reason}", self.shown(filename));
+        self.add_to_report(filename, size, Outcome::Skipped, Some(reason), None, None);
+    }
+
+    /// Counts a file about to be removed and prints the detail line for it.
+    fn announce(
+        &mut self,
+        action: &'static str,
+        filename: &str,
+        current_file_size: u64,
+        allocated: Option<u64>,
+        digest: Option<&str>,
+        matched: Option<&str>,
+    ) {
+        self.summary.total_file_count += 1;
+        self.summary.total_file_size += current_file_size;
+        for observer in &mut self.observers {
+            observer.on_file_start(filename, current_file_size);
+        }
+
+        if self.settings.show_detail_info {
+            let mut detail = i18n::tf(
+                "{action}: {path} for {size} bytes.",
+                &[
+                    ("action", &i18n::t(action)),
+                    ("path", &self.shown(filename)),
+                    ("size", &current_file_size),
+                ],
+            );
+            if let Some(allocated) = allocated {
+                detail.push(' ');
+                detail.push_str(&i18n::tf(
+                    "Sparse: {size} bytes allocated.",
+                    &[("size", &allocated)],
+                ));
+            }
+            if let Some(digest) = digest {
+                detail.push_str(&format!(" SHA-256: {digest}"));
+            }
+            if let Some(matched) = matched {
+                detail.push_str(&format!(" {} {matched}", i18n::t("Matched:")));
+            }
+            log::info!("{detail}");
+        }
+    }
+
+    /// Adds a file removed to the totals of the directory target it was found below, if any.
+    fn count_in_directory(&mut self, filename: &str, current_file_size: u64) {
+        if self.summary.directories.is_empty() {
+            return;
+        }
+        let found = Path::new(filename).ancestors().find_map(|dir| {
+            let dir = dir.to_str()?;
+            self.summary.directories.contains_key(dir).then_some(dir)
+        });
+        if let Some(totals) = found.and_then(|dir| self.summary.directories.get_mut(dir)) {
+            totals.file_count += self.trees.get(filename).map_or(1, |stats| stats.file_count);
+            totals.size += current_file_size;
+        }
+    }
+
+    /// Records a file that would have been removed if this wasn't a dry-run, and adds the
+    /// command removing it to the `--emit-script` script.
+    fn record_dry_run(
+        &mut self,
+        candidate: &Candidate,
+        action: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let (filename, current_file_size) = (candidate.entry.name.as_str(), candidate.entry.size);
+        let (digest, matched) = (candidate.digest.as_deref(), candidate.matched.as_deref());
+        self.summary.processed_file_count += 1;
+        self.count_unfreed(&candidate.entry);
+        self.summary.types.add(
+            candidate.file_type,
+            self.trees.get(filename),
+            self.settings.special,
+        );
+        if let Some(kind) = candidate.kind {
+            *self.summary.kinds.entry(kind).or_default() += 1;
+        }
+        if let Some(filesystem) = candidate.filesystem {
+            self.summary.filesystem_types.insert(filesystem.to_string());
+        }
+        self.count_in_directory(filename, current_file_size);
+        self.add_to_report(
+            filename,
+            current_file_size,
+            Outcome::WouldRemove,
+            None,
+            digest,
+            matched,
+        );
+
+        // apply only deletes local regular files
+        let deleted = backend::scheme_of(filename) == "file"
+            && action == "Deleting"
+            && candidate.file_type == Some(FileType::File);
+        if let Some(plan) = self.plan.as_mut().filter(|_| deleted) {
+            plan.files.push(ManifestEntry {
+                path: filename.to_string(),
+                size: current_file_size,
+                sha256: digest.map(str::to_string),
+                mtime: candidate
+                    .modified
+                    .map(|modified| humantime::format_rfc3339_seconds(modified).to_string()),
+                matched: matched.map(str::to_string),
+            });
+        }
+
+        let Some(script) = self.script.as_mut() else {
+            return Ok(());
+        };
+        if backend::scheme_of(filename) != "file" {
+            return script.skip(filename, "not a local file");
+        }
+        // e.g. files a plan moves to the trash
+        if action != "Deleting" {
+            return script.skip(
+                filename,
+                &format!("{} rather than deleting", action.to_lowercase()),
+            );
+        }
+        let removal = if self.trees.contains_key(filename) {
+            Removal::Tree
+        } else if candidate.file_type == Some(FileType::Dir) {
+            Removal::EmptyDir
+        } else {
+            Removal::File
+        };
+        script.add(filename, removal)
+    }
+
+    /// Records a file that was removed, and where it went if it wasn't deleted.
+    fn record_removed(
+        &mut self,
+        candidate: &Candidate,
+        removed: Removed,
+    ) -> Result<(), Box<dyn Error>> {
+        let (filename, current_file_size) = (candidate.entry.name.as_str(), candidate.entry.size);
+        let (digest, matched) = (candidate.digest.as_deref(), candidate.matched.as_deref());
+        self.summary.processed_file_count += 1;
+        self.count_unfreed(&candidate.entry);
+        self.summary.types.add(
+            candidate.file_type,
+            self.trees.get(filename),
+            self.settings.special,
+        );
+        if let Some(kind) = candidate.kind {
+            *self.summary.kinds.entry(kind).or_default() += 1;
+        }
+        if let Some(filesystem) = candidate.filesystem {
+            self.summary.filesystem_types.insert(filesystem.to_string());
+        }
+        self.count_in_directory(filename, current_file_size);
+
+        match &removed {
+            Removed::Deleted => {}
+            Removed::Quarantined(target) => {
+                log::debug!("Quarantined {filename} to {}", target.display());
+                self.summary.quarantined_file_count += 1;
+            }
+            Removed::Moved(target) => {
+                log::debug!("Moved {filename} to {}", target.display());
+                self.summary.moved_file_count += 1;
+            }
+            Removed::Trashed(_) => {
+                log::debug!("Moved {filename} to the trash");
+                self.summary.trashed_file_count += 1;
+            }
+            Removed::Shredded(_) => self.summary.shredded_file_count += 1,
+            Removed::PendingRelease(target) => {
+                log::warn!(
+                    "{filename} is still open, so its space isn't freed until it's closed. It's kept as {} until then.",
+                    target.display()
+                );
+                self.summary.pending_release_file_count += 1;
+                self.summary.pending_release_size += current_file_size;
+                self.pending_release
+                    .push((target.clone(), current_file_size));
+            }
+        }
+
+        // The file is gone from view, but not yet from the disk
+        let reason = matches!(removed, Removed::PendingRelease(_))
+            .then_some("Pending release: still open when deleted");
+
+        if let Some(audit_log) = self.audit_log.as_mut() {
+            let target = match &removed {
+                Removed::Quarantined(target) | Removed::Moved(target) => {
+                    Some(target.to_string_lossy())
+                }
+                Removed::Deleted
+                | Removed::Trashed(_)
+                | Removed::Shredded(_)
+                | Removed::PendingRelease(_) => None,
+            };
+            let mut record = AuditRecord::new(filename, current_file_size);
+            record.checksum_algorithm = digest.map(|_| "sha256");
+            record.checksum = digest;
+            record.security = self.security.get(filename);
+            match &removed {
+                Removed::Quarantined(_) => record.quarantined_to = target.as_deref(),
+                Removed::Moved(_) => record.moved_to = target.as_deref(),
+                Removed::Trashed(_) => record.trashed = true,
+                Removed::Shredded(method) => record.shred_method = Some(method.name()),
+                Removed::Deleted | Removed::PendingRelease(_) => {}
+            }
+            audit_log.record(&record)?;
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.done(filename, &removed)?;
+        }
+        if self.settings.verify {
+            self.removed.push((filename.to_string(), removed));
+        }
+
+        self.add_to_report(
+            filename,
+            current_file_size,
+            Outcome::Removed,
+            reason,
+            digest,
+            matched,
+        );
+
+        self.run_after_each(filename, Outcome::Removed)
+    }
+
+    /// Checks whether the files kept as `.nfs` files because they were still open have been
+    /// closed and released since, and counts the space of those as freed.
+    fn recheck_pending(&mut self) {
+        let pending = std::mem::take(&mut self.pending_release);
+        let count = pending.len();
+        for (target, size) in pending {
+            if target.symlink_metadata().is_ok() {
+                self.pending_release.push((target, size));
+                continue;
+            }
+            log::debug!("{} has been released", target.display());
+            self.summary.pending_release_file_count -= 1;
+            self.summary.pending_release_size -= size;
+        } // for target
+        if count > 0 {
+            log::info!(
+                "{} of {count} files pending release were released by the end of the run.",
+                count - self.pending_release.len()
+            );
+        }
+    }
+
+    /// Checks that the entries removed are gone, and that those moved elsewhere (to the trash,
+    /// the quarantine or `--move-to`) are where they were moved to, for `--verify`, in case a
+    /// backend got it wrong or another process put them back. Each discrepancy is warned about
+    /// and counted. Entries that can't be checked are only warned about.
+    fn verify(&mut self) {
+        let removed = std::mem::take(&mut self.removed);
+        let mut unchecked = 0;
+        for (filename, removed) in &removed {
+            let entry = Entry {
+                name: filename.clone(),
+                size: 0,
+            };
+            let mut exists = None;
+            let checked = self.with_backend(backend::scheme_of(filename), |_, backend| {
+                exists = backend.exists(&entry)?;
+                Ok(())
+            });
+            let shown = self.shown(filename).into_owned();
+            match (checked, exists) {
+                (Err(err), _) => {
+                    log::warn!("Unable to verify that {shown} was removed: {err}");
+                    unchecked += 1;
+                }
+                (Ok(()), None) => unchecked += 1,
+                (Ok(()), Some(true)) => {
+                    log::warn!("Verification failed: {shown} is still there.");
+                    self.summary.unverified_file_count += 1;
+                }
+                (Ok(()), Some(false)) => {
+                    let (Removed::Quarantined(target)
+                    | Removed::Moved(target)
+                    | Removed::Trashed(Some(target))) = removed
+                    else {
+                        continue;
+                    };
+                    if target.symlink_metadata().is_err() {
+                        log::warn!(
+                            "Verification failed: {shown} was moved to {}, but it isn't there.",
+                            target.display()
+                        );
+                        self.summary.unverified_file_count += 1;
+                    }
+                }
+            }
+        } // for filename
+        log::info!(
+            "Verified {} of {} removed files: {} discrepancies found.",
+            removed.len() - unchecked,
+            removed.len(),
+            self.summary.unverified_file_count
+        );
+    }
+
+    /// Tries again to remove the files that permission was denied for, through a single run
+    /// under `sudo`, for `--sudo-retry`. The files that are gone afterwards are counted as
+    /// removed rather than failed. A failed run is only warned about, as the files have already
+    /// been counted as failures.
+    fn retry_with_sudo(&mut self) {
+        let mut denied = std::mem::take(&mut self.denied);
+        // The run under sudo can't ask, so it's only given directories already confirmed
+        denied.retain(|filename| {
+            let refused =
+                self.trees.contains_key(filename) && !self.confirmed_dirs.contains(filename);
+            if refused {
+                log::warn!(
+                    "Not trying {} again with sudo, as removing it wasn't confirmed.",
+                    self.shown(filename)
+                );
+            }
+            !refused
+        });
+        if denied.is_empty() {
+            return;
+        }
+        if self.settings.dry_run {
+            log::info!(
+                "Would try to remove {} files again with sudo.",
+                denied.len()
+            );
+            return;
+        }
+
+        #[cfg(unix)]
+        {
+            if crate::sudo::is_root() {
+                log::debug!("Already running as root, so there's nothing to gain from sudo.");
+                return;
+            }
+
+            let mut options = Vec::new();
+            if self.settings.recursive {
+                options.push("--recursive");
+                // Directories not confirmed were left out above, and those below the targets
+                // weren't asked about in this run either
+                let is_dir = |filename: &String| {
+                    Path::new(filename)
+                        .symlink_metadata()
+                        .is_ok_and(|metadata| metadata.is_dir())
+                };
+                if denied.iter().any(is_dir) {
+                    options.push("--yes");
+                }
+            }
+            if self.settings.special == Special::Delete {
+                options.extend(["--special", "delete"]);
+            }
+            if self.settings.force_immutable {
+                options.push("--force-immutable");
+            }
+            if self.settings.i_know_what_im_doing {
+                options.push("--i-know-what-im-doing");
+            }
+            if self.settings.no_preserve_root {
+                options.push("--no-preserve-root");
+            }
+            let audit_file = self
+                .settings
+                .audit_file
+                .as_ref()
+                .map(|file| file.to_string_lossy());
+            if let Some(audit_file) = audit_file.as_deref().filter(|_| self.audit_log.is_some()) {
+                options.extend(["--audit-log", audit_file]);
+                if self.settings.audit_sync {
+                    options.push("--audit-sync");
+                }
+            }
+
+            log::info!("Trying to remove {} files again with sudo.", denied.len());
+            let paths: Vec<&str> = denied.iter().map(String::as_str).collect();
+            if let Err(err) = crate::sudo::retry(&paths, &options) {
+                log::warn!("{err}");
+            }
+        }
+
+        let mut removed = 0;
+        for filename in denied {
+            if Path::new(&filename).symlink_metadata().is_ok() {
+                continue;
+            }
+            log::debug!("Removed {filename} with sudo.");
+            let shown = self.shown(&filename).into_owned();
+            removed += 1;
+            self.summary.skipped_file_count -= 1;
+            self.summary.errors.permission_denied -= 1;
+            self.summary.processed_file_count += 1;
+            self.summary.sudo_file_count += 1;
+            self.failures.retain(|failure| failure.path != filename);
+            if let Some(report) = &mut self.report {
+                for file in report
+                    .files
+                    .iter_mut()
+                    .filter(|file| file.path == shown && file.outcome == Outcome::Failed)
+                {
+                    file.outcome = Outcome::Removed;
+                    file.reason = Some("Removed with sudo".to_string());
+                } // for file
+            }
+        } // for filename
+        log::info!("{removed} files were removed with sudo.");
+    }
+
+    /// Adds a file that couldn't be removed to the failures, the summary and the report.
+    fn add_failure(
+        &mut self,
+        filename: &str,
+        current_file_size: u64,
+        digest: Option<&str>,
+        matched: Option<&str>,
+        err: &(dyn Error + 'static),
+    ) {
+        let reason = err.to_string();
+        self.failures.push(Failure {
+            path: filename.to_string(),
+            reason: reason.clone(),
+        });
+
+        for observer in &mut self.observers {
+            observer.on_error(filename, err);
+        }
+
+        if err.is::<TimedOut>() {
+            self.summary.timed_out_file_count += 1;
+        } else {
+            let denied = self.summary.errors.permission_denied;
+            self.summary.skipped_file_count += 1;
+            self.summary.errors.add(err);
+            if self.summary.errors.permission_denied > denied
+                && backend::scheme_of(filename) == "file"
+            {
+                self.denied.push(filename.to_string());
+            }
+        }
+        self.add_to_report(
+            filename,
+            current_file_size,
+            Outcome::Failed,
+            Some(&reason),
+            digest,
+            matched,
+        );
+    }
+
+    /// Records a file that couldn't be removed. Returns an error if the run should stop, because
+    /// there have been more errors than `--max-errors` (or `--stop-on-error`) allows.
+    fn record_failed(
+        &mut self,
+        filename: &str,
+        current_file_size: u64,
+        digest: Option<&str>,
+        matched: Option<&str>,
+        err: &(dyn Error + 'static),
+    ) -> Result<(), Box<dyn Error>> {
+        self.add_failure(filename, current_file_size, digest, matched, err);
+        if let Some(halt) = self.too_many_errors(filename, err) {
+            return Err(halt);
+        }
+        log::warn!(
+            "Unable to remove file {}. Continuing.",
+            self.shown(filename)
+        );
+
+        self.run_after_each(filename, Outcome::Failed)
+    }
+
+    /// Returns the error to stop the run with, if there have been more errors than
+    /// `--max-errors` (or `--stop-on-error`) allows.
+    fn too_many_errors(&self, filename: &str, err: &dyn Error) -> Option<Box<dyn Error>> {
+        let filename = self.shown(filename);
+        match self.settings.max_errors {
+            Some(0) => {
+                Some(format!("Error: {err}. Unable to remove file {filename}. Halting.").into())
+            }
+            Some(max_errors) if self.failures.len() > max_errors => Some(format!(
+                "Error: {err}. Unable to remove file {filename}. Halting after {} errors, more than --max-errors allows.",
+                self.failures.len()
+            )
+            .into()),
+            _ => None,
+        }
+    }
+
+    /// Runs the `--before-each` command for a file about to be removed. Returns the error if
+    /// it failed and the file is to be left alone because of it.
+    fn run_before_each(&mut self, filename: &str) -> Option<Box<dyn Error>> {
+        let err = self
+            .settings
+            .before_each
+            .as_ref()?
+            .run_for_file(filename, "pending")
+            .err()?;
+
+        self.summary.hook_failed_count += 1;
+        if self.settings.fail_on_hook_error {
+            return Some(err);
+        }
+        log::warn!("{err}. Continuing.");
+        None
+    }
+
+    /// Runs the `--after-each` command for a file that has been dealt with. A failure is only
+    /// warned about, unless failed hooks are to count as errors, in which case it's added to the
+    /// failures and may stop the run.
+    fn run_after_each(&mut self, filename: &str, outcome: Outcome) -> Result<(), Box<dyn Error>> {
+        let Some(hook) = &self.settings.after_each else {
+            return Ok(());
+        };
+        let Err(err) = hook.run_for_file(filename, outcome.name()) else {
+            return Ok(());
+        };
+
+        self.summary.hook_failed_count += 1;
+        if !self.settings.fail_on_hook_error {
+            log::warn!("{err}. Continuing.");
+            return Ok(());
+        }
+
+        self.failures.push(Failure {
+            path: filename.to_string(),
+            reason: err.to_string(),
+        });
+        match self.too_many_errors(filename, &*err) {
+            Some(halt) => Err(halt),
+            None => {
+                log::warn!("{err}. Continuing.");
+                Ok(())
+            }
+        }
+    }
+
+    /// Permanently removes quarantine folders older than the retention period.
+    pub fn purge_quarantine(&mut self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        let retain = self
+            .settings
+            .retain
+            .ok_or("A retention period must be given with --retain.")?;
+
+        let purged = quarantine::purge(dir, retain, self.clock.now(), self.settings.dry_run)?;
+        self.summary.purged_file_count += purged.file_count;
+        self.summary.purged_size += purged.size;
+
+        Ok(())
+    }
+
+    /// Permanently removes the files deleted to the trash longer ago than the retention period.
+    pub fn purge_trash(&mut self) -> Result<(), Box<dyn Error>> {
+        let retain = self
+            .settings
+            .retain
+            .ok_or("A retention period must be given with --retain.")?;
+
+        let purged = trash::purge(retain, self.clock.now(), self.settings.dry_run)?;
+        self.summary.purged_file_count += purged.file_count;
+        self.summary.purged_size += purged.size;
+
+        Ok(())
+    }
+
+    /// Copies the entry into the archive, if one is being written.
+    fn archive_entry(
+        &mut self,
+        backend: &mut dyn Backend,
+        entry: &Entry,
+    ) -> Result<(), Box<dyn Error>> {
+        match self.archive.as_mut() {
+            Some(archive) => backend
+                .archive(entry, archive)
+                .map_err(|err| format!("Unable to archive: {err}").into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a file as shown in the log and report, after `--resolve-paths` and `--relative-to`.
+    fn shown<'a>(&self, filename: &'a str) -> Cow<'a, str> {
+        if backend::scheme_of(filename) != "file" {
+            return Cow::Borrowed(filename);
+        }
+        let shown = match self.settings.resolve_paths {
+            Some(resolve) => {
+                let given = self.given.get(filename).map_or(filename, String::as_str);
+                Cow::Owned(paths::resolve(given, resolve))
+            }
+            None => Cow::Borrowed(filename),
+        };
+        match &self.base {
+            Some(base) => Cow::Owned(paths::relative(&shown, base)),
+            None => shown,
+        }
+    }
+
+    /// Adds an entry to the report, if one is being kept.
+    fn add_to_report(
+        &mut self,
+        filename: &str,
+        size: u64,
+        outcome: Outcome,
+        reason: Option<&str>,
+        digest: Option<&str>,
+        matched: Option<&str>,
+    ) {
+        if self.report.is_none() && self.observers.is_empty() {
+            return;
+        }
+
+        let mut file = FileReport::new(&self.shown(filename), size, outcome);
+        file.reason = reason.map(str::to_string);
+        file.checksum = digest.map(str::to_string);
+        file.matched = matched.map(str::to_string);
+        self.file_done(file);
+    }
+
+    /// Hands a file that has been dealt with to the observers, and adds it to the report.
+    fn file_done(&mut self, file: FileReport) {
+        for observer in &mut self.observers {
+            observer.on_file_done(&file);
+        }
+        if let Some(report) = self.report.as_mut() {
+            report.add(file);
+        }
+    }
+
+    /// Writes the paths of the files that couldn't be removed to `path`, one per line,
+    /// or separated by NUL characters if requested.
+    fn write_failed_list(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let separator = if self.settings.null_separated {
+            '\0'
+        } else {
+            '\n'
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        for failure in &self.failures {
+            write!(writer, "{}{separator}", failure.path)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Removes everything the targets refer to like `run`, and then finishes the run like
+    /// `finish`, on a thread where blocking is allowed, so async services can clean up without
+    /// holding up their runtime. Returns the summary of the run.
+    #[cfg(feature = "async")]
+    pub async fn run_async<I>(mut self, targets: I) -> Result<Summary, Box<dyn Error + Send + Sync>>
+    where
+        I: IntoIterator<Item = String> + Send + 'static,
+    {
+        tokio::task::spawn_blocking(move || {
+            self.run_to_end(targets.into_iter().map(Ok))
+                .map_err(crate::timeout::sendable)
+        })
+        .await?
+    }
+
+    /// Removes everything the targets refer to on a thread of its own, handing back each file
+    /// as soon as it has been dealt with, so callers can act on every file (e.g. update a
+    /// database) without waiting for the summary. The run is finished like `finish` when all
+    /// the targets have been processed.
+    pub fn stream<I>(self, targets: I) -> Stream
+    where
+        I: IntoIterator<Item = String> + Send + 'static,
+    {
+        let (sender, files) = mpsc::sync_channel(stream::QUEUE_SIZE);
+        let mut deleter = self.with_observer(stream::Sender(sender));
+        let worker = thread::spawn(move || {
+            deleter
+                .run_to_end(targets.into_iter().map(Ok))
+                .map_err(crate::timeout::sendable)
+        });
+
+        Stream::new(files, worker)
+    }
+
+    /// Runs and finishes the run, returning the summary.
+    pub(crate) fn run_to_end<I>(&mut self, targets: I) -> Result<Summary, Box<dyn Error>>
+    where
+        I: IntoIterator<Item = Result<String, Box<dyn Error>>>,
+    {
+        // Finish even if processing stopped early, so the archive and report are complete
+        let result = self.run(targets);
+        let finished = self.finish();
+        result.and(finished)?;
+        Ok(std::mem::take(&mut self.summary))
+    }
+
+    /// Finishes the run: sweeps the quarantine, completes the archive, prints the summary if
+    /// requested, writes the report and the failure list, and sends the notifications and metrics.
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        // Everything is removed, so the log and the summary can take over from the dashboard
+        tui::close();
+
+        // Every file has been decided on, so the filter program only has to exit cleanly
+        if let Some(filter) = self.filter_cmd.take() {
+            if let Err(err) = filter.finish() {
+                log::warn!("{err}");
+            }
+        }
+
+        // Sweep the quarantine as part of every run when a retention period is given
+        if let (Some(dir), Some(_)) = (self.settings.quarantine.clone(), self.settings.retain) {
+            if dir.is_dir() {
+                self.purge_quarantine(&dir)?;
+            }
+        }
+
+        // Keep the trash within the limits in the config file whenever files are moved to it.
+        // The files have already been trashed, so failing to do so doesn't fail the run.
+        let (max_size, max_age) = (self.settings.trash_max_size, self.settings.trash_max_age);
+        if self.summary.trashed_file_count > 0 && (max_size.is_some() || max_age.is_some()) {
+            match trash::enforce_limits(max_size, max_age, self.clock.now(), self.settings.dry_run)
+            {
+                Ok(purged) => {
+                    self.summary.purged_file_count += purged.file_count;
+                    self.summary.purged_size += purged.size;
+                }
+                Err(err) => log::warn!("Unable to keep the trash within its limits: {err}"),
+            }
+        }
+
+        if self.outside_active_hours {
+            self.summary.queued_file_count = self.summary.processed_file_count;
+        }
+
+        if let Some(archive) = self.archive.take() {
+            self.summary.archive_size = Some(archive.finish()?);
+        }
+
+        if let Some(script) = self.script.take() {
+            script.finish()?;
+        }
+
+        if let Some(journal) = self.journal.take() {
+            journal.finish()?;
+        }
+
+        if let (Some(plan), Some(plan_file)) = (&self.plan, &self.settings.save_plan) {
+            plan.write(plan_file)?;
+        }
+
+        for backend in self.backends.values_mut() {
+            backend.finish()?;
+        }
+
+        if self.settings.sudo_retry && !self.denied.is_empty() {
+            self.retry_with_sudo();
+        }
+
+        if self.settings.verify && !self.removed.is_empty() {
+            self.verify();
+        }
+
+        if self.settings.recheck_pending {
+            self.recheck_pending();
+        }
+
+        if self.settings.print_summary {
+            self.summary.print();
+        }
+        for observer in &mut self.observers {
+            observer.on_summary(&self.summary);
+        }
+        if self.settings.scan_only {
+            self.scanned.print();
+        }
+
+        if let (Some(report), Some(report_file)) = (&self.report, &self.settings.report_file) {
+            report.write(report_file, &self.summary)?;
+        }
+
+        if let Some(failed_list) = &self.settings.failed_list {
+            self.write_failed_list(failed_list)?;
+        }
+
+        // The files have already been dealt with, so a failed notification doesn't fail the run
+        if let Some(url) = &self.settings.notify_url {
+            let failures = self
+                .settings
+                .notify_failures
+                .then_some(self.failures.as_slice());
+            if let Err(err) = notify::send(url, self.settings.dry_run, &self.summary, failures) {
+                log::warn!("Unable to send the notification to {url}: {err}");
+            }
+        }
+
+        if self.settings.notify_desktop {
+            if let Err(err) = notify::desktop(self.settings.dry_run, &self.summary) {
+                log::warn!("Unable to show the desktop notification: {err}");
+            }
+        }
+
+        // Dry-runs would skew the numbers, so metrics are only sent for real runs
+        if let (Some(address), false) = (&self.settings.statsd, self.settings.dry_run) {
+            let prefix = &self.settings.statsd_prefix;
+            let duration = self.started.elapsed();
+            if let Err(err) = metrics::send_statsd(address, prefix, &self.summary, duration) {
+                log::warn!("Unable to send metrics to {address}: {err}");
+            }
+        }
+
+        if let (Some(hook), false) = (&self.settings.post_run, self.settings.dry_run) {
+            if let Err(err) = hook.run_with_input(&serde_json::to_vec(&self.summary)?) {
+                if self.settings.fail_on_hook_error {
+                    return Err(err);
+                }
+                log::warn!("{err}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the error is because a file doesn't exist.
+fn is_not_found(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == io::ErrorKind::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Makes a file for a test, returning its path.
+    fn test_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rdel-deleter-{name}-{}", std::process::id()));
+        fs::write(&path, b"hello").unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    /// Makes a candidate for the file, as the scan would have found it.
+    fn candidate(filename: &str, stamp: Option<Stamp>) -> Candidate {
+        Candidate {
+            entry: Entry {
+                name: filename.to_string(),
+                size: 5,
+            },
+            digest: None,
+            matched: None,
+            stamp,
+            modified: None,
+            file_type: Some(FileType::File),
+            kind: None,
+            filesystem: None,
+        }
+    }
+
+    /// Checks that every file examined is counted as removed or skipped for one reason or another.
+    fn assert_totals_balance(summary: &Summary) {
+        let accounted = summary.processed_file_count
+            + summary.skipped_file_count
+            + summary.changed_file_count
+            + summary.unmatched_file_count
+            + summary.recent_file_count
+            + summary.in_use_file_count
+            + summary.timed_out_file_count;
+        assert_eq!(summary.total_file_count, accounted, "{summary:?}");
+    }
+
+    #[test]
+    fn failed_change_check_counted() {
+        let filename = test_file("changed");
+        let mut backend = Local::new(Disposal::Delete, &Settings::default());
+        let stamp = backend
+            .stamp(&Entry {
+                name: filename.clone(),
+                size: 5,
+            })
+            .unwrap();
+        // Gone before it's looked at again, so checking whether it changed fails
+        fs::remove_file(&filename).unwrap();
+
+        let mut deleter = Deleter::new(Settings::default()).unwrap();
+        deleter
+            .remove_candidates(&mut backend, vec![candidate(&filename, stamp)])
+            .unwrap();
+        assert_eq!(deleter.summary.total_file_count, 1);
+        assert_eq!(deleter.summary.skipped_file_count, 1);
+        assert_eq!(deleter.failures.len(), 1);
+        assert_totals_balance(&deleter.summary);
+    }
+
+    #[test]
+    fn failed_paranoid_resolve_counted() {
+        let filename = test_file("paranoid");
+        fs::remove_file(&filename).unwrap();
+        let settings = Settings {
+            paranoid: true,
+            ..Settings::default()
+        };
+        let mut backend = Local::new(Disposal::Delete, &settings);
+        let mut deleter = Deleter::new(settings).unwrap();
+        deleter
+            .remove_found(&mut backend, candidate(&filename, None))
+            .unwrap();
+        assert_eq!(deleter.summary.total_file_count, 1);
+        assert_eq!(deleter.summary.skipped_file_count, 1);
+        assert_totals_balance(&deleter.summary);
+    }
+
+    #[test]
+    fn changed_files_not_hooked_or_archived() {
+        let (filename, unchanged) = (test_file("recheck"), test_file("unchanged"));
+        let settings = Settings {
+            recheck: true,
+            before_each: Some(Hook::new("touch {}.hooked")),
+            ..Settings::default()
+        };
+        let mut backend = Local::new(Disposal::Delete, &settings);
+        let mut found = |filename: &str| {
+            let entry = Entry {
+                name: filename.to_string(),
+                size: 5,
+            };
+            candidate(filename, backend.stamp(&entry).unwrap())
+        };
+        let pending = [found(&filename), found(&unchanged)];
+        // Still being written to after it was announced
+        fs::write(&filename, b"hello again").unwrap();
+
+        let mut deleter = Deleter::new(settings).unwrap();
+        deleter.summary.total_file_count = 2;
+        deleter.summary.total_file_size = 10;
+        deleter
+            .remove_pending(&mut backend, pending.iter().collect())
+            .unwrap();
+        assert!(Path::new(&filename).exists());
+        assert!(!Path::new(&format!("{filename}.hooked")).exists());
+        assert!(!Path::new(&unchanged).exists());
+        assert!(Path::new(&format!("{unchanged}.hooked")).exists());
+        assert_eq!(deleter.summary.changed_file_count, 1);
+        assert_eq!(deleter.summary.processed_file_count, 1);
+        assert_totals_balance(&deleter.summary);
+        fs::remove_file(&filename).unwrap();
+        fs::remove_file(format!("{unchanged}.hooked")).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn directories_kept_for_special_files_not_counted() {
+        let dir = std::env::temp_dir().join(format!("rdel-deleter-kept-{}", std::process::id()));
+        fs::create_dir_all(dir.join("a").join("b")).unwrap();
+        fs::create_dir_all(dir.join("c")).unwrap();
+        fs::write(dir.join("c").join("f"), b"x").unwrap();
+        rustix::fs::mknodat(
+            rustix::fs::CWD,
+            dir.join("a").join("b").join("fifo"),
+            rustix::fs::FileType::Fifo,
+            rustix::fs::Mode::from(0o600),
+            0,
+        )
+        .unwrap();
+
+        let mut deleter = Deleter::new(Settings {
+            recursive: true,
+            ..Settings::default()
+        })
+        .unwrap();
+        deleter
+            .run([dir.to_str().unwrap().to_string()].map(Ok))
+            .unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        // The fifo keeps a, b and the top of the tree; only c is gone
+        assert_eq!(deleter.summary.types.directories, 1);
+        assert_eq!(deleter.summary.types.regular_files, 1);
+    }
+}
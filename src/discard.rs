@@ -0,0 +1,41 @@
+use std::error::Error;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Returns the mount point of the file system `dir` is on, found by walking up the directory
+/// tree until the device changes.
+pub fn mount_point(dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let mut mount = fs::canonicalize(dir)?;
+    let device = fs::metadata(&mount)?.dev();
+    while let Some(parent) = mount.parent() {
+        if fs::metadata(parent)?.dev() != device {
+            break;
+        }
+        mount = parent.to_path_buf();
+    }
+
+    Ok(mount)
+}
+
+/// Tells the device below the file system mounted at `mount` which blocks are no longer in use,
+/// using `fstrim`, so the space freed is returned to SSDs and thin-provisioned volumes.
+/// Returns what `fstrim` reported.
+pub fn trim(mount: &Path) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("fstrim")
+        .arg("--verbose")
+        .arg(mount)
+        .output()
+        .map_err(|err| format!("Unable to run fstrim: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to trim {}: {}",
+            mount.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
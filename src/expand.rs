@@ -0,0 +1,114 @@
+use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Expands `~`, `~user`, `$VAR` and `${VAR}` in a path, and `%VAR%` on Windows, for paths that
+/// haven't been through a shell (e.g. from a scheduler). A variable that isn't set is an error,
+/// rather than silently expanding to nothing.
+///
+/// A path that exists as given is returned unchanged, so file names that happen to contain
+/// `$` or `~` can still be used.
+pub fn expand_path(path: &str) -> Result<String, Box<dyn Error>> {
+    if !path.contains(['~', '$', '%']) || Path::new(path).symlink_metadata().is_ok() {
+        return Ok(path.to_string());
+    }
+
+    let expanded = expand_tilde(&expand_vars(path)?)?;
+    if expanded != path {
+        log::debug!("Expanded {path} to {expanded}");
+    }
+
+    Ok(expanded)
+}
+
+/// Replaces the environment variables in `path` with their values.
+fn expand_vars(path: &str) -> Result<String, Box<dyn Error>> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let lookup = |name: &str| {
+        env::var(name).map_err(|_| format!("Environment variable {name} in {path} is not set."))
+    };
+
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        let marker = &rest[start..start + 1];
+        let after = &rest[start + 1..];
+
+        // ${VAR}
+        if marker == "$" && after.starts_with('{') {
+            if let Some(end) = after.find('}') {
+                expanded.push_str(&lookup(&after[1..end])?);
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+
+        // $VAR
+        if marker == "$" && after.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+            expanded.push_str(&lookup(&after[..end])?);
+            rest = &after[end..];
+            continue;
+        }
+
+        // %VAR%
+        if marker == "%" && cfg!(windows) {
+            if let Some(end) = after.find('%').filter(|&end| end > 0) {
+                if after[..end].chars().all(is_name_char) {
+                    expanded.push_str(&lookup(&after[..end])?);
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+        }
+
+        expanded.push_str(marker);
+        rest = after;
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Replaces a leading `~` with the current user's home directory, or `~user` with that user's.
+fn expand_tilde(path: &str) -> Result<String, Box<dyn Error>> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Ok(path.to_string());
+    };
+    let (user, rest) = rest.split_at(rest.find(['/', '\\']).unwrap_or(rest.len()));
+
+    let home = if user.is_empty() {
+        home_dir().ok_or("Unable to find the home directory.")?
+    } else {
+        user_home_dir(user).ok_or_else(|| format!("Unknown user in {path}: {user}"))?
+    };
+
+    Ok(format!("{}{rest}", home.display()))
+}
+
+/// Returns the current user's home directory.
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// Returns the home directory of the named user, from the password file.
+#[cfg(unix)]
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    // name:password:uid:gid:gecos:home:shell
+    std::fs::read_to_string("/etc/passwd")
+        .ok()?
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() >= 6 && fields[0] == user)
+        .map(|fields| PathBuf::from(fields[5]))
+}
+
+/// Returns the home directory of the named user, assumed to be next to the current user's.
+#[cfg(not(unix))]
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    let dir = home_dir()?.parent()?.join(user);
+    dir.is_dir().then_some(dir)
+}
@@ -0,0 +1,601 @@
+use glob::Pattern;
+use std::error::Error;
+use std::time::{Duration, SystemTime};
+
+use crate::age::TimeField;
+use crate::backend::{Backend, Entry};
+use crate::name;
+use crate::utils;
+
+/// A filter expression given with `--where`, e.g.
+/// `size > 100MB && mtime > 30d && ext in ('log','tmp')`.
+///
+/// Comparisons can be combined with `&&`/`and`, `||`/`or` and `!`/`not`, and grouped with
+/// parentheses. The fields are:
+///
+/// - `size`: the size in bytes. Values accept suffixes such as K, M and G.
+/// - `mtime`, `atime`, `ctime`, `btime`: the age of the timestamp, e.g. `mtime > 30d`.
+/// - `name`, `ext`, `path`: the file name, the extension without the dot, and the full path.
+/// - `type`: `f` for files, `d` for directories and `l` for symbolic links.
+///
+/// The text fields support `==`, `!=`, `in (...)` and `~` for glob matching.
+#[derive(Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Value),
+    In(Field, Vec<String>),
+}
+
+/// A property of a file that can be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Size,
+    Age(TimeField),
+    Name,
+    Ext,
+    Path,
+    Type,
+}
+
+/// A comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    Glob,
+}
+
+/// The value a field is compared with.
+#[derive(Debug)]
+pub enum Value {
+    Size(u64),
+    Age(Duration),
+    Text(String),
+    Pattern(Pattern),
+}
+
+/// The file an expression is evaluated against, along with the backend used to look up
+/// its timestamps and type.
+pub struct Subject<'a> {
+    pub entry: &'a Entry,
+    pub backend: &'a mut dyn Backend,
+    /// The time ages are counted back from.
+    pub now: SystemTime,
+}
+
+/// A token in an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Op(&'static str),
+}
+
+/// The operators, longest first so `>=` isn't read as `>` followed by `=`.
+const OPERATORS: &[&str] = &[
+    "&&", "||", ">=", "<=", "==", "!=", ">", "<", "!", "(", ")", ",", "~",
+];
+
+impl Expr {
+    /// Parses an expression. Text comparisons ignore case if requested.
+    pub fn parse(text: &str, ignore_case: bool) -> Result<Self, Box<dyn Error>> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            ignore_case,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(expr),
+            token => Err(format!("Unexpected {} in --where expression.", describe(token)).into()),
+        }
+    }
+
+    /// Evaluates the expression for a file. Comparisons against a timestamp the file
+    /// doesn't have are false.
+    pub fn matches(
+        &self,
+        subject: &mut Subject,
+        ignore_case: bool,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(match self {
+            Self::And(left, right) => {
+                left.matches(subject, ignore_case)? && right.matches(subject, ignore_case)?
+            }
+            Self::Or(left, right) => {
+                left.matches(subject, ignore_case)? || right.matches(subject, ignore_case)?
+            }
+            Self::Not(expr) => !expr.matches(subject, ignore_case)?,
+            Self::In(field, values) => values.contains(&text_of(*field, subject, ignore_case)?),
+            Self::Compare(Field::Size, op, Value::Size(size)) => {
+                compare(subject.entry.size, *op, *size)
+            }
+            Self::Compare(Field::Age(field), op, Value::Age(age)) => {
+                match subject.backend.time(subject.entry, *field)? {
+                    Some(time) => {
+                        let actual = subject.now.duration_since(time).unwrap_or_default();
+                        compare(actual, *op, *age)
+                    }
+                    None => false,
+                }
+            }
+            Self::Compare(field, Op::Glob, Value::Pattern(pattern)) => pattern.matches_with(
+                &text_of(*field, subject, ignore_case)?,
+                name::match_options(ignore_case),
+            ),
+            Self::Compare(field, op, Value::Text(value)) => {
+                let text = text_of(*field, subject, ignore_case)?;
+                match op {
+                    Op::Ne => text != *value,
+                    _ => text == *value,
+                }
+            }
+            Self::Compare(..) => false,
+        })
+    }
+
+    /// Combines two optional expressions with `&&`.
+    pub fn and(left: Option<Self>, right: Option<Self>) -> Option<Self> {
+        match (left, right) {
+            (Some(left), Some(right)) => Some(Self::And(Box::new(left), Box::new(right))),
+            (left, right) => left.or(right),
+        }
+    }
+}
+
+/// Applies a comparison operator to two ordered values.
+fn compare<T: PartialOrd>(actual: T, op: Op, expected: T) -> bool {
+    match op {
+        Op::Lt => actual < expected,
+        Op::Le => actual <= expected,
+        Op::Gt => actual > expected,
+        Op::Ge => actual >= expected,
+        Op::Eq | Op::Glob => actual == expected,
+        Op::Ne => actual != expected,
+    }
+}
+
+/// Returns the text of a text field for the file, lower-cased if case is ignored.
+fn text_of(
+    field: Field,
+    subject: &mut Subject,
+    ignore_case: bool,
+) -> Result<String, Box<dyn Error>> {
+    let path = subject.entry.name.as_str();
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    let text = match field {
+        Field::Name => file_name,
+        Field::Ext => file_name
+            .rsplit_once('.')
+            .map_or("", |(stem, ext)| if stem.is_empty() { "" } else { ext }),
+        Field::Type => subject
+            .backend
+            .file_type(subject.entry)?
+            .map_or("", |file_type| file_type.letter()),
+        _ => path,
+    };
+
+    Ok(if ignore_case {
+        text.to_lowercase()
+    } else {
+        text.to_string()
+    })
+}
+
+/// Splits an expression into words, quoted strings and operators.
+fn tokenize(text: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '\'' || *c == '"') {
+            let end = rest[1..]
+                .find(quote)
+                .ok_or("Unterminated string in --where expression.")?;
+            tokens.push(Token::Text(rest[1..=end].to_string()));
+            rest = &rest[end + 2..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "&|><=!(),~'\"".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("Unexpected character in --where expression: {rest}").into());
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    ignore_case: bool,
+}
+
+impl Parser {
+    /// Returns the next token without consuming it.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Consumes and returns the next token.
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consumes the next token if it's the given operator or keyword.
+    fn accept(&mut self, op: &str, keyword: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Op(found)) => *found == op,
+            Some(Token::Word(word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        };
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    /// Consumes the given operator, or fails.
+    fn expect(&mut self, op: &str) -> Result<(), Box<dyn Error>> {
+        if self.accept(op, "") {
+            Ok(())
+        } else {
+            Err(format!("Expected '{op}' in --where expression.").into())
+        }
+    }
+
+    /// or := and ('||' and)*
+    fn or(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.and()?;
+        while self.accept("||", "or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    /// and := unary ('&&' unary)*
+    fn and(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut expr = self.unary()?;
+        while self.accept("&&", "and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    /// unary := '!' unary | '(' or ')' | comparison
+    fn unary(&mut self) -> Result<Expr, Box<dyn Error>> {
+        if self.accept("!", "not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.accept("(", "") {
+            let expr = self.or()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    /// comparison := field op value | field 'in' '(' value (',' value)* ')'
+    fn comparison(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let field = match self.next() {
+            Some(Token::Word(word)) => parse_field(&word)?,
+            other => {
+                return Err(format!(
+                    "Expected a field name in --where expression, found {}.",
+                    describe(other)
+                )
+                .into())
+            }
+        };
+
+        if self.accept("", "in") {
+            if matches!(field, Field::Size | Field::Age(_)) {
+                return Err("'in' can only be used with name, ext, path and type.".into());
+            }
+            self.expect("(")?;
+            let mut values = vec![self.text()?];
+            while self.accept(",", "") {
+                values.push(self.text()?);
+            }
+            self.expect(")")?;
+            return Ok(Expr::In(field, values));
+        }
+
+        let op = match self.next() {
+            Some(Token::Op("<")) => Op::Lt,
+            Some(Token::Op("<=")) => Op::Le,
+            Some(Token::Op(">")) => Op::Gt,
+            Some(Token::Op(">=")) => Op::Ge,
+            Some(Token::Op("==")) => Op::Eq,
+            Some(Token::Op("!=")) => Op::Ne,
+            Some(Token::Op("~")) => Op::Glob,
+            other => {
+                return Err(format!(
+                    "Expected a comparison in --where expression, found {}.",
+                    describe(other)
+                )
+                .into())
+            }
+        };
+
+        // Checked before the value is read, so the error doesn't depend on what it is
+        if op == Op::Glob && matches!(field, Field::Size | Field::Age(_)) {
+            return Err("'~' can only be used with name, ext, path and type.".into());
+        }
+        let value = match field {
+            Field::Size => Value::Size(utils::parse_size(&self.text()?)?),
+            Field::Age(_) => Value::Age(humantime::parse_duration(&self.text()?)?),
+            _ if op == Op::Glob => Value::Pattern(Pattern::new(&self.text()?)?),
+            _ if matches!(op, Op::Eq | Op::Ne) => Value::Text(self.text()?),
+            _ => return Err("Text fields can only be compared with ==, != and ~.".into()),
+        };
+
+        Ok(Expr::Compare(field, op, value))
+    }
+
+    /// Reads a value, lower-cased if case is ignored so it can be compared directly.
+    fn text(&mut self) -> Result<String, Box<dyn Error>> {
+        match self.next() {
+            Some(Token::Word(text)) | Some(Token::Text(text)) if self.ignore_case => {
+                Ok(text.to_lowercase())
+            }
+            Some(Token::Word(text)) | Some(Token::Text(text)) => Ok(text),
+            other => Err(format!(
+                "Expected a value in --where expression, found {}.",
+                describe(other)
+            )
+            .into()),
+        }
+    }
+}
+
+/// Describes a token for error messages.
+fn describe(token: Option<Token>) -> String {
+    match token {
+        Some(Token::Word(word)) => format!("'{word}'"),
+        Some(Token::Text(text)) => format!("'{text}'"),
+        Some(Token::Op(op)) => format!("'{op}'"),
+        None => "the end of the expression".to_string(),
+    }
+}
+
+/// Converts a field name into a `Field`.
+fn parse_field(word: &str) -> Result<Field, Box<dyn Error>> {
+    Ok(match word.to_ascii_lowercase().as_str() {
+        "size" => Field::Size,
+        "mtime" => Field::Age(TimeField::Modified),
+        "atime" => Field::Age(TimeField::Accessed),
+        "ctime" => Field::Age(TimeField::Changed),
+        "btime" => Field::Age(TimeField::Created),
+        "name" => Field::Name,
+        "ext" => Field::Ext,
+        "path" => Field::Path,
+        "type" => Field::Type,
+        _ => return Err(format!("Unknown field in --where expression: {word}").into()),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::backend::{FileType, RemoveResults};
+    use std::io::Read;
+
+    /// A backend that knows the type and modification time of every file, for evaluating
+    /// expressions without touching the disk.
+    pub(crate) struct Fake {
+        pub file_type: FileType,
+        pub modified: Option<SystemTime>,
+    }
+
+    impl Backend for Fake {
+        fn resolve(&mut self, target: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+            Ok(vec![Entry {
+                name: target.to_string(),
+                size: 0,
+            }])
+        }
+
+        fn file_type(&mut self, _entry: &Entry) -> Result<Option<FileType>, Box<dyn Error>> {
+            Ok(Some(self.file_type))
+        }
+
+        fn time(
+            &mut self,
+            _entry: &Entry,
+            field: TimeField,
+        ) -> Result<Option<SystemTime>, Box<dyn Error>> {
+            Ok(self.modified.filter(|_| field == TimeField::Modified))
+        }
+
+        fn open(&mut self, _entry: &Entry) -> Result<Box<dyn Read + '_>, Box<dyn Error>> {
+            Err("Not readable.".into())
+        }
+
+        fn remove(&mut self, _entries: &[Entry]) -> Result<RemoveResults, Box<dyn Error>> {
+            Err("Not removable.".into())
+        }
+    }
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// Evaluates `expr` for a file of `file_type` at `path` of `size` bytes, modified `age` ago.
+    pub(crate) fn check(
+        expr: &Expr,
+        file_type: FileType,
+        path: &str,
+        size: u64,
+        age: Duration,
+        ignore_case: bool,
+    ) -> bool {
+        let now = SystemTime::now();
+        let mut backend = Fake {
+            file_type,
+            modified: Some(now - age),
+        };
+        let entry = Entry {
+            name: path.to_string(),
+            size,
+        };
+        let mut subject = Subject {
+            entry: &entry,
+            backend: &mut backend,
+            now,
+        };
+        expr.matches(&mut subject, ignore_case).unwrap()
+    }
+
+    /// Evaluates `text` for a regular file at `path` of `size` bytes, modified 40 days ago.
+    fn eval(text: &str, path: &str, size: u64, ignore_case: bool) -> bool {
+        let expr = Expr::parse(text, ignore_case).unwrap();
+        check(&expr, FileType::File, path, size, 40 * DAY, ignore_case)
+    }
+
+    fn error(text: &str) -> String {
+        Expr::parse(text, false).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let expr = Expr::parse("name == x || size > 10 && ext == log", false).unwrap();
+        assert!(matches!(expr, Expr::Or(_, ref right) if matches!(**right, Expr::And(..))));
+
+        let text = "name == x || size > 10 && ext == log";
+        assert!(eval(text, "/d/x", 0, false));
+        assert!(eval(text, "/d/y.log", 100, false));
+        assert!(!eval(text, "/d/y.txt", 100, false));
+        assert!(!eval(text, "/d/y.log", 1, false));
+    }
+
+    #[test]
+    fn parentheses_group() {
+        let text = "(name == x || size > 10) && ext == log";
+        assert!(!eval(text, "/d/x", 0, false));
+        assert!(eval(text, "/d/y.log", 100, false));
+        assert!(eval("((size >= 10))", "/d/y", 10, false));
+        assert_eq!(error("(size > 10"), "Expected ')' in --where expression.");
+    }
+
+    #[test]
+    fn negation() {
+        assert!(eval("!(size > 10)", "/d/y", 5, false));
+        assert!(!eval("!size < 10", "/d/y", 5, false));
+        assert!(eval("not size > 10", "/d/y", 5, false));
+        assert!(eval(
+            "NOT name == z and not ext == log",
+            "/d/y.txt",
+            5,
+            false
+        ));
+        assert!(!eval("!!(size > 10)", "/d/y", 5, false));
+    }
+
+    #[test]
+    fn in_takes_quoted_and_unquoted_values() {
+        let text = "ext in ('log', tmp, \"bak file\")";
+        assert!(eval(text, "/d/a.log", 0, false));
+        assert!(eval(text, "/d/a.tmp", 0, false));
+        assert!(eval(text, "/d/a.bak file", 0, false));
+        assert!(!eval(text, "/d/a.txt", 0, false));
+        assert!(eval("name in (a.txt)", "/d/a.txt", 0, false));
+        assert_eq!(
+            error("size in (1, 2)"),
+            "'in' can only be used with name, ext, path and type."
+        );
+    }
+
+    #[test]
+    fn sizes_ages_and_types() {
+        assert!(eval("size >= 1K", "/d/a", 1024, false));
+        assert!(!eval("size > 1K", "/d/a", 1024, false));
+        assert!(eval("mtime > 30d", "/d/a", 0, false));
+        assert!(!eval("mtime < 30d", "/d/a", 0, false));
+        // A timestamp the backend doesn't know never matches
+        assert!(!eval("atime > 1s", "/d/a", 0, false));
+        assert!(eval("type == f", "/d/a", 0, false));
+        assert!(eval("path ~ '/d/*'", "/d/a", 0, false));
+    }
+
+    #[test]
+    fn unterminated_strings_are_errors() {
+        assert_eq!(
+            error("name == 'abc"),
+            "Unterminated string in --where expression."
+        );
+        assert_eq!(
+            error("name in (\"a, b)"),
+            "Unterminated string in --where expression."
+        );
+    }
+
+    #[test]
+    fn unexpected_tokens_are_errors() {
+        assert_eq!(
+            error("size > 10 )"),
+            "Unexpected ')' in --where expression."
+        );
+        assert_eq!(
+            error("size > 10 name"),
+            "Unexpected 'name' in --where expression."
+        );
+        assert_eq!(
+            error("size >"),
+            "Expected a value in --where expression, found the end of the expression."
+        );
+        assert_eq!(
+            error("weight > 10"),
+            "Unknown field in --where expression: weight"
+        );
+    }
+
+    #[test]
+    fn globs_are_only_for_text_fields() {
+        for text in ["size ~ 10", "size ~ '*'", "mtime ~ 1d", "ctime ~ '*'"] {
+            assert_eq!(
+                error(text),
+                "'~' can only be used with name, ext, path and type.",
+                "{text}"
+            );
+        }
+        assert_eq!(
+            error("name > a"),
+            "Text fields can only be compared with ==, != and ~."
+        );
+    }
+
+    #[test]
+    fn dotfiles_have_no_extension() {
+        assert!(eval("ext == ''", "/home/me/.bashrc", 0, false));
+        assert!(!eval("ext == bashrc", "/home/me/.bashrc", 0, false));
+        assert!(eval("ext == gz", "/d/archive.tar.gz", 0, false));
+        assert!(eval("ext == ''", "/d/Makefile", 0, false));
+        assert!(eval("ext == txt", "C:\\d\\notes.txt", 0, false));
+    }
+
+    #[test]
+    fn ignoring_case_lower_cases_both_sides() {
+        assert!(eval("name == 'README.MD'", "/d/ReadMe.md", 0, true));
+        assert!(!eval("name == 'README.MD'", "/d/ReadMe.md", 0, false));
+        assert!(eval("ext in (LOG, Tmp)", "/d/a.tMp", 0, true));
+        assert!(eval("name ~ 'read*'", "/d/README", 0, true));
+        assert!(!eval("name ~ 'read*'", "/d/README", 0, false));
+        assert!(eval("NAME != 'other'", "/d/README", 0, true));
+    }
+}
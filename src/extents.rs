@@ -0,0 +1,97 @@
+use rustix::fs::{self as rfs, Mode, OFlags};
+use rustix::io::Errno;
+use rustix::ioctl::{self, opcode, Opcode, Updater};
+use std::error::Error;
+use std::path::Path;
+
+/// How many extents are fetched with each FIEMAP call.
+const EXTENT_COUNT: usize = 64;
+
+/// Set on the last extent of a file.
+const FIEMAP_EXTENT_LAST: u32 = 0x0001;
+
+/// Set on extents that are shared with other files, e.g. after a reflink copy or a snapshot.
+const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+
+/// `struct fiemap` from `linux/fiemap.h`, without the extents that follow it.
+#[repr(C)]
+#[derive(Default)]
+struct FiemapHeader {
+    start: u64,
+    length: u64,
+    flags: u32,
+    mapped_extents: u32,
+    extent_count: u32,
+    reserved: u32,
+}
+
+/// `struct fiemap_extent` from `linux/fiemap.h`.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FiemapExtent {
+    logical: u64,
+    physical: u64,
+    length: u64,
+    reserved64: [u64; 2],
+    flags: u32,
+    reserved: [u32; 3],
+}
+
+/// A `struct fiemap` with room for the extents.
+#[repr(C)]
+struct Fiemap {
+    header: FiemapHeader,
+    extents: [FiemapExtent; EXTENT_COUNT],
+}
+
+/// `FS_IOC_FIEMAP`, which maps the extents of a file.
+const FS_IOC_FIEMAP: Opcode = opcode::read_write::<FiemapHeader>(b'f', 11);
+
+/// Returns how many bytes of a file are in extents shared with other files (reflinks on btrfs
+/// and XFS, or snapshots), which removing the file won't free. File systems that don't map
+/// extents report nothing shared.
+pub fn shared_size(path: &Path) -> Result<u64, Box<dyn Error>> {
+    let fd = rfs::open(
+        path,
+        OFlags::RDONLY | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+
+    let mut shared = 0;
+    let mut start = 0;
+    loop {
+        let mut map = Fiemap {
+            header: FiemapHeader {
+                start,
+                length: u64::MAX - start,
+                extent_count: EXTENT_COUNT as u32,
+                ..Default::default()
+            },
+            extents: [FiemapExtent::default(); EXTENT_COUNT],
+        };
+
+        // SAFETY: FS_IOC_FIEMAP takes a struct fiemap followed by extent_count extents,
+        // which Fiemap matches
+        match unsafe { ioctl::ioctl(&fd, Updater::<FS_IOC_FIEMAP, Fiemap>::new(&mut map)) } {
+            Ok(()) => {}
+            Err(Errno::OPNOTSUPP | Errno::NOTTY) => return Ok(0),
+            Err(err) => return Err(err.into()),
+        }
+
+        let mapped = &map.extents[..(map.header.mapped_extents as usize).min(EXTENT_COUNT)];
+        let Some(last) = mapped.last() else {
+            break;
+        };
+        shared += mapped
+            .iter()
+            .filter(|extent| extent.flags & FIEMAP_EXTENT_SHARED != 0)
+            .map(|extent| extent.length)
+            .sum::<u64>();
+        if last.flags & FIEMAP_EXTENT_LAST != 0 {
+            break;
+        }
+        start = last.logical + last.length;
+    } // loop
+
+    Ok(shared)
+}
@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::io;
+
+/// Which removals are made to fail on purpose, for `--inject-failures`, so the automation
+/// around rdel can be tested against partial failures.
+#[derive(Debug, Clone)]
+pub enum FaultInjection {
+    /// A random fraction of the removals, between 0 and 1.
+    Rate(f64),
+
+    /// The removals of the files whose paths match the pattern.
+    Pattern(glob::Pattern),
+}
+
+impl FaultInjection {
+    /// Parses the command line value: a rate such as `0.1` or `10%`, or else a glob pattern
+    /// such as `*.log`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let rate = match value.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f64>().ok().map(|rate| rate / 100.0),
+            None => value.parse::<f64>().ok(),
+        };
+        match rate {
+            Some(rate) if (0.0..=1.0).contains(&rate) => Ok(Self::Rate(rate)),
+            Some(_) => Err(format!(
+                "{value} is not a rate between 0 and 1 (or 0% and 100%)."
+            )),
+            None => glob::Pattern::new(value)
+                .map(Self::Pattern)
+                .map_err(|err| format!("{value} is not a valid pattern: {err}")),
+        }
+    }
+
+    /// Returns the error to fail the removal of the file with, if it's one to fail.
+    pub fn failure(&self, filename: &str) -> Option<Box<dyn Error>> {
+        let fail = match self {
+            Self::Rate(rate) => rand::random::<f64>() < *rate,
+            Self::Pattern(pattern) => pattern.matches(filename),
+        };
+        fail.then(|| io::Error::other("Injected failure (--inject-failures)").into())
+    }
+}
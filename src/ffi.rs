@@ -0,0 +1,281 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::deleter::{Deleter, Summary};
+use crate::expr::Expr;
+use crate::observer::Observer;
+use crate::report::{FileReport, Outcome};
+
+/// Move files to the trash instead of deleting them.
+pub const RDEL_TRASH: u32 = 1;
+
+/// Remove directories along with everything in them.
+pub const RDEL_RECURSIVE: u32 = 2;
+
+/// The outcomes passed to the progress callback.
+pub const RDEL_REMOVED: c_int = 0;
+pub const RDEL_WOULD_REMOVE: c_int = 1;
+pub const RDEL_SKIPPED: c_int = 2;
+pub const RDEL_FAILED: c_int = 3;
+
+/// Called with each file as it's dealt with: the user data given with it, the path, the size
+/// and one of the outcomes. The path is only valid during the call.
+pub type RdelProgress =
+    extern "C" fn(user_data: *mut c_void, path: *const c_char, size: u64, outcome: c_int);
+
+/// The totals of a run, filled in by `rdel_plan` and `rdel_execute`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RdelSummary {
+    pub files_examined: u64,
+    pub files_removed: u64,
+    pub files_failed: u64,
+    pub bytes_removed: u64,
+    pub bytes_freed: u64,
+}
+
+impl From<&Summary> for RdelSummary {
+    fn from(summary: &Summary) -> Self {
+        Self {
+            files_examined: summary.total_file_count as u64,
+            files_removed: summary.processed_file_count as u64,
+            files_failed: (summary.skipped_file_count + summary.timed_out_file_count) as u64,
+            bytes_removed: summary.total_file_size,
+            bytes_freed: summary.freed_size(),
+        }
+    }
+}
+
+/// The options for the runs started from C, made with `rdel_new`.
+pub struct RdelOptions {
+    filter: Option<String>,
+    flags: u32,
+    progress: Option<Progress>,
+}
+
+/// The progress callback and the user data to pass it.
+#[derive(Clone, Copy)]
+struct Progress {
+    callback: RdelProgress,
+    user_data: *mut c_void,
+}
+
+// The caller of rdel_set_progress promises the user data can be used from the thread the run
+// is started on, which is the only one it's used from
+unsafe impl Send for Progress {}
+
+impl Observer for Progress {
+    fn on_file_done(&mut self, file: &FileReport) {
+        let Ok(path) = CString::new(file.path.as_str()) else {
+            return;
+        };
+        let outcome = match file.outcome {
+            Outcome::Removed => RDEL_REMOVED,
+            Outcome::WouldRemove => RDEL_WOULD_REMOVE,
+            Outcome::Skipped => RDEL_SKIPPED,
+            Outcome::Failed => RDEL_FAILED,
+        };
+        (self.callback)(self.user_data, path.as_ptr(), file.size, outcome);
+    }
+}
+
+thread_local! {
+    /// The message of the last error on this thread, for `rdel_last_error`.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Keeps the message of an error for `rdel_last_error`.
+fn set_last_error(err: &dyn Error) {
+    let message = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Creates the options for runs, with an optional filter expression (as for `--where`) and
+/// any of the `RDEL_TRASH` and `RDEL_RECURSIVE` flags. Returns NULL, with the reason available
+/// from `rdel_last_error`, if the filter isn't valid. Free the options with `rdel_free`.
+///
+/// # Safety
+///
+/// `filter` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rdel_new(filter: *const c_char, flags: u32) -> *mut RdelOptions {
+    let filter = if filter.is_null() {
+        None
+    } else {
+        let parsed = CStr::from_ptr(filter)
+            .to_str()
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|filter| Expr::parse(filter, false).map(|_| filter.to_string()));
+        match parsed {
+            Ok(filter) => Some(filter),
+            Err(err) => {
+                set_last_error(&*err);
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    Box::into_raw(Box::new(RdelOptions {
+        filter,
+        flags,
+        progress: None,
+    }))
+}
+
+/// Sets the callback told about each file as it's dealt with, or removes it if NULL.
+///
+/// # Safety
+///
+/// `options` must come from `rdel_new`. `user_data` is passed to the callback as it is, on the
+/// thread that starts the run.
+#[no_mangle]
+pub unsafe extern "C" fn rdel_set_progress(
+    options: *mut RdelOptions,
+    callback: Option<RdelProgress>,
+    user_data: *mut c_void,
+) {
+    if let Some(options) = options.as_mut() {
+        options.progress = callback.map(|callback| Progress {
+            callback,
+            user_data,
+        });
+    }
+}
+
+/// Works out what would be removed, without removing anything, as for `--dry-run`. Returns 0
+/// on success, with the totals in `summary` if it isn't NULL, or -1 with the reason available
+/// from `rdel_last_error`.
+///
+/// # Safety
+///
+/// `options` must come from `rdel_new`, and `targets` must point to `count` NUL-terminated
+/// strings. `summary` must be NULL or point to an `RdelSummary`.
+#[no_mangle]
+pub unsafe extern "C" fn rdel_plan(
+    options: *const RdelOptions,
+    targets: *const *const c_char,
+    count: usize,
+    summary: *mut RdelSummary,
+) -> c_int {
+    run(options, targets, count, summary, true)
+}
+
+/// Removes the files the targets refer to. Returns like `rdel_plan`.
+///
+/// # Safety
+///
+/// As for `rdel_plan`.
+#[no_mangle]
+pub unsafe extern "C" fn rdel_execute(
+    options: *const RdelOptions,
+    targets: *const *const c_char,
+    count: usize,
+    summary: *mut RdelSummary,
+) -> c_int {
+    run(options, targets, count, summary, false)
+}
+
+/// Returns the message of the last error on this thread, or NULL if there hasn't been one.
+/// The message is valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn rdel_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.as_ptr())
+    })
+}
+
+/// Frees the options made by `rdel_new`.
+///
+/// # Safety
+///
+/// `options` must be NULL or come from `rdel_new`, and mustn't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rdel_free(options: *mut RdelOptions) {
+    if !options.is_null() {
+        drop(Box::from_raw(options));
+    }
+}
+
+/// Returns the message a panic was started with, if it was given one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "no message"
+    }
+}
+
+/// Runs with the options, as a dry-run or for real, and fills in the summary. A panic is caught
+/// and reported as an error, as it mustn't unwind into the caller.
+unsafe fn run(
+    options: *const RdelOptions,
+    targets: *const *const c_char,
+    count: usize,
+    summary: *mut RdelSummary,
+    dry_run: bool,
+) -> c_int {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| -> Result<Summary, Box<dyn Error>> {
+        let options = options.as_ref().ok_or("No options given.")?;
+        let targets = match count {
+            0 => Vec::new(),
+            _ if targets.is_null() => return Err("No targets given.".into()),
+            _ => std::slice::from_raw_parts(targets, count)
+                .iter()
+                .map(|&target| match target.is_null() {
+                    true => Err("A target is NULL.".into()),
+                    false => Ok(CStr::from_ptr(target).to_str()?.to_string()),
+                })
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?,
+        };
+
+        let mut builder = Deleter::builder()
+            .dry_run(dry_run)
+            .trash(options.flags & RDEL_TRASH != 0)
+            .recursive(options.flags & RDEL_RECURSIVE != 0);
+        if let Some(filter) = &options.filter {
+            builder = builder.filter(filter);
+        }
+        if let Some(progress) = options.progress {
+            builder = builder.observer(progress);
+        }
+
+        builder.build()?.run_to_end(targets.into_iter().map(Ok))
+    }))
+    .unwrap_or_else(|payload| Err(format!("rdel panicked: {}", panic_message(&*payload)).into()));
+
+    match result {
+        Ok(totals) => {
+            if let Some(summary) = summary.as_mut() {
+                *summary = RdelSummary::from(&totals);
+            }
+            0
+        }
+        Err(err) => {
+            set_last_error(&*err);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_messages() {
+        let payload = panic::catch_unwind(|| panic!("plain")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "plain");
+        let payload = panic::catch_unwind(|| panic!("formatted {}", 1)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "formatted 1");
+        let payload = panic::catch_unwind(|| panic::panic_any(1)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "no message");
+    }
+}
@@ -0,0 +1,179 @@
+use clap::ArgMatches;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::age::TimeField;
+use crate::expr::{Expr, Field, Op, Value};
+
+/// The length of a day, as used by `--mtime`.
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Converts the find-style predicates (`--type`, `--size` and `--mtime`) into a filter expression,
+/// so they go through the same filter pipeline as `--where`. All the predicates must match.
+pub fn predicates(cli_args: &ArgMatches) -> Result<Option<Expr>, Box<dyn Error>> {
+    let mut expr = None;
+
+    if let Some(types) = cli_args.get_one::<String>("type") {
+        expr = Expr::and(expr, Some(file_type(types)?));
+    }
+    for size in cli_args.get_many::<String>("size").unwrap_or_default() {
+        expr = Expr::and(expr, Some(size_predicate(size)?));
+    }
+    for days in cli_args.get_many::<String>("mtime").unwrap_or_default() {
+        expr = Expr::and(expr, Some(mtime(days)?));
+    }
+
+    Ok(expr)
+}
+
+/// `--type f,l`: the entry is one of the listed kinds.
+fn file_type(types: &str) -> Result<Expr, Box<dyn Error>> {
+    let types: Vec<String> = types.split(',').map(str::to_string).collect();
+    if let Some(unknown) = types
+        .iter()
+        .find(|t| !["f", "d", "l"].contains(&t.as_str()))
+    {
+        return Err(format!("Invalid --type {unknown}. Use f, d or l.").into());
+    }
+
+    Ok(Expr::In(Field::Type, types))
+}
+
+/// Splits `+N`, `-N` or `N` into the comparison to use and the rest of the value.
+fn split_sign(value: &str) -> (Op, &str) {
+    match value.as_bytes().first() {
+        Some(b'+') => (Op::Gt, &value[1..]),
+        Some(b'-') => (Op::Lt, &value[1..]),
+        _ => (Op::Eq, value),
+    }
+}
+
+/// `--size [+-]N[ckMG]`: larger than, smaller than or exactly N. As with `find`, N is in 512-byte
+/// blocks without a suffix, in bytes with `c`, and in KiB, MiB or GiB with `k`, `M` or `G`.
+fn size_predicate(value: &str) -> Result<Expr, Box<dyn Error>> {
+    let invalid = || format!("Invalid --size {value}. Expected e.g. +100M.");
+
+    let (op, rest) = split_sign(value);
+    let split = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let (number, unit) = rest.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit {
+        "" | "b" => 512,
+        "c" => 1,
+        "k" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(invalid().into()),
+    };
+    let size = number.checked_mul(multiplier).ok_or_else(invalid)?;
+
+    Ok(Expr::Compare(Field::Size, op, Value::Size(size)))
+}
+
+/// `--mtime [+-]N`: modified more than, less than or exactly N days ago. As with `find`,
+/// the age is counted in whole days, so `+30` means at least 31 days ago.
+fn mtime(value: &str) -> Result<Expr, Box<dyn Error>> {
+    let (op, rest) = split_sign(value);
+    let days: u32 = rest
+        .parse()
+        .map_err(|_| format!("Invalid --mtime {value}. Expected e.g. +30."))?;
+    let age = |op, days| Expr::Compare(Field::Age(TimeField::Modified), op, Value::Age(DAY * days));
+
+    Ok(match op {
+        Op::Gt => age(Op::Ge, days + 1),
+        Op::Lt => age(Op::Lt, days),
+        _ => Expr::And(Box::new(age(Op::Ge, days)), Box::new(age(Op::Lt, days + 1))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::FileType;
+    use crate::expr::tests::check;
+
+    const MIB: u64 = 1 << 20;
+
+    fn sized(expr: &Expr, size: u64) -> bool {
+        check(expr, FileType::File, "/d/f", size, DAY, false)
+    }
+
+    fn aged(expr: &Expr, age: Duration) -> bool {
+        check(expr, FileType::File, "/d/f", 0, age, false)
+    }
+
+    fn error(result: Result<Expr, Box<dyn Error>>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn types_are_listed_letters() {
+        let expr = file_type("f,l").unwrap();
+        assert!(check(&expr, FileType::File, "/d/f", 0, DAY, false));
+        assert!(check(&expr, FileType::Symlink, "/d/f", 0, DAY, false));
+        assert!(!check(&expr, FileType::Dir, "/d/f", 0, DAY, false));
+        assert_eq!(error(file_type("f,x")), "Invalid --type x. Use f, d or l.");
+        assert_eq!(error(file_type("")), "Invalid --type . Use f, d or l.");
+    }
+
+    #[test]
+    fn sizes_are_in_blocks_unless_a_unit_is_given() {
+        let expr = size_predicate("2").unwrap();
+        assert!(sized(&expr, 1024));
+        assert!(!sized(&expr, 1023));
+        assert!(sized(&size_predicate("10c").unwrap(), 10));
+        assert!(sized(&size_predicate("4b").unwrap(), 2048));
+        assert!(sized(&size_predicate("3k").unwrap(), 3072));
+        assert!(sized(&size_predicate("1G").unwrap(), 1 << 30));
+    }
+
+    #[test]
+    fn size_signs_compare() {
+        let larger = size_predicate("+100M").unwrap();
+        assert!(sized(&larger, 100 * MIB + 1));
+        assert!(!sized(&larger, 100 * MIB));
+        let smaller = size_predicate("-1M").unwrap();
+        assert!(sized(&smaller, MIB - 1));
+        assert!(!sized(&smaller, MIB));
+    }
+
+    #[test]
+    fn invalid_sizes_are_errors() {
+        for value in ["", "+", "M", "10T", "1.5M", "+-1", "18014398509481984G"] {
+            assert_eq!(
+                error(size_predicate(value)),
+                format!("Invalid --size {value}. Expected e.g. +100M.")
+            );
+        }
+    }
+
+    #[test]
+    fn mtime_counts_whole_days() {
+        let exactly = mtime("2").unwrap();
+        assert!(aged(&exactly, 2 * DAY));
+        assert!(aged(&exactly, 3 * DAY - Duration::from_secs(1)));
+        assert!(!aged(&exactly, 3 * DAY));
+        assert!(!aged(&exactly, 2 * DAY - Duration::from_secs(60)));
+
+        // +30 means at least 31 days, as with find
+        let older = mtime("+30").unwrap();
+        assert!(aged(&older, 31 * DAY));
+        assert!(!aged(&older, 30 * DAY + Duration::from_secs(3600)));
+
+        let newer = mtime("-1").unwrap();
+        assert!(aged(&newer, Duration::from_secs(3600)));
+        assert!(!aged(&newer, DAY));
+    }
+
+    #[test]
+    fn invalid_mtimes_are_errors() {
+        for value in ["", "+", "1d", "-1.5", "x"] {
+            assert_eq!(
+                error(mtime(value)),
+                format!("Invalid --mtime {value}. Expected e.g. +30.")
+            );
+        }
+    }
+}
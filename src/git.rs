@@ -0,0 +1,224 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+
+/// How many ignored files can be found ahead of the ones being processed.
+const QUEUE_SIZE: usize = 1024;
+
+/// Receives each ignored file as it's found.
+type Found<'a> = dyn FnMut(String) -> Result<(), Box<dyn Error>> + 'a;
+
+/// Finds the files in a git work tree that are ignored by its `.gitignore` rules, like the ones
+/// `git clean -X` would remove. The rules in nested `.gitignore` files, `.git/info/exclude` and
+/// the global excludes file are all applied. Nested repositories are left alone.
+///
+/// The work tree is searched in the background, and the files are returned as they are found,
+/// so work trees of any size can be handled without holding every path in memory. The files in
+/// each directory are returned in order of name. An error during the search is returned after
+/// the files found before it.
+pub fn ignored_files(
+    repo: &Path,
+) -> Result<impl Iterator<Item = Result<String, Box<dyn Error>>>, Box<dyn Error>> {
+    if !repo.join(".git").exists() {
+        return Err(format!("{} is not the root of a git work tree.", repo.display()).into());
+    }
+
+    let repo = repo.to_path_buf();
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+    let worker = thread::spawn(move || -> Result<(), String> {
+        // Later matchers take precedence over earlier ones
+        let (global, err) = Gitignore::global();
+        if let Some(err) = err {
+            log::warn!("Unable to read the global git excludes file: {err}");
+        }
+        let mut matchers = vec![global];
+        if let Some(exclude) = matcher(&repo, &repo.join(".git").join("info").join("exclude")) {
+            matchers.push(exclude);
+        }
+
+        // Sending only fails when the files are no longer wanted, which ends the search
+        let mut count = 0;
+        let mut found = |file: String| -> Result<(), Box<dyn Error>> {
+            count += 1;
+            sender
+                .send(file)
+                .map_err(|_| "No longer looking for ignored files.".into())
+        };
+        walk(&repo, &mut matchers, &mut found).map_err(|err| err.to_string())?;
+        log::debug!("Found {count} ignored files in {}", repo.display());
+
+        Ok(())
+    });
+
+    let mut worker = Some(worker);
+    let outcome = std::iter::from_fn(move || match worker.take()?.join() {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => Some(Err(err.into())),
+        Err(_) => Some(Err("The search for ignored files failed.".into())),
+    });
+
+    Ok(receiver.into_iter().map(Ok).chain(outcome))
+}
+
+/// Reads the ignore rules in `file`, which apply to paths below `dir`.
+fn matcher(dir: &Path, file: &Path) -> Option<Gitignore> {
+    if !file.is_file() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(err) = builder.add(file) {
+        log::warn!("Unable to read {}: {err}", file.display());
+    }
+    builder.build().ok()
+}
+
+/// Returns true if the rules ignore the path. The rules closest to the path win.
+fn is_ignored(matchers: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    for matcher in matchers.iter().rev() {
+        match matcher.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+
+    false
+}
+
+/// Hands the ignored files below `dir` to `found`, applying any `.gitignore` in `dir` on the way.
+fn walk(
+    dir: &Path,
+    matchers: &mut Vec<Gitignore>,
+    found: &mut Found,
+) -> Result<(), Box<dyn Error>> {
+    let local = matcher(dir, &dir.join(".gitignore"));
+    let pushed = local.is_some();
+    matchers.extend(local);
+
+    for entry in sorted_entries(dir)? {
+        let path = entry.path();
+        let is_dir = entry.file_type()?.is_dir();
+
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        if is_dir && path.join(".git").exists() {
+            log::debug!("Skipping nested repository {}", path.display());
+            continue;
+        }
+
+        if is_ignored(matchers, &path, is_dir) {
+            if is_dir {
+                // Everything in an ignored directory is ignored, whatever the rules below it say
+                collect_all(&path, found)?;
+            } else {
+                add_file(&path, found)?;
+            }
+        } else if is_dir {
+            walk(&path, matchers, found)?;
+        }
+    }
+
+    if pushed {
+        matchers.pop();
+    }
+
+    Ok(())
+}
+
+/// Hands every file below `dir` to `found`, without following symbolic links.
+fn collect_all(dir: &Path, found: &mut Found) -> Result<(), Box<dyn Error>> {
+    for entry in sorted_entries(dir)? {
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            if path.join(".git").exists() {
+                log::debug!("Skipping nested repository {}", path.display());
+            } else {
+                collect_all(&path, found)?;
+            }
+        } else {
+            add_file(&path, found)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the entries in a directory, in order of name.
+fn sorted_entries(dir: &Path) -> Result<Vec<fs::DirEntry>, Box<dyn Error>> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+    Ok(entries)
+}
+
+/// Hands a file to `found`, skipping paths that aren't valid UTF-8.
+fn add_file(path: &Path, found: &mut Found) -> Result<(), Box<dyn Error>> {
+    match path.to_str() {
+        Some(path_str) => found(path_str.to_string())?,
+        None => log::warn!("Skipping {}: not a valid UTF-8 path.", path.display()),
+    }
+    Ok(())
+}
+
+/// The files tracked in the index of each git work tree seen so far.
+#[derive(Debug, Default)]
+pub struct TrackedFiles {
+    repos: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+impl TrackedFiles {
+    /// Returns the root of the work tree containing `path`, if `path` is tracked in its index.
+    /// `path` should be absolute. The index is read using `git ls-files` the first time
+    /// a work tree is seen.
+    pub fn tracked_in(&mut self, path: &Path) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        let Some(root) = path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join(".git").exists())
+        else {
+            return Ok(None);
+        };
+
+        if !self.repos.contains_key(root) {
+            self.repos.insert(root.to_path_buf(), list_tracked(root)?);
+        }
+
+        Ok(self.repos[root].contains(path).then(|| root.to_path_buf()))
+    }
+}
+
+/// Lists the files in the index of the work tree at `root`.
+fn list_tracked(root: &Path) -> Result<HashSet<PathBuf>, Box<dyn Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "-z", "--full-name"])
+        .output()
+        .map_err(|err| format!("Unable to run git to check for tracked files: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Unable to list the tracked files in {}: {}",
+            root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    let tracked: HashSet<PathBuf> = output
+        .stdout
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| root.join(String::from_utf8_lossy(name).as_ref()))
+        .collect();
+    log::debug!("{} files tracked in {}", tracked.len(), root.display());
+
+    Ok(tracked)
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Something about a file that makes removing it by mistake costlier than usual, especially on
+/// a shared system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hazard {
+    /// A program that runs with the privileges of its owner, e.g. `passwd`.
+    Setuid,
+
+    /// A program that runs with the privileges of its group.
+    Setgid,
+
+    /// Another user's file in a directory with the sticky bit set, e.g. `/tmp`, where only
+    /// its owner (or root) could normally remove it.
+    SharedDir { dir: PathBuf, owner: u32 },
+
+    /// A file in a directory without the sticky bit that anyone can write to, so anyone could
+    /// have put it there, or swapped it for something else.
+    WorldWritableDir(PathBuf),
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Setuid => write!(
+                f,
+                "is a setuid program, which runs with the privileges of its owner"
+            ),
+            Self::Setgid => write!(
+                f,
+                "is a setgid program, which runs with the privileges of its group"
+            ),
+            Self::SharedDir { dir, owner } => write!(
+                f,
+                "belongs to user {owner}, in the shared directory {}",
+                dir.display()
+            ),
+            Self::WorldWritableDir(dir) => {
+                write!(f, "is in {}, which anyone can write to", dir.display())
+            }
+        }
+    }
+}
+
+/// Checks a local file for the hazards of removing it. Returns the first one found, if any.
+/// Only Unix has the permission bits to check; elsewhere nothing is found.
+pub fn check(path: &Path) -> Option<Hazard> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        const SETUID: u32 = 0o4000;
+        const SETGID: u32 = 0o2000;
+        const STICKY: u32 = 0o1000;
+        const WORLD_WRITABLE: u32 = 0o002;
+
+        let metadata = path.symlink_metadata().ok()?;
+        let mode = metadata.permissions().mode();
+        // A setgid directory only hands its group on to new files
+        if metadata.is_file() && mode & SETUID != 0 {
+            return Some(Hazard::Setuid);
+        }
+        if metadata.is_file() && mode & SETGID != 0 {
+            return Some(Hazard::Setgid);
+        }
+
+        let dir = match path.parent() {
+            Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+            parent => parent?,
+        };
+        let dir_mode = dir.metadata().ok()?.permissions().mode();
+        if dir_mode & STICKY != 0 {
+            // Removing one's own files from /tmp is what it's for
+            let owner = metadata.uid();
+            return (owner != rustix::process::geteuid().as_raw()).then(|| Hazard::SharedDir {
+                dir: dir.to_path_buf(),
+                owner,
+            });
+        }
+        (dir_mode & WORLD_WRITABLE != 0).then(|| Hazard::WorldWritableDir(dir.to_path_buf()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
@@ -0,0 +1,81 @@
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// A user command run by the shell as files are removed, e.g. to tell a cache index or a CDN
+/// about each file deleted, or to take a snapshot before a run.
+#[derive(Debug, Clone)]
+pub struct Hook {
+    command: String,
+}
+
+impl Hook {
+    /// Sets up a hook for a shell command.
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+        }
+    }
+
+    /// Runs the command for a file, with `{}` replaced by the path and `{outcome}` by what
+    /// happened to the file, both quoted for the shell. They are also available as the
+    /// `RDEL_PATH` and `RDEL_OUTCOME` environment variables. Fails if the command does.
+    pub fn run_for_file(&self, path: &str, outcome: &str) -> Result<(), Box<dyn Error>> {
+        let command = self
+            .command
+            .replace("{outcome}", &quote(outcome))
+            .replace("{}", &quote(path));
+
+        let status = shell(&command)
+            .env("RDEL_PATH", path)
+            .env("RDEL_OUTCOME", outcome)
+            .status()
+            .map_err(|err| format!("Unable to run the hook for {path}: {err}"))?;
+        if !status.success() {
+            return Err(format!("The hook for {path} failed ({status}): {command}").into());
+        }
+        Ok(())
+    }
+
+    /// Runs the command with `input` on its standard input. Fails if the command does.
+    pub fn run_with_input(&self, input: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut child = shell(&self.command)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Unable to run the hook {}: {err}", self.command))?;
+
+        // The command doesn't have to read its input
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(input);
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("The hook failed ({status}): {}", self.command).into());
+        }
+        Ok(())
+    }
+}
+
+/// Returns a command running `command` with the system's shell.
+pub(crate) fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+/// Quotes a value so the shell passes it on as it is.
+fn quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
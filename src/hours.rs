@@ -0,0 +1,67 @@
+use chrono::{DateTime, Local, Timelike};
+use std::fmt;
+use std::time::SystemTime;
+
+/// A window of the day, in local time, within which files may be removed, e.g. a maintenance
+/// window of `01:00-05:00`. A window may wrap past midnight, as `22:00-02:00` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveHours {
+    /// The start of the window, in minutes after midnight.
+    start: u32,
+
+    /// The end of the window, in minutes after midnight. The window ends just before it.
+    end: u32,
+}
+
+impl ActiveHours {
+    /// Parses a window given as `HH:MM-HH:MM`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| format!("{value} is not a window such as 01:00-05:00"))?;
+        let hours = Self {
+            start: minutes(start.trim())?,
+            end: minutes(end.trim())?,
+        };
+        if hours.start == hours.end {
+            return Err(format!("The window {value} is empty"));
+        }
+        Ok(hours)
+    }
+
+    /// Tells whether `time` falls within the window, in local time.
+    pub fn contains(&self, time: SystemTime) -> bool {
+        let time = DateTime::<Local>::from(time);
+        let minute = time.hour() * 60 + time.minute();
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl fmt::Display for ActiveHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+/// Reads a time of day such as `01:00` or `24:00`, as minutes after midnight.
+fn minutes(value: &str) -> Result<u32, String> {
+    let invalid = || format!("{value} is not a time of day such as 01:00");
+    let (hour, minute) = value.split_once(':').ok_or_else(invalid)?;
+    let hour: u32 = hour.parse().map_err(|_| invalid())?;
+    let minute: u32 = minute.parse().map_err(|_| invalid())?;
+    if minute >= 60 || hour * 60 + minute > 24 * 60 {
+        return Err(invalid());
+    }
+    Ok(hour * 60 + minute)
+}
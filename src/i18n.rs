@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// The message catalogs, by language code. Each maps the English text of a message to its
+/// translation; messages missing from a catalog are shown in English.
+const CATALOGS: &[(&str, &str)] = &[
+    ("de", include_str!("../locales/de.toml")),
+    ("nb", include_str!("../locales/nb.toml")),
+];
+
+/// The languages that can be asked for with `--lang`.
+pub const LANGUAGES: &[&str] = &["en", "de", "nb"];
+
+/// The translations for the language in use, once it has been picked.
+static MESSAGES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Picks the language of the messages: `lang` if given (e.g. with `--lang`), or else the
+/// language of the first of `LC_ALL`, `LC_MESSAGES` and `LANG` that is set. English is used for
+/// languages there's no catalog for, and if this is never called. Only the first call counts.
+pub fn init(lang: Option<&str>) {
+    let locale = lang.map(str::to_string).or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+    });
+    let code = locale.as_deref().map_or("en", language_of);
+
+    let messages = CATALOGS
+        .iter()
+        .find(|(catalog, _)| *catalog == code)
+        .map(|(_, text)| {
+            toml::from_str(text).unwrap_or_else(|err| {
+                log::warn!("The {code} message catalog is invalid: {err}");
+                HashMap::new()
+            })
+        })
+        .unwrap_or_default();
+    let _ = MESSAGES.set(messages);
+}
+
+/// Returns the language code of a locale such as `nb_NO.UTF-8`. Norwegian (`no`) is written in
+/// Bokmål.
+fn language_of(locale: &str) -> &str {
+    let code = locale
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or_default();
+    match code {
+        "no" => "nb",
+        code => code,
+    }
+}
+
+/// Translates a message, given in English.
+pub fn t(message: &'static str) -> &'static str {
+    MESSAGES
+        .get()
+        .and_then(|messages| messages.get(message))
+        .map_or(message, String::as_str)
+}
+
+/// Translates a message, given in English, and fills in the `{name}` placeholders in it.
+pub fn tf(message: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter()
+        .fold(t(message).to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+}
@@ -0,0 +1,203 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The flag bits that stop a file being removed or renamed, whatever its permissions: those set
+/// with `chattr +i` and `chattr +a` on Linux, and the user and system immutable (`uchg`,
+/// `schg`) and append-only (`uappnd`, `sappnd`) flags on macOS and FreeBSD.
+#[cfg(target_os = "linux")]
+const IMMUTABLE: u32 = rustix::fs::IFlags::IMMUTABLE.bits();
+#[cfg(target_os = "linux")]
+const APPEND: u32 = rustix::fs::IFlags::APPEND.bits();
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+const IMMUTABLE: u32 = 0x0000_0002 | 0x0002_0000;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+const APPEND: u32 = 0x0000_0004 | 0x0004_0000;
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+const IMMUTABLE: u32 = 0;
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+const APPEND: u32 = 0;
+
+/// A flag that stops a file being removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    Immutable,
+    AppendOnly,
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Immutable => write!(f, "immutable"),
+            Self::AppendOnly => write!(f, "append-only"),
+        }
+    }
+}
+
+/// The flags of a file, as the file system keeps them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u32);
+
+impl Flags {
+    /// Reads the flags of a file. Symbolic links have none.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            use rustix::fs::{self as rfs, Mode, OFlags};
+
+            let fd = match rfs::open(
+                path,
+                OFlags::RDONLY | OFlags::NONBLOCK | OFlags::NOFOLLOW | OFlags::NOCTTY,
+                Mode::empty(),
+            ) {
+                Ok(fd) => fd,
+                Err(rustix::io::Errno::LOOP) => return Ok(Self(0)),
+                Err(err) => return Err(err.into()),
+            };
+            match rfs::ioctl_getflags(&fd) {
+                Ok(flags) => Ok(Self(flags.bits())),
+                // File systems without flags, and special files, have none to stop a removal
+                Err(rustix::io::Errno::NOTTY | rustix::io::Errno::NOTSUP) => Ok(Self(0)),
+                Err(err) => Err(err.into()),
+            }
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            use std::os::macos::fs::MetadataExt;
+            Ok(Self(path.symlink_metadata()?.st_flags()))
+        }
+
+        #[cfg(target_os = "freebsd")]
+        {
+            use std::os::freebsd::fs::MetadataExt;
+            Ok(Self(path.symlink_metadata()?.st_flags()))
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+        {
+            let _ = path;
+            Ok(Self(0))
+        }
+    }
+
+    /// Returns the flag that stops the file being removed, if any is set.
+    pub fn locking(self) -> Option<Flag> {
+        if self.0 & IMMUTABLE != 0 {
+            Some(Flag::Immutable)
+        } else if self.0 & APPEND != 0 {
+            Some(Flag::AppendOnly)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the flags without the ones that stop the file being removed.
+    pub fn unlocked(self) -> Self {
+        Self(self.0 & !(IMMUTABLE | APPEND))
+    }
+
+    /// Sets the flags of a file. Clearing the immutable and append-only flags needs root
+    /// (`CAP_LINUX_IMMUTABLE` on Linux) for all but the user flags on macOS and FreeBSD.
+    pub fn write(self, path: &Path) -> Result<(), Box<dyn Error>> {
+        #[cfg(target_os = "linux")]
+        {
+            use rustix::fs::{self as rfs, IFlags, Mode, OFlags};
+
+            let fd = rfs::open(
+                path,
+                OFlags::RDONLY | OFlags::NONBLOCK | OFlags::NOFOLLOW | OFlags::NOCTTY,
+                Mode::empty(),
+            )?;
+            rfs::ioctl_setflags(&fd, IFlags::from_bits_retain(self.0)).map_err(|err| {
+                format!(
+                    "Unable to change the flags of {}: {}",
+                    path.display(),
+                    io::Error::from(err)
+                )
+            })?;
+        }
+
+        // There's no call for it in the standard library, so chflags is used, with the flags
+        // as an octal number
+        #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+        {
+            let status = std::process::Command::new("chflags")
+                .arg(format!("{:o}", self.0))
+                .arg(path)
+                .status()
+                .map_err(|err| format!("Unable to run chflags: {err}"))?;
+            if !status.success() {
+                return Err(format!(
+                    "Unable to change the flags of {}: chflags failed with {status}.",
+                    path.display()
+                )
+                .into());
+            }
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+        let _ = path;
+
+        Ok(())
+    }
+}
+
+/// A file that couldn't be removed because it, or the directory it's in, has the immutable or
+/// append-only flag set.
+#[derive(Debug, Clone)]
+pub struct Locked {
+    /// The file removed.
+    pub target: PathBuf,
+
+    /// The file or directory with the flag.
+    pub path: PathBuf,
+    pub flag: Flag,
+    pub flags: Flags,
+}
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path == self.target {
+            write!(
+                f,
+                "{} is {}. Use --force-immutable to clear the flag and remove it.",
+                self.path.display(),
+                self.flag
+            )
+        } else {
+            write!(
+                f,
+                "{} can't be removed, as the directory it's in ({}) is {}. Use --force-immutable to clear the flag while removing it.",
+                self.target.display(),
+                self.path.display(),
+                self.flag
+            )
+        }
+    }
+}
+
+impl Error for Locked {}
+
+/// Finds out what stops a file being removed: the immutable or append-only flag on the file
+/// itself, or on the directory it's in. Called once a removal has been refused, as looking up
+/// the flags of every file up front would be slow.
+pub fn locks(target: &Path) -> Vec<Locked> {
+    let parent = match target.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Some(Path::new(".")),
+        parent => parent,
+    };
+    std::iter::once(target)
+        .chain(parent)
+        .filter_map(|path| {
+            let flags = Flags::read(path).ok()?;
+            Some(Locked {
+                target: target.to_path_buf(),
+                path: path.to_path_buf(),
+                flag: flags.locking()?,
+                flags,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,203 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+/// A process that has a file open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Holder {
+    pub pid: u32,
+    pub name: String,
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.pid)
+    }
+}
+
+/// Finds the processes that have files open, so files in use can be left alone instead of
+/// failing (or, on Unix, being removed from under the process using them).
+#[derive(Debug, Default)]
+pub struct OpenFiles {
+    /// The open files of every process, by device and inode, and when they were collected.
+    #[cfg(target_os = "linux")]
+    snapshot: Option<(std::time::Instant, linux::Snapshot)>,
+}
+
+impl OpenFiles {
+    /// Returns the processes that have the file open. Only processes the user is allowed to
+    /// look at are found.
+    #[cfg(target_os = "linux")]
+    pub fn holders(&mut self, path: &Path) -> Result<Vec<Holder>, Box<dyn Error>> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::symlink_metadata(path)?;
+        let snapshot = match self.snapshot.take() {
+            Some((taken, snapshot)) if taken.elapsed() < linux::SNAPSHOT_AGE => snapshot,
+            _ => linux::snapshot()?,
+        };
+        let holders = snapshot
+            .get(&(metadata.dev(), metadata.ino()))
+            .cloned()
+            .unwrap_or_default();
+        self.snapshot = Some((std::time::Instant::now(), snapshot));
+
+        Ok(holders)
+    }
+
+    /// Returns the processes that have the file open, using the Restart Manager.
+    #[cfg(windows)]
+    pub fn holders(&mut self, path: &Path) -> Result<Vec<Holder>, Box<dyn Error>> {
+        restart_manager::holders(path)
+    }
+
+    /// Open files can't be found on this platform, so nothing is ever in use.
+    #[cfg(not(any(target_os = "linux", windows)))]
+    pub fn holders(&mut self, _path: &Path) -> Result<Vec<Holder>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+}
+
+/// On Linux, the open files of each process are the links in `/proc/<pid>/fd`. Going through
+/// every process for every file would be slow, so all of them are read at once and the result
+/// is reused for a short while.
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::Holder;
+    use std::collections::HashMap;
+    use std::error::Error;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::time::Duration;
+
+    /// How long the open files collected are used for before they are collected again.
+    pub const SNAPSHOT_AGE: Duration = Duration::from_secs(1);
+
+    /// The processes holding each open file, by device and inode.
+    pub type Snapshot = HashMap<(u64, u64), Vec<Holder>>;
+
+    /// Collects the open files of every process that can be looked at.
+    pub fn snapshot() -> Result<Snapshot, Box<dyn Error>> {
+        let own_pid = std::process::id();
+        let mut snapshot = Snapshot::new();
+
+        for entry in fs::read_dir("/proc")? {
+            let entry = entry?;
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            if pid == own_pid {
+                continue;
+            }
+
+            // Processes owned by other users can't be looked at, and processes may exit
+            let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+                continue;
+            };
+            let name = fs::read_to_string(entry.path().join("comm"))
+                .map(|comm| comm.trim_end().to_string())
+                .unwrap_or_default();
+
+            for fd in fds.flatten() {
+                // The link leads to the open file, even if it has been renamed
+                let Ok(metadata) = fs::metadata(fd.path()) else {
+                    continue;
+                };
+                let holders = snapshot
+                    .entry((metadata.dev(), metadata.ino()))
+                    .or_default();
+                if !holders.iter().any(|holder| holder.pid == pid) {
+                    holders.push(Holder {
+                        pid,
+                        name: name.clone(),
+                    });
+                }
+            } // for fd
+        } // for entry
+
+        Ok(snapshot)
+    }
+}
+
+/// On Windows, the Restart Manager knows which processes use a file.
+#[cfg(windows)]
+mod restart_manager {
+    use super::Holder;
+    use std::error::Error;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows::core::{PCWSTR, PWSTR};
+    use windows::Win32::Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS};
+    use windows::Win32::System::RestartManager::{
+        RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+        RM_PROCESS_INFO,
+    };
+
+    /// Returns the processes that have the file open.
+    pub fn holders(path: &Path) -> Result<Vec<Holder>, Box<dyn Error>> {
+        let mut session = 0;
+        let mut key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+        let err = unsafe { RmStartSession(&mut session, None, PWSTR(key.as_mut_ptr())) };
+        if err != ERROR_SUCCESS {
+            return Err(
+                format!("Unable to start a Restart Manager session: error {}", err.0).into(),
+            );
+        }
+
+        let result = list(session, path);
+        unsafe {
+            let _ = RmEndSession(session);
+        }
+        result
+    }
+
+    /// Asks the Restart Manager session which processes use the file.
+    fn list(session: u32, path: &Path) -> Result<Vec<Holder>, Box<dyn Error>> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let err =
+            unsafe { RmRegisterResources(session, Some(&[PCWSTR(wide.as_ptr())]), None, None) };
+        if err != ERROR_SUCCESS {
+            return Err(format!("Unable to register {}: error {}", path.display(), err.0).into());
+        }
+
+        // The list can grow between calls, so keep asking until it fits
+        let mut processes: Vec<RM_PROCESS_INFO> = Vec::new();
+        loop {
+            let mut needed = 0;
+            let mut count = processes.len() as u32;
+            let mut reasons = 0;
+            let buffer = (!processes.is_empty()).then(|| processes.as_mut_ptr());
+            let err = unsafe { RmGetList(session, &mut needed, &mut count, buffer, &mut reasons) };
+            if err == ERROR_SUCCESS {
+                processes.truncate(count as usize);
+                break;
+            } else if err == ERROR_MORE_DATA {
+                processes.resize(needed as usize, RM_PROCESS_INFO::default());
+            } else {
+                return Err(format!(
+                    "Unable to find the processes using {}: error {}",
+                    path.display(),
+                    err.0
+                )
+                .into());
+            }
+        } // loop
+
+        Ok(processes
+            .iter()
+            .map(|process| {
+                let name = &process.strAppName;
+                let length = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+                Holder {
+                    pid: process.Process.dwProcessId,
+                    name: String::from_utf16_lossy(&name[..length]),
+                }
+            })
+            .collect())
+    }
+}
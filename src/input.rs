@@ -0,0 +1,38 @@
+use std::error::Error;
+use std::io::{self, BufRead};
+
+/// The targets to process, produced one at a time so lists of any length can be handled.
+pub type Targets = Box<dyn Iterator<Item = Result<String, Box<dyn Error>>>>;
+
+/// Reads targets from standard input as they are needed, one per line or separated by NUL
+/// characters. Empty lines are ignored, as are paths that aren't valid UTF-8, with a warning.
+pub fn from_stdin(null_separated: bool) -> Targets {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+
+    Box::new(
+        io::stdin()
+            .lock()
+            .split(separator)
+            .filter_map(move |line| match line {
+                Ok(line) => {
+                    let line = match String::from_utf8(line) {
+                        Ok(line) => line,
+                        Err(err) => {
+                            log::warn!(
+                                "Skipping {}: not a valid UTF-8 path.",
+                                String::from_utf8_lossy(err.as_bytes())
+                            );
+                            return None;
+                        }
+                    };
+                    let line = if null_separated {
+                        line.as_str()
+                    } else {
+                        line.trim_end_matches('\r')
+                    };
+                    (!line.is_empty()).then(|| Ok(line.to_string()))
+                }
+                Err(err) => Some(Err(err.into())),
+            }),
+    )
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::{error::Error, path::Path};
+
+use crate::backend::{self, Entry, Removed};
+
+/// The version of the journal format written by `--journal`.
+pub const VERSION: u32 = 1;
+
+/// A line of the journal.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Record {
+    /// The first line, written when the run starts.
+    Started {
+        journal: u32,
+        started: String,
+        command_line: Vec<String>,
+    },
+
+    /// A file about to be removed, with its size.
+    Intent { intent: String, size: u64 },
+
+    /// A file that was removed, with what was done with it.
+    Done { done: String, outcome: String },
+
+    /// The last line, written when the run finishes.
+    Finished { finished: String },
+}
+
+/// A journal of the removals in a run, for `--journal`, so that after a crash or a power cut
+/// `rdel recover` can tell which files were removed and which weren't.
+///
+/// Each batch of files is written to the journal, and the journal flushed to the disk, before
+/// any of them is removed; the files removed are then marked as done, and flushed again once the
+/// batch is over.
+pub struct Journal {
+    writer: BufWriter<File>,
+}
+
+impl Journal {
+    /// Creates the journal, replacing it if it already exists, and writes its first line.
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(path)
+            .map_err(|err| format!("Unable to create journal {}: {err}", path.display()))?;
+        let mut journal = Self {
+            writer: BufWriter::new(file),
+        };
+        journal.write(&Record::Started {
+            journal: VERSION,
+            started: crate::timestamp::now_rfc3339(),
+            command_line: std::env::args_os()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+        })?;
+        journal.sync()?;
+
+        // The journal itself has to survive a crash too
+        #[cfg(unix)]
+        if let Some(dir) = path.parent() {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(journal)
+    }
+
+    /// Writes the entries about to be removed, and flushes them to the disk.
+    pub fn intend(&mut self, entries: &[Entry]) -> Result<(), Box<dyn Error>> {
+        for entry in entries {
+            self.write(&Record::Intent {
+                intent: entry.name.clone(),
+                size: entry.size,
+            })?;
+        } // for entry
+        self.sync()
+    }
+
+    /// Marks a file as removed. It's flushed to the disk with the next [`Journal::sync`].
+    pub fn done(&mut self, filename: &str, removed: &Removed) -> Result<(), Box<dyn Error>> {
+        let outcome = match removed {
+            Removed::Deleted => "deleted".to_string(),
+            Removed::Quarantined(target) => format!("quarantined to {}", target.display()),
+            Removed::Moved(target) => format!("moved to {}", target.display()),
+            Removed::Trashed(_) => "trashed".to_string(),
+            Removed::Shredded(method) => format!("shredded ({})", method.name()),
+            Removed::PendingRelease(target) => {
+                format!("deleted, pending release as {}", target.display())
+            }
+        };
+        self.write(&Record::Done {
+            done: filename.to_string(),
+            outcome,
+        })
+    }
+
+    /// Flushes what has been written to the disk.
+    pub fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    /// Writes the last line, showing the run was over, and flushes the journal to the disk.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.write(&Record::Finished {
+            finished: crate::timestamp::now_rfc3339(),
+        })?;
+        self.sync()
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// What became of a file in the journal, as far as can be told.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fate {
+    /// The file was marked as removed, with what was done with it.
+    Removed(String),
+
+    /// The file wasn't marked as removed, but it's gone: the run stopped between removing it and
+    /// marking it.
+    Gone,
+
+    /// The file is still there.
+    Remaining,
+
+    /// The file wasn't marked as removed, and it can't be looked up, e.g. as it's in S3.
+    Unknown,
+}
+
+/// A file in the journal, and what became of it.
+#[derive(Debug)]
+pub struct JournalEntry {
+    pub path: String,
+    pub size: u64,
+    pub fate: Fate,
+}
+
+/// What a journal says about the run that wrote it, for `rdel recover`.
+#[derive(Debug, Default)]
+pub struct Recovered {
+    /// When the run started, in RFC 3339 format, in local time or in UTC with `--utc`.
+    pub started: Option<String>,
+
+    /// When the run finished, if it did.
+    pub finished: Option<String>,
+
+    /// The command line of the run.
+    pub command_line: Vec<String>,
+
+    /// Each file the run was about to remove, in the order they were written.
+    pub entries: Vec<JournalEntry>,
+}
+
+/// Reads a journal written by `--journal`, and finds out what became of each file in it. Local
+/// files not marked as removed are looked up to see whether they are still there. A last line
+/// cut short, as a crash can leave it, is ignored.
+pub fn recover(path: &Path) -> Result<Recovered, Box<dyn Error>> {
+    let file = File::open(path)
+        .map_err(|err| format!("Unable to open journal {}: {err}", path.display()))?;
+    let lines: Vec<String> = BufReader::new(file).lines().collect::<Result<_, _>>()?;
+
+    let mut recovered = Recovered::default();
+    let mut removed = std::collections::HashMap::new();
+    for (number, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(err) if number + 1 == lines.len() => {
+                log::warn!(
+                    "Ignoring the last line of journal {}, which was cut short: {err}",
+                    path.display()
+                );
+                break;
+            }
+            Err(err) => {
+                return Err(format!(
+                    "Unable to read line {} of journal {}: {err}",
+                    number + 1,
+                    path.display()
+                )
+                .into())
+            }
+        };
+        match record {
+            Record::Started {
+                journal,
+                started,
+                command_line,
+            } => {
+                if journal > VERSION {
+                    return Err(format!(
+                        "Journal {} is version {journal}, but this rdel only reads up to version {VERSION}.",
+                        path.display()
+                    )
+                    .into());
+                }
+                recovered.started = Some(started);
+                recovered.command_line = command_line;
+            }
+            Record::Intent { intent, size } => recovered.entries.push(JournalEntry {
+                path: intent,
+                size,
+                fate: Fate::Unknown,
+            }),
+            Record::Done { done, outcome } => {
+                removed.insert(done, outcome);
+            }
+            Record::Finished { finished } => recovered.finished = Some(finished),
+        }
+    } // for line
+
+    for entry in &mut recovered.entries {
+        entry.fate = match removed.get(&entry.path) {
+            Some(outcome) => Fate::Removed(outcome.clone()),
+            None if backend::scheme_of(&entry.path) != "file" => Fate::Unknown,
+            None if fs::symlink_metadata(&entry.path).is_ok() => Fate::Remaining,
+            None => Fate::Gone,
+        };
+    } // for entry
+    Ok(recovered)
+}
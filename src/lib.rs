@@ -0,0 +1,73 @@
+//! The engine behind the rdel command line tool: finding files, filtering them and removing,
+//! trashing or quarantining them on the local file system or a remote backend.
+
+pub mod age;
+pub mod archive;
+pub mod audit;
+pub mod backend;
+#[cfg(target_os = "linux")]
+pub mod btrfs;
+pub mod builder;
+pub mod checksum;
+pub mod classify;
+pub mod clock;
+pub mod config;
+pub mod confirm;
+pub mod contains;
+pub mod danger;
+pub mod dedupe;
+pub mod deleter;
+#[cfg(target_os = "linux")]
+pub mod discard;
+pub mod expand;
+pub mod expr;
+#[cfg(target_os = "linux")]
+pub mod extents;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod find;
+pub mod git;
+pub mod hazard;
+pub mod hook;
+pub mod hours;
+pub mod i18n;
+#[cfg(unix)]
+pub mod immutable;
+pub mod in_use;
+pub mod input;
+pub mod journal;
+pub mod lists;
+pub mod manifest;
+pub mod metrics;
+pub mod mounts;
+pub mod name;
+pub mod normalize;
+pub mod notify;
+pub mod observer;
+pub mod order;
+pub mod paths;
+pub mod plan;
+pub mod plugin;
+#[cfg(unix)]
+pub mod progress;
+pub mod quarantine;
+pub mod recovery;
+pub mod report;
+pub mod script;
+pub mod security;
+pub mod shred;
+pub mod stream;
+#[cfg(unix)]
+pub mod sudo;
+pub mod timeout;
+pub mod timestamp;
+pub mod timings;
+pub mod transfer;
+pub mod trash;
+pub mod tui;
+pub mod utils;
+pub mod walker;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub mod xattr;
@@ -0,0 +1,72 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::deleter::Summary;
+use crate::observer::Observer;
+use crate::report::{FileReport, Outcome};
+
+/// Writes the paths of the files removed and of those left alone to separate lists as the run
+/// goes, for `--print-deleted` and `--print-skipped`, so pipelines can take either without
+/// picking through the log.
+pub struct OutcomeLists {
+    deleted: Option<Box<dyn Write + Send>>,
+    skipped: Option<Box<dyn Write + Send>>,
+    separator: u8,
+}
+
+impl OutcomeLists {
+    /// Opens the lists given. A list of `-` is written to standard output for the files
+    /// removed, and standard error for those left alone. The paths are separated by NUL
+    /// characters if `null_separated` is set, or else by newlines.
+    pub fn open(
+        deleted: Option<&Path>,
+        skipped: Option<&Path>,
+        null_separated: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let open = |path: &Path, standard: fn() -> Box<dyn Write + Send>| {
+            if path == Path::new("-") {
+                return Ok(standard());
+            }
+            File::create(path)
+                .map(|file| Box::new(BufWriter::new(file)) as Box<dyn Write + Send>)
+                .map_err(|err| format!("Unable to create {}: {err}", path.display()))
+        };
+
+        Ok(Self {
+            deleted: deleted
+                .map(|path| open(path, || Box::new(io::stdout())))
+                .transpose()?,
+            skipped: skipped
+                .map(|path| open(path, || Box::new(io::stderr())))
+                .transpose()?,
+            separator: if null_separated { b'\0' } else { b'\n' },
+        })
+    }
+}
+
+impl Observer for OutcomeLists {
+    fn on_file_done(&mut self, file: &FileReport) {
+        let list = match file.outcome {
+            Outcome::Removed | Outcome::WouldRemove => &mut self.deleted,
+            Outcome::Skipped | Outcome::Failed => &mut self.skipped,
+        };
+        if let Some(list) = list {
+            let written = list
+                .write_all(file.path.as_bytes())
+                .and_then(|()| list.write_all(&[self.separator]));
+            if let Err(err) = written {
+                log::warn!("Unable to list {}: {err}", file.path);
+            }
+        }
+    }
+
+    fn on_summary(&mut self, _summary: &Summary) {
+        for list in [&mut self.deleted, &mut self.skipped].into_iter().flatten() {
+            if let Err(err) = list.flush() {
+                log::warn!("Unable to write the list of files: {err}");
+            }
+        } // for list
+    }
+}
@@ -1,9 +1,14 @@
-use clap::parser::ValueSource;
-use std::fs;
+use clap::{parser::ValueSource, ArgMatches};
 use std::{error::Error, path::Path};
 
 mod cli;
-mod utils;
+
+use rdel::deleter::{Deleter, Settings};
+use rdel::journal::{self, Fate};
+use rdel::manifest::{Manifest, ManifestEntry, Verification};
+use rdel::plan::Plan;
+use rdel::trash::OnConflict;
+use rdel::{audit, dedupe, expand, git, i18n, input, normalize, plan, timestamp, trash, utils};
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// This is where the magic happens.
@@ -12,78 +17,348 @@ fn run() -> Result<(), Box<dyn Error>> {
     let cli_args = cli::build();
 
     // Set up logging
+    timestamp::init(
+        cli_args
+            .get_one::<String>("time-format")
+            .map(String::as_str),
+        cli_args.value_source("utc") == Some(ValueSource::CommandLine),
+    );
     let _logbuilder = utils::log_build(&cli_args);
+    i18n::init(cli_args.get_one::<String>("lang").map(String::as_str));
 
-    let files_to_delete = cli_args
-        .get_many::<String>("files")
-        .unwrap_or_default()
-        .map(std::string::String::as_str);
-    log::trace!("files_to_delete: {files_to_delete:?}");
+    // Restoring files, listing the history, reading journals and comparing plans don't remove
+    // anything
+    match cli_args.subcommand() {
+        Some(("restore", restore_args)) => return restore(&cli_args, restore_args),
+        Some(("history", history_args)) => return history(&cli_args, history_args),
+        Some(("recover", recover_args)) => return recover(recover_args),
+        Some(("diff", diff_args)) => return diff(diff_args),
+        _ => {}
+    }
 
-    let move_files = cli_args.value_source("move") == Some(ValueSource::CommandLine);
-    let stop_on_error = cli_args.value_source("stop") == Some(ValueSource::CommandLine);
-    let show_detail_info = cli_args.value_source("detail-off") != Some(ValueSource::CommandLine);
-    let dry_run = cli_args.value_source("dry-run") == Some(ValueSource::CommandLine);
-    let print_summary = cli_args.value_source("print-summary") == Some(ValueSource::CommandLine);
-    log::debug!("move_files: {move_files}, stop_on_error: {stop_on_error}, show_detail_info: {show_detail_info}, dry_run: {dry_run}, print-summary: {print_summary}");
+    let settings = Settings::from_args(&cli_args)?;
+    log::debug!("settings: {settings:?}");
 
-    if dry_run {
+    if settings.scan_only {
+        log::info!("Scan-only run starting.");
+    } else if settings.dry_run {
         log::info!("Dry-run starting.");
     }
 
-    let mut total_file_count: usize = 0;
-    let mut processed_file_count: usize = 0;
-    let mut skipped_file_count: usize = 0;
-    let mut total_file_size: u64 = 0;
+    let mut deleter = Deleter::new(settings)?;
 
-    // Delete files
+    // Finish even if processing stopped early, so the archive and report are complete
+    let result = process(&cli_args, &mut deleter);
+    deleter.finish()?;
+    result?;
 
-    for filename in files_to_delete {
-        total_file_count += 1;
+    // Files that weren't removed after all should get noticed by whoever runs this
+    let unverified = deleter.summary.unverified_file_count;
+    if unverified > 0 {
+        return Err(format!("{unverified} file(s) failed verification.").into());
+    }
+
+    // As with rm, the exit code shows whether everything could be removed
+    let failed = deleter.summary.skipped_file_count + deleter.summary.timed_out_file_count;
+    if deleter.settings.rm_compat && failed > 0 {
+        return Err(format!("{failed} file(s) could not be removed.").into());
+    }
+    Ok(())
+} // fn run()
 
-        let current_file_size = fs::metadata(Path::new(&filename))?.len();
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Processes the files given on the command line, or by the subcommand.
+fn process(cli_args: &ArgMatches, deleter: &mut Deleter) -> Result<(), Box<dyn Error>> {
+    match cli_args.subcommand() {
+        Some(("apply", apply_args)) => {
+            // Delete the files in the manifest, but only if they still match
+            let manifest_file = apply_args
+                .get_one::<String>("verify")
+                .ok_or("No manifest specified.")?;
+            let mut manifest = Manifest::load(Path::new(manifest_file))?;
+            log::trace!("manifest: {manifest:?}");
 
-        total_file_size += current_file_size;
+            for entry in &mut manifest.files {
+                entry.path = normalize::normalize(&entry.path, deleter.settings.normalize);
+                if deleter.traverses_parent(&entry.path) {
+                    continue;
+                }
+                match entry.verify()? {
+                    Verification::Matches { size, digest } => {
+                        deleter.remove(&entry.path, size, digest.as_deref())?;
+                    }
+                    Verification::Changed(reason) => deleter.skip_changed(&entry.path, &reason),
+                }
+            } // for entry
+        }
+        Some(("dedupe", dedupe_args)) => {
+            // Delete all but one copy of each duplicate file
+            let dir = dedupe_args
+                .get_one::<String>("dir")
+                .ok_or("No directory specified.")?;
+            let keep = dedupe::Keep::from_arg(
+                dedupe_args
+                    .get_one::<String>("keep")
+                    .map_or("oldest", String::as_str),
+            );
+            log::debug!("dedupe dir: {dir}, keep: {keep:?}");
 
-        if show_detail_info {
-            log::info!("Deleting: {filename} for {current_file_size} bytes.");
+            dedupe::dedupe(Path::new(dir), keep, deleter)?;
+        }
+        Some(("purge", purge_args)) => {
+            // Permanently remove quarantined or trashed files past the retention period
+            match purge_args.get_one::<String>("dir") {
+                Some(dir) => deleter.purge_quarantine(Path::new(dir))?,
+                None => deleter.purge_trash()?,
+            }
         }
+        Some(("delete" | "trash", files_args)) => process_files(files_args, deleter)?,
+        _ => process_files(cli_args, deleter)?,
+    }
 
-        if dry_run {
-            processed_file_count += 1;
+    // Everything is a-okay in the end
+    Ok(())
+} // fn process()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Deletes the files given, either on their own or to the `delete` and `trash` commands.
+fn process_files(files_args: &ArgMatches, deleter: &mut Deleter) -> Result<(), Box<dyn Error>> {
+    if !["files", "files-from", "git-ignored"]
+        .iter()
+        .any(|id| files_args.contains_id(id))
+    {
+        // rm -f is happy with nothing to do
+        if deleter.settings.ignore_missing {
+            return Ok(());
+        }
+        return Err("No files to delete were given.".into());
+    }
+
+    // Process the files in the plan, but only if they are still as expected
+    if let Some(plan_file) = files_args.get_one::<String>("files-from") {
+        let mut plan = Plan::load(Path::new(plan_file))?;
+        log::trace!("plan: {plan:?}");
+
+        for entry in &mut plan.files {
+            entry.path = normalize::normalize(
+                &expand::expand_path(&entry.path)?,
+                deleter.settings.normalize,
+            );
+            if deleter.traverses_parent(&entry.path) {
+                continue;
+            }
+            match entry.verify()? {
+                plan::Verification::Matches { size } => match entry.disposal() {
+                    Some(disposal) => deleter.dispose(&entry.path, size, disposal)?,
+                    None => deleter.remove(&entry.path, size, None)?,
+                },
+                plan::Verification::Changed(reason) => deleter.skip_changed(&entry.path, &reason),
+            }
+        } // for entry
+    }
+
+    // A target of - reads the targets from standard input, as they are needed
+    let null_separated = deleter.settings.null_separated;
+    let files: Vec<String> = files_args
+        .get_many::<String>("files")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    log::trace!("files: {files:?}");
+    let targets = files.into_iter().flat_map(move |file| -> input::Targets {
+        if file == "-" {
+            input::from_stdin(null_separated)
         } else {
-            match std::fs::remove_file(filename) {
-                Ok(_) => {
-                    processed_file_count += 1;
-                }
-                Err(err) => {
-                    if stop_on_error {
-                        return Err(format!(
-                            "Error: {err}. Unable to remove file {filename}. Halting.",
-                        )
-                        .into());
-                    }
-                    log::warn!("Unable to remove file {filename}. Continuing.");
-                    skipped_file_count += 1;
-                } // Err
-            } // match
+            Box::new(std::iter::once(Ok(file)))
         }
-    } // for filename
+    });
+
+    // Add the files ignored by git, if requested
+    let targets: input::Targets = match files_args.get_one::<String>("git-ignored") {
+        Some(repo) => Box::new(targets.chain(git::ignored_files(Path::new(repo))?)),
+        None => Box::new(targets),
+    };
+
+    // Delete files, wherever they are
+    deleter.run(targets)
+} // fn process_files()
 
-    // Print summary information
-    if print_summary {
-        log::info!("Total files examined:        {total_file_count:5}");
-        log::info!("Files removed:               {processed_file_count:5}");
-        log::info!("Files skipped due to errors: {skipped_file_count:5}");
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Moves the files matching the patterns back out of the trash.
+fn restore(cli_args: &ArgMatches, restore_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let patterns = patterns(restore_args)?;
+    let dry_run = cli_args.value_source("dry-run") == Some(ValueSource::CommandLine);
+
+    let on_conflict = restore_args
+        .get_one::<String>("on-conflict")
+        .map(|value| OnConflict::from_arg(value));
+
+    let restored = trash::restore(&patterns, on_conflict, dry_run)?;
+    log::info!(
+        "{:<29}{:5}",
+        i18n::t("Files restored:"),
+        restored.file_count
+    );
+    if restored.skipped_count > 0 {
         log::info!(
-            "Bytes freed:                 {:>}",
-            thousand_separated(total_file_size)
+            "{:<29}{:5}",
+            i18n::t("Files skipped, path taken:"),
+            restored.skipped_count
         );
     }
+    if restored.failed_count > 0 {
+        return Err(format!("{} file(s) could not be restored.", restored.failed_count).into());
+    }
+    Ok(())
+} // fn restore()
 
-    // Everything is a-okay in the end
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Lists the files removed by earlier runs, from the audit log.
+fn history(cli_args: &ArgMatches, history_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let audit_log = cli_args
+        .get_one::<String>("audit-log")
+        .ok_or("The audit log to read must be given with --audit-log.")?;
+
+    audit::history(
+        Path::new(audit_log),
+        &patterns(history_args)?,
+        history_args.get_one::<usize>("last").copied(),
+    )
+} // fn history()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Tells which of the files in a journal written with `--journal` were removed, e.g. after the
+/// run was cut short by a crash.
+fn recover(recover_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let journal_file = recover_args
+        .get_one::<String>("journal-file")
+        .ok_or("The journal to read must be given.")?;
+    let recovered = journal::recover(Path::new(journal_file))?;
+
+    let (mut removed, mut remaining, mut unknown) = (0, 0, 0);
+    for entry in &recovered.entries {
+        let fate = match &entry.fate {
+            Fate::Removed(outcome) => {
+                removed += 1;
+                outcome.clone()
+            }
+            Fate::Gone => {
+                removed += 1;
+                "removed, not marked".to_string()
+            }
+            Fate::Remaining => {
+                remaining += 1;
+                "not removed".to_string()
+            }
+            Fate::Unknown => {
+                unknown += 1;
+                "unknown".to_string()
+            }
+        };
+        log::info!(
+            "{:>15}  {}  {fate}",
+            utils::thousand_separated(entry.size),
+            entry.path
+        );
+    } // for entry
+
+    match (&recovered.started, &recovered.finished) {
+        (_, Some(finished)) => log::info!(
+            "{}",
+            i18n::tf(
+                "The run finished at {finished}.",
+                &[("finished", &timestamp::reformat(finished))]
+            )
+        ),
+        (Some(started), None) => log::warn!(
+            "{}",
+            i18n::tf(
+                "The run started at {started} didn't finish.",
+                &[("started", &timestamp::reformat(started))]
+            )
+        ),
+        (None, None) => {}
+    }
+    log::info!(
+        "{}",
+        i18n::tf(
+            "{removed} files removed, {remaining} not removed, {unknown} unknown.",
+            &[
+                ("removed", &removed),
+                ("remaining", &remaining),
+                ("unknown", &unknown),
+            ]
+        )
+    );
     Ok(())
-} // fn run()
+} // fn recover()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Shows how the files listed in two manifests differ, e.g. plans saved by `--save-plan` before
+/// and after a filter was tightened.
+fn diff(diff_args: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let load = |id: &str| {
+        let path = diff_args
+            .get_one::<String>(id)
+            .ok_or("Two manifests must be given.")?;
+        Manifest::load(Path::new(path))
+    };
+    let (old, new) = (load("old")?, load("new")?);
+    let diff = old.diff(&new);
+
+    for entry in &diff.added {
+        log::info!(
+            "+ {:>15}  {}",
+            utils::thousand_separated(entry.size),
+            entry.path
+        );
+    } // for entry
+    for entry in &diff.dropped {
+        log::info!(
+            "- {:>15}  {}",
+            utils::thousand_separated(entry.size),
+            entry.path
+        );
+    } // for entry
+    for (entry, old_size) in &diff.resized {
+        log::info!(
+            "~ {:>15}  {}  ({} bytes before)",
+            utils::thousand_separated(entry.size),
+            entry.path,
+            utils::thousand_separated(*old_size)
+        );
+    } // for entry
+
+    let total = |entries: &[&ManifestEntry]| {
+        utils::thousand_separated(entries.iter().map(|entry| entry.size).sum::<u64>())
+    };
+    log::info!(
+        "{}",
+        i18n::tf(
+            "{added} files added ({added_size} bytes), {dropped} dropped ({dropped_size} bytes), {resized} changed size.",
+            &[
+                ("added", &diff.added.len()),
+                ("added_size", &total(&diff.added)),
+                ("dropped", &diff.dropped.len()),
+                ("dropped_size", &total(&diff.dropped)),
+                ("resized", &diff.resized.len()),
+            ]
+        )
+    );
+    Ok(())
+} // fn diff()
+
+//////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Parses the glob patterns given to a command.
+fn patterns(args: &ArgMatches) -> Result<Vec<glob::Pattern>, Box<dyn Error>> {
+    args.get_many::<String>("patterns")
+        .unwrap_or_default()
+        .map(|pattern| {
+            glob::Pattern::new(pattern)
+                .map_err(|err| format!("{pattern} is not a valid pattern: {err}").into())
+        })
+        .collect()
+} // fn patterns()
 
 //////////////////////////////////////////////////////////////////////////////////////////////////////////////
 /// The actual executable function that gets called when the program in invoked.
@@ -96,25 +371,3 @@ fn main() {
         }
     });
 }
-
-/// Pretty-prints integer values;
-/// Examples:
-///
-/// ```
-/// assert_eq!(thousand_separated(10000), "10,000".to_string());
-/// assert_eq!(thousand_separated(10000000), "10,000,000".to_string());
-/// ```
-pub fn thousand_separated<T>(val: T) -> String
-where
-    T: std::fmt::Display,
-{
-    let s = val.to_string();
-    let bytes: Vec<_> = s.bytes().rev().collect();
-    let chunks: Vec<_> = bytes
-        .chunks(3)
-        .map(|chunk| std::str::from_utf8(chunk).unwrap_or("0"))
-        .collect();
-    let result: Vec<_> = chunks.join(",").bytes().rev().collect();
-    let default = String::from("NaN");
-    String::from_utf8(result).unwrap_or(default)
-}
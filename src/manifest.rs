@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{error::Error, path::Path};
+
+use crate::checksum;
+
+/// The version of the manifest format written by `--save-plan`. Manifests without a version
+/// are taken to be this one.
+pub const VERSION: u32 = 1;
+
+/// A list of files along with the attributes they are expected to have when deleted.
+///
+/// Written by a dry-run with `--save-plan`, for `apply` and for tools that review or approve
+/// the plan before it's carried out, or put together by hand or by other tools.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The version of the format, so tools can tell what to expect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+
+    /// When the plan was made, in RFC 3339 format (UTC).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
+
+    /// The command line of the dry-run that made the plan.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command_line: Vec<String>,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// A single file in the manifest.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The path of the file to delete.
+    pub path: String,
+
+    /// The expected size of the file in bytes.
+    pub size: u64,
+
+    /// The expected SHA-256 digest of the file contents, as a hex string, if it should be
+    /// checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+
+    /// The expected modification time of the file in RFC 3339 format, if it should be checked.
+    /// Times are compared to the second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<String>,
+
+    /// Why the file was picked, e.g. the `--name` pattern it matched. Only for the reader.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched: Option<String>,
+}
+
+/// How the files listed differ between two manifests, e.g. plans saved before and after a
+/// filter was tightened. Each list is sorted by path.
+#[derive(Debug, Default)]
+pub struct ManifestDiff<'a> {
+    /// The files only in the newer manifest.
+    pub added: Vec<&'a ManifestEntry>,
+
+    /// The files only in the older manifest.
+    pub dropped: Vec<&'a ManifestEntry>,
+
+    /// The files in both whose size differs, as listed in the newer manifest, with the size
+    /// in the older one.
+    pub resized: Vec<(&'a ManifestEntry, u64)>,
+}
+
+/// The outcome of comparing a file on disk with its manifest entry.
+#[derive(Debug)]
+pub enum Verification {
+    /// The file still matches the manifest. Contains the size and, if it was checked, the
+    /// digest found.
+    Matches { size: u64, digest: Option<String> },
+
+    /// The file has changed since the manifest was generated. Contains the reason.
+    Changed(String),
+}
+
+impl Manifest {
+    /// Starts an empty manifest of the current version, for a plan made now.
+    pub fn new() -> Self {
+        Self {
+            version: Some(VERSION),
+            generated: Some(humantime::format_rfc3339_seconds(SystemTime::now()).to_string()),
+            command_line: std::env::args_os()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Reads a manifest from a JSON file.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let manifest: Self = serde_json::from_reader(reader)
+            .map_err(|err| format!("Unable to read manifest {}: {err}", path.display()))?;
+        if let Some(version) = manifest.version.filter(|&version| version > VERSION) {
+            return Err(format!(
+                "Manifest {} is version {version}, but this rdel only reads up to version {VERSION}.",
+                path.display()
+            )
+            .into());
+        }
+        for entry in &manifest.files {
+            if let Some(mtime) = &entry.mtime {
+                humantime::parse_rfc3339_weak(mtime).map_err(|err| {
+                    format!(
+                        "Invalid manifest {}: mtime {mtime} of {}: {err}",
+                        path.display(),
+                        entry.path
+                    )
+                })?;
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Compares the files listed with those in a `newer` manifest, by path.
+    pub fn diff<'a>(&'a self, newer: &'a Self) -> ManifestDiff<'a> {
+        let by_path = |manifest: &'a Self| -> BTreeMap<&'a str, &'a ManifestEntry> {
+            manifest
+                .files
+                .iter()
+                .map(|entry| (entry.path.as_str(), entry))
+                .collect()
+        };
+        let (old, new) = (by_path(self), by_path(newer));
+
+        let mut diff = ManifestDiff::default();
+        for (path, entry) in &new {
+            match old.get(path) {
+                None => diff.added.push(entry),
+                Some(before) if before.size != entry.size => {
+                    diff.resized.push((entry, before.size));
+                }
+                Some(_) => {}
+            }
+        } // for path
+        diff.dropped = old
+            .iter()
+            .filter(|(path, _)| !new.contains_key(*path))
+            .map(|(_, entry)| *entry)
+            .collect();
+        diff
+    }
+
+    /// Writes the manifest as JSON to `path`.
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = File::create(path)
+            .map_err(|err| format!("Unable to create plan {}: {err}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+impl ManifestEntry {
+    /// Checks whether the file on disk still has the expected size, and the modification time
+    /// and digest where given. The digest is only calculated if everything else matches.
+    pub fn verify(&self) -> Result<Verification, Box<dyn Error>> {
+        let path = Path::new(&self.path);
+
+        // A symbolic link is removed itself, so it's the link that has to match
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Ok(Verification::Changed("File no longer exists.".to_string()));
+            }
+            Err(err) => return Err(format!("Unable to look up {}: {err}", self.path).into()),
+        };
+
+        if metadata.len() != self.size {
+            return Ok(Verification::Changed(format!(
+                "Size is {} bytes, expected {} bytes.",
+                metadata.len(),
+                self.size
+            )));
+        }
+
+        if let Some(mtime) = &self.mtime {
+            let expected = humantime::parse_rfc3339_weak(mtime)?;
+            let modified = metadata.modified()?;
+            let seconds = |time: SystemTime| {
+                time.duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs())
+            };
+            if seconds(modified) != seconds(expected) {
+                return Ok(Verification::Changed(format!(
+                    "Modified at {}, expected {mtime}.",
+                    humantime::format_rfc3339_seconds(modified)
+                )));
+            }
+        }
+
+        let Some(expected) = &self.sha256 else {
+            return Ok(Verification::Matches {
+                size: metadata.len(),
+                digest: None,
+            });
+        };
+        if !metadata.is_file() {
+            return Ok(Verification::Changed(
+                "No longer a regular file, so its SHA-256 can't be checked.".to_string(),
+            ));
+        }
+        let digest = checksum::sha256(path)?;
+        if !digest.eq_ignore_ascii_case(expected) {
+            return Ok(Verification::Changed(format!(
+                "SHA-256 is {digest}, expected {expected}."
+            )));
+        }
+
+        Ok(Verification::Matches {
+            size: metadata.len(),
+            digest: Some(digest),
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Makes a manifest entry for `path`, expecting `size` bytes and maybe a digest.
+    fn entry(path: &Path, size: u64, sha256: Option<&str>) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_str().unwrap().to_string(),
+            size,
+            sha256: sha256.map(str::to_string),
+            mtime: None,
+            matched: None,
+        }
+    }
+
+    #[test]
+    fn symlinks_checked_themselves() {
+        let dir = std::env::temp_dir().join(format!("rdel-manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (file, link) = (dir.join("file"), dir.join("link"));
+        fs::write(&file, b"hello").unwrap();
+        std::os::unix::fs::symlink("file", &link).unwrap();
+        let digest = checksum::sha256(&file).unwrap();
+
+        let file_matches = entry(&file, 5, Some(&digest)).verify().unwrap();
+        // The link is four bytes long, the length of what it points to
+        let link_size = entry(&link, 5, None).verify().unwrap();
+        let link_matches = entry(&link, 4, None).verify().unwrap();
+        let link_digest = entry(&link, 4, Some(&digest)).verify().unwrap();
+        let missing = entry(&dir.join("missing"), 5, None).verify().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            file_matches,
+            Verification::Matches { size: 5, .. }
+        ));
+        assert!(matches!(link_size, Verification::Changed(_)));
+        assert!(matches!(
+            link_matches,
+            Verification::Matches { size: 4, .. }
+        ));
+        assert!(
+            matches!(link_digest, Verification::Changed(reason) if reason.contains("regular file"))
+        );
+        assert!(
+            matches!(missing, Verification::Changed(reason) if reason == "File no longer exists.")
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use std::error::Error;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use crate::deleter::Summary;
+
+/// Sends the run's metrics to a StatsD server over UDP, in a single packet:
+/// files deleted, bytes freed and errors as counters, and the run duration as a timer.
+pub fn send_statsd(
+    address: &str,
+    prefix: &str,
+    summary: &Summary,
+    duration: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let packet = [
+        format!("{prefix}.files_deleted:{}|c", summary.processed_file_count),
+        format!("{prefix}.bytes_freed:{}|c", summary.freed_size()),
+        format!("{prefix}.errors:{}|c", summary.skipped_file_count),
+        format!("{prefix}.timeouts:{}|c", summary.timed_out_file_count),
+        format!("{prefix}.duration:{}|ms", duration.as_millis()),
+    ]
+    .join("\n");
+
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.send_to(packet.as_bytes(), address)?;
+
+    log::debug!("Sent metrics to {address}: {packet:?}");
+    Ok(())
+}
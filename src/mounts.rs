@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+
+/// Returns the mount points at or below `dir`, such as a backup volume mounted inside a cache
+/// directory, so a recursive removal doesn't wander onto another file system unawares. On Linux
+/// they are read from `/proc/self/mountinfo`; on other Unix systems only `dir` itself is checked,
+/// by whether it's on a different device than the directory it's in.
+pub fn mounts_in(dir: &Path) -> Vec<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        mount_table()
+            .iter()
+            .map(|filesystem| &filesystem.mount)
+            .filter(|mount| mount.starts_with(dir))
+            .cloned()
+            .collect()
+    }
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let device = |path: &Path| std::fs::metadata(path).ok().map(|metadata| metadata.dev());
+        match dir.parent() {
+            Some(parent) if device(dir).is_some() && device(dir) != device(parent) => {
+                vec![dir.to_path_buf()]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        Vec::new()
+    }
+}
+
+/// A mounted file system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filesystem {
+    /// Where it's mounted.
+    pub mount: PathBuf,
+
+    /// Its type, as the kernel names it, e.g. `ext4`, `nfs4` or `fuse.sshfs`.
+    pub fs_type: String,
+}
+
+impl Filesystem {
+    /// Tells whether the file system is a network one (NFS, SMB, FUSE and the like), where every
+    /// call is a round trip to a server that may be slow or go away for a while.
+    pub fn is_network(&self) -> bool {
+        const NETWORK: &[&str] = &[
+            "nfs",
+            "nfs4",
+            "cifs",
+            "smb3",
+            "smbfs",
+            "fuse",
+            "9p",
+            "afs",
+            "ceph",
+            "glusterfs",
+        ];
+        NETWORK.contains(&self.fs_type.as_str()) || self.fs_type.starts_with("fuse.")
+    }
+
+    /// Tells whether the file system is NFS, where files deleted while open are renamed to
+    /// `.nfsXXXX` rather than removed.
+    pub fn is_nfs(&self) -> bool {
+        matches!(self.fs_type.as_str(), "nfs" | "nfs4")
+    }
+}
+
+/// Returns the file system a file is on, i.e. the innermost mount holding it. Only Linux has a
+/// mount table to look in; elsewhere nothing is returned.
+pub fn filesystem_of(path: &Path) -> Option<&'static Filesystem> {
+    #[cfg(target_os = "linux")]
+    {
+        let path = std::path::absolute(path).ok()?;
+        mount_table()
+            .iter()
+            .filter(|filesystem| path.starts_with(&filesystem.mount))
+            .max_by_key(|filesystem| filesystem.mount.as_os_str().len())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Returns the file systems mounted, from `/proc/self/mountinfo`. The mount table is only read
+/// once, as there may be many files to check.
+#[cfg(target_os = "linux")]
+fn mount_table() -> &'static [Filesystem] {
+    static MOUNTS: std::sync::OnceLock<Vec<Filesystem>> = std::sync::OnceLock::new();
+    MOUNTS.get_or_init(|| {
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").unwrap_or_default();
+        // The mount point is the fifth field: ID, parent ID, device, root, mount point, ...
+        // The type is the first field after the optional ones, which end with a lone `-`
+        mountinfo
+            .lines()
+            .filter_map(|line| {
+                let mount = line.split(' ').nth(4)?;
+                let (_, rest) = line.split_once(" - ")?;
+                let fs_type = rest.split(' ').next()?;
+                Some(Filesystem {
+                    mount: PathBuf::from(unescape(mount)),
+                    fs_type: unescape(fs_type),
+                })
+            })
+            .collect()
+    })
+}
+
+/// Decodes the octal escapes (e.g. `\040` for a space) the kernel uses in `mountinfo`.
+#[cfg(target_os = "linux")]
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 4;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    } // while i
+    String::from_utf8_lossy(&decoded).into_owned()
+}
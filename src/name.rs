@@ -0,0 +1,53 @@
+use glob::{MatchOptions, Pattern};
+use std::error::Error;
+
+use crate::normalize::{self, Normalization};
+
+/// Selects files based on their names.
+#[derive(Debug)]
+pub struct NameFilter {
+    patterns: Vec<Pattern>,
+    options: MatchOptions,
+    normalization: Option<Normalization>,
+}
+
+impl NameFilter {
+    /// Creates a filter from one or more glob patterns. A name matches if it matches any of them.
+    pub fn new(
+        patterns: &[&str],
+        ignore_case: bool,
+        normalization: Option<Normalization>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                Pattern::new(&normalize::normalize(pattern, normalization))
+                    .map_err(|err| format!("Invalid --name pattern {pattern}: {err}"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            patterns,
+            options: match_options(ignore_case),
+            normalization,
+        })
+    }
+
+    /// Returns true if the last component of `path` matches one of the patterns.
+    pub fn matches(&self, path: &str) -> bool {
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        let file_name = normalize::normalize(file_name, self.normalization);
+
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_with(&file_name, self.options))
+    }
+}
+
+/// The glob options used for all name matching.
+pub fn match_options(ignore_case: bool) -> MatchOptions {
+    MatchOptions {
+        case_sensitive: !ignore_case,
+        ..MatchOptions::new()
+    }
+}
@@ -0,0 +1,38 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// The Unicode normalization form to convert file names to before matching them.
+///
+/// The same visible name can be stored composed (NFC, usual on Linux and Windows) or decomposed
+/// (NFD, usual on macOS), so names from a list generated on another system may not match the
+/// names on disk unless both are converted to the same form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    Nfc,
+    Nfd,
+}
+
+impl Normalization {
+    /// Converts the command line value into a `Normalization`. Unknown values use NFC.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "nfd" => Self::Nfd,
+            _ => Self::Nfc,
+        }
+    }
+
+    /// Returns `text` converted to this normalization form.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Self::Nfc => text.nfc().collect(),
+            Self::Nfd => text.nfd().collect(),
+        }
+    }
+}
+
+/// Converts `text` to the normalization form, if one is given.
+pub fn normalize(text: &str, normalization: Option<Normalization>) -> String {
+    match normalization {
+        Some(normalization) => normalization.apply(text),
+        None => text.to_string(),
+    }
+}
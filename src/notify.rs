@@ -0,0 +1,68 @@
+use serde::Serialize;
+use std::error::Error;
+
+use crate::deleter::Summary;
+use crate::utils;
+
+/// A file that couldn't be removed, and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct Failure {
+    pub path: String,
+    pub reason: String,
+}
+
+/// POSTs the summary as JSON to `url` when the run finishes. The failures are included if given.
+pub fn send(
+    url: &str,
+    dry_run: bool,
+    summary: &Summary,
+    failures: Option<&[Failure]>,
+) -> Result<(), Box<dyn Error>> {
+    #[derive(Serialize)]
+    struct Notification<'a> {
+        dry_run: bool,
+        summary: &'a Summary,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        failures: Option<&'a [Failure]>,
+    }
+
+    let body = serde_json::to_string(&Notification {
+        dry_run,
+        summary,
+        failures,
+    })?;
+
+    ureq::post(url)
+        .header("Content-Type", "application/json")
+        .send(body.as_bytes())?;
+
+    log::debug!("Sent notification to {url}");
+    Ok(())
+}
+
+/// Raises a desktop notification with the summary when the run finishes.
+pub fn desktop(dry_run: bool, summary: &Summary) -> Result<(), Box<dyn Error>> {
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    let mut body = format!(
+        "{verb} {} of {} files, {} bytes.",
+        summary.processed_file_count,
+        summary.total_file_count,
+        utils::thousand_separated(summary.freed_size())
+    );
+    if summary.skipped_file_count > 0 {
+        body.push_str(&format!(
+            " {} skipped due to errors.",
+            summary.skipped_file_count
+        ));
+    }
+    if summary.timed_out_file_count > 0 {
+        body.push_str(&format!(" {} timed out.", summary.timed_out_file_count));
+    }
+
+    notify_rust::Notification::new()
+        .summary("rdel finished")
+        .body(&body)
+        .show()?;
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use std::error::Error;
+
+use crate::deleter::Summary;
+use crate::report::FileReport;
+
+/// Follows a run as it goes, e.g. so a GUI wrapper can show its own progress without parsing
+/// the log. Every method does nothing unless it's overridden.
+pub trait Observer: Send {
+    /// The files in a chunk of targets have been found, before any of them is removed. There
+    /// may be more chunks to come.
+    fn on_found(&mut self, _count: usize, _size: u64) {}
+
+    /// A file is about to be removed, or would be in a dry-run.
+    fn on_file_start(&mut self, _path: &str, _size: u64) {}
+
+    /// A file has been dealt with, whether it was removed, skipped or couldn't be removed.
+    fn on_file_done(&mut self, _file: &FileReport) {}
+
+    /// A file couldn't be removed. `on_file_done` follows.
+    fn on_error(&mut self, _path: &str, _err: &(dyn Error + 'static)) {}
+
+    /// The run has finished, with these totals.
+    fn on_summary(&mut self, _summary: &Summary) {}
+}
+
+impl<O: Observer + ?Sized> Observer for Box<O> {
+    fn on_found(&mut self, count: usize, size: u64) {
+        (**self).on_found(count, size);
+    }
+
+    fn on_file_start(&mut self, path: &str, size: u64) {
+        (**self).on_file_start(path, size);
+    }
+
+    fn on_file_done(&mut self, file: &FileReport) {
+        (**self).on_file_done(file);
+    }
+
+    fn on_error(&mut self, path: &str, err: &(dyn Error + 'static)) {
+        (**self).on_error(path, err);
+    }
+
+    fn on_summary(&mut self, summary: &Summary) {
+        (**self).on_summary(summary);
+    }
+}
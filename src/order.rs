@@ -0,0 +1,66 @@
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+/// The order files are processed in, for `--order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// By path.
+    Path,
+
+    /// The largest files first.
+    SizeDesc,
+
+    /// The smallest files first.
+    SizeAsc,
+
+    /// The least recently modified files first.
+    OldestFirst,
+
+    /// The most recently modified files first.
+    NewestFirst,
+}
+
+/// What files are ordered by.
+#[derive(Debug, Clone, Copy)]
+pub struct Key<'a> {
+    pub path: &'a str,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Order {
+    /// Converts the command line value into an `Order`. Unknown values order by path.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "size-desc" => Self::SizeDesc,
+            "size-asc" => Self::SizeAsc,
+            "oldest-first" => Self::OldestFirst,
+            "newest-first" => Self::NewestFirst,
+            _ => Self::Path,
+        }
+    }
+
+    /// Whether the modification times of the files are needed to order them.
+    pub fn uses_time(self) -> bool {
+        matches!(self, Self::OldestFirst | Self::NewestFirst)
+    }
+
+    /// Compares two files. Files without a modification time come after those with one, and
+    /// files that are otherwise equal are ordered by path, so the order is always the same.
+    pub fn compare(self, a: &Key, b: &Key) -> Ordering {
+        let ordering = match self {
+            Self::Path => Ordering::Equal,
+            Self::SizeDesc => b.size.cmp(&a.size),
+            Self::SizeAsc => a.size.cmp(&b.size),
+            Self::OldestFirst | Self::NewestFirst => match (a.modified, b.modified) {
+                (Some(a_time), Some(b_time)) if self == Self::OldestFirst => a_time.cmp(&b_time),
+                (Some(a_time), Some(b_time)) => b_time.cmp(&a_time),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+
+        ordering.then_with(|| a.path.cmp(b.path))
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::backend;
+
+/// Which form the paths of local files are shown in, in the log messages and the report, for
+/// `--resolve-paths`. The files are removed the same way whatever the form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolve {
+    /// As supplied, once `~` and environment variables are expanded, e.g. `logs/old.log`.
+    Given,
+
+    /// Made absolute from the current directory, leaving symbolic links alone.
+    Absolute,
+
+    /// Made absolute with the symbolic links in the directories resolved.
+    Canonical,
+}
+
+impl Resolve {
+    /// Converts the command line argument into a form.
+    pub fn from_arg(arg: &str) -> Self {
+        match arg {
+            "given" => Self::Given,
+            "absolute" => Self::Absolute,
+            _ => Self::Canonical,
+        }
+    }
+}
+
+/// Returns a local path, as it was supplied, in the form asked for.
+pub fn resolve(given: &str, resolve: Resolve) -> String {
+    match resolve {
+        Resolve::Given => given.to_string(),
+        Resolve::Absolute => std::path::absolute(Path::new(given))
+            .ok()
+            .and_then(|path| path.to_str().map(str::to_string))
+            .unwrap_or_else(|| given.to_string()),
+        Resolve::Canonical => backend::canonical(given),
+    }
+}
+
+/// Returns a local path relative to `base`, for `--relative-to`, climbing out of it with `..`
+/// where needed, e.g. `../b/x` for `/data/b/x` relative to `/data/a`. `base` is absolute; a
+/// relative `path` is taken to be relative to the current directory. Paths on another drive
+/// than `base` (on Windows) are left absolute.
+pub fn relative(path: &str, base: &Path) -> String {
+    let Ok(absolute) = std::path::absolute(Path::new(path)) else {
+        return path.to_string();
+    };
+    let mut path_components = absolute.components().peekable();
+    let mut base_components = base.components().peekable();
+    if path_components.peek() != base_components.peek() {
+        return absolute
+            .to_str()
+            .map_or_else(|| path.to_string(), str::to_string);
+    }
+    while path_components.peek().is_some() && path_components.peek() == base_components.peek() {
+        path_components.next();
+        base_components.next();
+    } // while
+
+    let relative: PathBuf = base_components
+        .map(|_| Component::ParentDir)
+        .chain(path_components)
+        .collect();
+    match relative.to_str() {
+        Some("") => ".".to_string(),
+        Some(relative) => relative.to_string(),
+        None => path.to_string(),
+    }
+}
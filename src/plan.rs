@@ -0,0 +1,366 @@
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use std::{error::Error, path::Path};
+
+use crate::backend::Disposal;
+
+/// A list of files handed to rdel by another tool, with what to do with each of them.
+#[derive(Debug)]
+pub struct Plan {
+    pub files: Vec<PlanEntry>,
+}
+
+/// The layouts accepted for a plan: a bare list of entries, or an object with a `files` list
+/// like the manifest used by `apply`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PlanFile {
+    List(Vec<PlanEntry>),
+    Object { files: Vec<PlanEntry> },
+}
+
+/// What to do with a file in the plan.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Delete the file.
+    #[default]
+    Delete,
+
+    /// Move the file to the trash.
+    Trash,
+
+    /// Move the file into the directory given by `to`.
+    Move,
+}
+
+/// A single file in the plan.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanEntry {
+    /// The path of the file.
+    pub path: String,
+
+    /// The expected size of the file in bytes, if it should be checked.
+    #[serde(default)]
+    pub size: Option<u64>,
+
+    /// The expected modification time of the file in RFC 3339 format, if it should be checked.
+    #[serde(default)]
+    pub mtime: Option<String>,
+
+    /// What to do with the file. Default: delete.
+    #[serde(default)]
+    pub action: Action,
+
+    /// The directory to move the file into, for the move action.
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+/// The outcome of comparing a file on disk with its plan entry.
+#[derive(Debug)]
+pub enum Verification {
+    /// The file still has the expected attributes. Contains the size found.
+    Matches { size: u64 },
+
+    /// The file has changed since the plan was made. Contains the reason.
+    Changed(String),
+}
+
+impl Plan {
+    /// Reads a plan from a JSON file, or a CSV file if its name ends in `.csv`, checking that
+    /// each entry is complete.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        let files = if is_csv {
+            parse_csv(&fs::read_to_string(path)?)
+                .map_err(|err| format!("Unable to read plan {}: {err}", path.display()))?
+        } else {
+            let reader = BufReader::new(File::open(path)?);
+            match serde_json::from_reader(reader)
+                .map_err(|err| format!("Unable to read plan {}: {err}", path.display()))?
+            {
+                PlanFile::List(files) | PlanFile::Object { files } => files,
+            }
+        };
+
+        for entry in &files {
+            if entry.action == Action::Move && entry.to.is_none() {
+                return Err(format!(
+                    "Invalid plan {}: {} is to be moved, but has no \"to\" directory.",
+                    path.display(),
+                    entry.path
+                )
+                .into());
+            }
+            if let Some(mtime) = &entry.mtime {
+                humantime::parse_rfc3339_weak(mtime).map_err(|err| {
+                    format!(
+                        "Invalid plan {}: mtime {mtime} of {}: {err}",
+                        path.display(),
+                        entry.path
+                    )
+                })?;
+            }
+        }
+
+        Ok(Self { files })
+    }
+}
+
+impl PlanEntry {
+    /// Checks whether the file on disk still has the expected size and modification time,
+    /// where given. Times are compared to the second.
+    pub fn verify(&self) -> Result<Verification, Box<dyn Error>> {
+        let Ok(metadata) = fs::symlink_metadata(&self.path) else {
+            return Ok(Verification::Changed("File no longer exists.".to_string()));
+        };
+
+        if let Some(size) = self.size {
+            if metadata.len() != size {
+                return Ok(Verification::Changed(format!(
+                    "Changed since listed: the size is {} bytes, expected {size} bytes.",
+                    metadata.len()
+                )));
+            }
+        }
+
+        if let Some(mtime) = &self.mtime {
+            let expected = humantime::parse_rfc3339_weak(mtime)?;
+            let modified = metadata.modified()?;
+            let seconds = |time: std::time::SystemTime| {
+                time.duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs())
+            };
+            if seconds(modified) != seconds(expected) {
+                return Ok(Verification::Changed(format!(
+                    "Changed since listed: modified at {}, expected {mtime}.",
+                    humantime::format_rfc3339_seconds(modified)
+                )));
+            }
+        }
+
+        Ok(Verification::Matches {
+            size: metadata.len(),
+        })
+    }
+
+    /// Returns how the file is to be disposed of, or `None` if it is simply deleted.
+    pub fn disposal(&self) -> Option<Disposal> {
+        match self.action {
+            Action::Delete => None,
+            Action::Trash => Some(Disposal::Trash),
+            Action::Move => self
+                .to
+                .as_ref()
+                .map(|dir| Disposal::MoveTo(PathBuf::from(dir))),
+        }
+    }
+}
+
+/// Reads the entries of a CSV plan. The first line names the columns, as the fields of a JSON
+/// entry are named (`path`, `size`, `mtime`, `action` and `to`); only `path` is required. Empty
+/// cells are left out.
+fn parse_csv(text: &str) -> Result<Vec<PlanEntry>, String> {
+    // Spreadsheets tend to start the file with a byte order mark
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = csv_records(text)?.into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or("the header line is missing")?
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    if let Some(name) = header
+        .iter()
+        .find(|name| !["path", "size", "mtime", "action", "to"].contains(&name.as_str()))
+    {
+        return Err(format!("unknown column {name}"));
+    }
+    if !header.iter().any(|name| name == "path") {
+        return Err("there is no path column".to_string());
+    }
+
+    let mut files = Vec::new();
+    for (number, record) in records.enumerate() {
+        // The header is record 1
+        let number = number + 2;
+        if record.len() != header.len() {
+            return Err(format!(
+                "record {number} has {} fields, but the header has {}",
+                record.len(),
+                header.len()
+            ));
+        }
+        let mut entry = PlanEntry {
+            path: String::new(),
+            size: None,
+            mtime: None,
+            action: Action::default(),
+            to: None,
+        };
+        for (name, value) in header.iter().zip(record) {
+            if value.is_empty() {
+                continue;
+            }
+            match name.as_str() {
+                "path" => entry.path = value,
+                "size" => {
+                    entry.size = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|err| format!("size {value} in record {number}: {err}"))?,
+                    );
+                }
+                "mtime" => entry.mtime = Some(value),
+                "action" => {
+                    entry.action = match value.trim().to_lowercase().as_str() {
+                        "delete" => Action::Delete,
+                        "trash" => Action::Trash,
+                        "move" => Action::Move,
+                        _ => return Err(format!("unknown action {value} in record {number}")),
+                    };
+                }
+                _ => entry.to = Some(value),
+            }
+        } // for name
+        if entry.path.is_empty() {
+            return Err(format!("record {number} has no path"));
+        }
+        files.push(entry);
+    } // for record
+
+    Ok(files)
+}
+
+/// Splits CSV text into records of fields, as RFC 4180 has it: fields are separated by commas
+/// and records by line breaks, and a field in double quotes can hold commas, line breaks and
+/// doubled double quotes. Blank lines are skipped.
+fn csv_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    } // while c
+    if quoted {
+        return Err("a quoted field isn't closed".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records.retain(|record: &Vec<String>| record.len() > 1 || !record[0].is_empty());
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Quotes a field as a spreadsheet would when saving CSV: in double quotes, with the double
+    /// quotes in it doubled, if it holds anything special.
+    fn quote(field: &str) -> String {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+
+    const PATHS: [&str; 6] = [
+        "/data/plain.log",
+        "/data/a, b.txt",
+        "/data/\"quoted\".txt",
+        "/data/line\nbreak.txt",
+        "/data/all, \"of\"\r\nthem,\n.txt",
+        "/data/\"",
+    ];
+
+    #[test]
+    fn csv_round_trip() {
+        let mut text = "path,size,action,to\r\n".to_string();
+        for (size, path) in PATHS.iter().enumerate() {
+            text.push_str(&format!("{},{size},move,{}\r\n", quote(path), quote(path)));
+        }
+
+        let file = std::env::temp_dir().join(format!("rdel-plan-{}.csv", std::process::id()));
+        fs::write(&file, &text).unwrap();
+        let plan = Plan::load(&file);
+        fs::remove_file(&file).unwrap();
+
+        let files = plan.unwrap().files;
+        assert_eq!(files.len(), PATHS.len());
+        for ((size, path), entry) in PATHS.iter().enumerate().zip(&files) {
+            assert_eq!(entry.path, *path);
+            assert_eq!(entry.to.as_deref(), Some(*path));
+            assert_eq!(entry.size, Some(size as u64));
+            assert_eq!(entry.action, Action::Move);
+        }
+    }
+
+    #[test]
+    fn csv_fields() {
+        let records = csv_records("\u{feff}a,\"b,\"\"c\"\"\"\n\n,\nlast").unwrap();
+        assert_eq!(
+            records,
+            [vec!["\u{feff}a", "b,\"c\""], vec!["", ""], vec!["last"]]
+        );
+        assert_eq!(
+            csv_records("path\n\"open").unwrap_err(),
+            "a quoted field isn't closed"
+        );
+    }
+
+    #[test]
+    fn csv_errors() {
+        let error = |text| parse_csv(text).unwrap_err();
+        assert_eq!(error(""), "the header line is missing");
+        assert_eq!(error("size\n1"), "there is no path column");
+        assert_eq!(error("path,owner\n/a,me"), "unknown column owner");
+        assert_eq!(
+            error("path,size\n/a"),
+            "record 2 has 1 fields, but the header has 2"
+        );
+        assert_eq!(error("path,size\n,1"), "record 2 has no path");
+        assert_eq!(
+            error("path,action\n/a,shred"),
+            "unknown action shred in record 2"
+        );
+        assert!(error("path,size\n/a,big").starts_with("size big in record 2: "));
+    }
+
+    #[test]
+    fn csv_empty_cells_are_left_out() {
+        let files =
+            parse_csv("\u{feff}Path, Size ,mtime\n/a,,\n/b, 7 ,2024-05-01T00:00:00Z\n").unwrap();
+        assert_eq!((files[0].size, files[0].mtime.as_deref()), (None, None));
+        assert_eq!(files[0].action, Action::Delete);
+        assert_eq!(files[1].size, Some(7));
+        assert_eq!(files[1].mtime.as_deref(), Some("2024-05-01T00:00:00Z"));
+    }
+}
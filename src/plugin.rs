@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+
+use crate::hook;
+
+/// An external program that decides which files may be removed, for retention rules rdel
+/// doesn't know about, given with `--filter-cmd`.
+///
+/// The program is started once, and is sent the path of each file that passed the other
+/// filters on its standard input, one per line. For each path, in order, it answers on its
+/// standard output with a line saying `keep` (leave the file alone) or `drop` (let it be
+/// removed). The program's standard input is closed at the end of the run, and it should exit
+/// then.
+pub struct FilterCommand {
+    command: String,
+    child: Child,
+    input: BufWriter<ChildStdin>,
+    output: BufReader<ChildStdout>,
+}
+
+/// The answer from the filter program for a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Leave the file alone.
+    Keep,
+
+    /// Let the file be removed.
+    Drop,
+}
+
+impl FilterCommand {
+    /// Starts the filter program with the system's shell.
+    pub fn spawn(command: &str) -> Result<Self, Box<dyn Error>> {
+        let mut child = hook::shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Unable to start the filter command {command}: {err}"))?;
+
+        let input = child
+            .stdin
+            .take()
+            .ok_or("No input for the filter command.")?;
+        let output = child
+            .stdout
+            .take()
+            .ok_or("No output from the filter command.")?;
+        Ok(Self {
+            command: command.to_string(),
+            child,
+            input: BufWriter::new(input),
+            output: BufReader::new(output),
+        })
+    }
+
+    /// Asks the program about a file.
+    pub fn decide(&mut self, path: &str) -> Result<Decision, Box<dyn Error>> {
+        if path.contains(['\n', '\r']) {
+            return Err(format!("{path:?} can't be sent to the filter command.").into());
+        }
+        writeln!(self.input, "{path}")
+            .and_then(|()| self.input.flush())
+            .map_err(|err| {
+                format!(
+                    "Unable to ask the filter command {} about {path}: {err}",
+                    self.command
+                )
+            })?;
+
+        let mut answer = String::new();
+        if self.output.read_line(&mut answer)? == 0 {
+            return Err(format!("The filter command {} stopped answering.", self.command).into());
+        }
+        match answer.trim() {
+            "keep" => Ok(Decision::Keep),
+            "drop" => Ok(Decision::Drop),
+            other => Err(format!(
+                "The filter command {} answered {other:?} for {path}, instead of keep or drop.",
+                self.command
+            )
+            .into()),
+        }
+    }
+
+    /// Closes the program's input, and waits for it to exit.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        let Self {
+            command,
+            mut child,
+            input,
+            ..
+        } = self;
+        drop(input);
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("The filter command {command} failed ({status}).").into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::deleter::Summary;
+use crate::observer::Observer;
+use crate::report::{FileReport, Outcome};
+
+/// The least time between progress records, so a fast run doesn't flood the reader.
+const INTERVAL: Duration = Duration::from_millis(200);
+
+/// A progress record, written as a line of JSON.
+#[derive(Serialize)]
+struct Record<'a> {
+    /// The files found that have been dealt with so far, whether they were removed or failed.
+    done: usize,
+
+    /// The files found to remove so far. It grows as the targets are scanned, chunk by chunk.
+    total: usize,
+
+    /// The files left alone so far, e.g. as they didn't match the filters.
+    skipped: usize,
+
+    /// The size of the files dealt with so far.
+    bytes: u64,
+
+    /// The size of the files found so far.
+    total_bytes: u64,
+
+    /// The file being worked on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a str>,
+
+    /// Set in the last record, once the run has finished.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    finished: bool,
+}
+
+/// Writes progress records to a file descriptor as the run goes, for `--progress-fd`, so a
+/// graphical front-end wrapping rdel can show a progress bar. A record is written at most every
+/// 200 ms, and once more when the run finishes.
+pub struct Progress {
+    out: File,
+    last: Option<Instant>,
+    done: usize,
+    total: usize,
+    skipped: usize,
+    bytes: u64,
+    total_bytes: u64,
+    current: Option<String>,
+}
+
+impl Progress {
+    /// Writes the progress to the open file descriptor `fd`, e.g. a pipe set up by the front-end.
+    pub fn to_fd(fd: i32) -> Result<Self, Box<dyn Error>> {
+        use std::os::fd::{BorrowedFd, FromRawFd};
+
+        // SAFETY: the descriptor is only borrowed to check it's open, and nothing is done with
+        // it if it isn't
+        let open = rustix::io::fcntl_getfd(unsafe { BorrowedFd::borrow_raw(fd) }).is_ok();
+        if !open {
+            return Err(
+                format!("File descriptor {fd} isn't open, so --progress-fd can't use it.").into(),
+            );
+        }
+
+        // SAFETY: the descriptor is open, and was handed to rdel for it to write to
+        let out = unsafe { File::from_raw_fd(fd) };
+        Ok(Self::new(out))
+    }
+
+    fn new(out: File) -> Self {
+        Self {
+            out,
+            last: None,
+            done: 0,
+            total: 0,
+            skipped: 0,
+            bytes: 0,
+            total_bytes: 0,
+            current: None,
+        }
+    }
+
+    /// Writes a record if enough time has passed since the last one, or if `finished`.
+    fn write(&mut self, finished: bool) {
+        if !finished && self.last.is_some_and(|last| last.elapsed() < INTERVAL) {
+            return;
+        }
+        self.last = Some(Instant::now());
+
+        // Files removed without being found first, e.g. from a plan, are counted as they come
+        let record = Record {
+            done: self.done,
+            total: self.total.max(self.done),
+            skipped: self.skipped,
+            bytes: self.bytes,
+            total_bytes: self.total_bytes.max(self.bytes),
+            path: self.current.as_deref().filter(|_| !finished),
+            finished,
+        };
+        let written = serde_json::to_writer(&mut self.out, &record)
+            .map_err(|err| err.to_string())
+            .and_then(|()| self.out.write_all(b"\n").map_err(|err| err.to_string()));
+        if let Err(err) = written {
+            log::debug!("Unable to write the progress: {err}");
+        }
+    }
+}
+
+impl Observer for Progress {
+    fn on_found(&mut self, count: usize, size: u64) {
+        self.total += count;
+        self.total_bytes += size;
+        self.write(false);
+    }
+
+    fn on_file_start(&mut self, path: &str, _size: u64) {
+        self.current = Some(path.to_string());
+        self.write(false);
+    }
+
+    fn on_file_done(&mut self, file: &FileReport) {
+        if file.outcome == Outcome::Skipped {
+            self.skipped += 1;
+        } else {
+            self.done += 1;
+            self.bytes += file.size;
+        }
+        self.write(false);
+    }
+
+    fn on_summary(&mut self, _summary: &Summary) {
+        self.write(true);
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{error::Error, io};
+
+use crate::transfer::{self, Copied};
+
+/// Moves files into a dated folder below the quarantine directory instead of deleting them.
+#[derive(Debug)]
+pub struct Quarantine {
+    dir: PathBuf,
+    today: String,
+}
+
+/// What was removed when purging the quarantine.
+#[derive(Debug, Default)]
+pub struct Purged {
+    pub file_count: usize,
+    pub size: u64,
+}
+
+impl Quarantine {
+    /// Sets up the quarantine below `dir`. Files are placed in a folder named after the date of
+    /// `now` (UTC), the time the run goes by.
+    pub fn new(dir: &Path, now: SystemTime) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            today: date_of(now),
+        }
+    }
+
+    /// Moves a file into today's quarantine folder, keeping its relative path.
+    /// If a file with the same name is already quarantined, a numbered suffix is added.
+    /// A quarantine on another file system gets a copy, and the file is deleted once the copy
+    /// checks out. Returns where the file ended up, and what had to be copied.
+    pub fn move_file(&self, filename: &str) -> Result<(PathBuf, Copied), Box<dyn Error>> {
+        let relative: PathBuf = Path::new(filename)
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+
+        let mut target = self.dir.join(&self.today).join(&relative);
+        let mut counter = 1;
+        while target.symlink_metadata().is_ok() {
+            let mut name = relative.file_name().unwrap_or_default().to_os_string();
+            name.push(format!(".{counter}"));
+            target = self
+                .dir
+                .join(&self.today)
+                .join(relative.with_file_name(name));
+            counter += 1;
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let copied = transfer::move_across(Path::new(filename), &target)?;
+
+        Ok((target, copied))
+    }
+}
+
+/// Returns the date part (`YYYY-MM-DD`) of a time, in UTC.
+fn date_of(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()[..10].to_string()
+}
+
+/// Parses a quarantine folder name back into the start of that day.
+fn parse_date(name: &str) -> Option<SystemTime> {
+    humantime::parse_rfc3339(&format!("{name}T00:00:00Z")).ok()
+}
+
+/// Counts the files and bytes below a directory.
+fn tally(dir: &Path, purged: &mut Purged) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            tally(&entry.path(), purged)?;
+        } else {
+            purged.file_count += 1;
+            purged.size += entry.metadata()?.len();
+        }
+    }
+    Ok(())
+}
+
+/// Permanently removes the quarantine folders in `dir` that are older than `retain` as of `now`.
+/// Folders whose names aren't dates are left alone.
+pub fn purge(
+    dir: &Path,
+    retain: Duration,
+    now: SystemTime,
+    dry_run: bool,
+) -> Result<Purged, Box<dyn Error>> {
+    let mut purged = Purged::default();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let name = entry.file_name();
+        let Some(date) = name.to_str().and_then(parse_date) else {
+            log::debug!(
+                "Ignoring {}: not a quarantine folder.",
+                entry.path().display()
+            );
+            continue;
+        };
+
+        // The folder holds files from the whole day, so its age is counted from the end of the day.
+        let age = now
+            .duration_since(date + Duration::from_secs(24 * 60 * 60))
+            .unwrap_or_default();
+        if age <= retain {
+            continue;
+        }
+
+        let mut folder = Purged::default();
+        tally(&entry.path(), &mut folder)?;
+        log::info!(
+            "Purging quarantine: {} ({} files, {} bytes).",
+            entry.path().display(),
+            folder.file_count,
+            folder.size
+        );
+
+        if !dry_run {
+            fs::remove_dir_all(entry.path())?;
+        }
+
+        purged.file_count += folder.file_count;
+        purged.size += folder.size;
+    }
+
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_is_named_after_the_time_given() {
+        let now = humantime::parse_rfc3339("2024-05-01T23:59:59Z").unwrap();
+        assert_eq!(Quarantine::new(Path::new("/q"), now).today, "2024-05-01");
+        assert_eq!(
+            parse_date("2024-05-01"),
+            humantime::parse_rfc3339("2024-05-01T00:00:00Z").ok()
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+use crate::i18n;
+
+/// What to do about a file that couldn't be removed, as chosen at the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Try to remove the file again, e.g. once whatever was holding it has been closed.
+    Retry,
+
+    /// Leave the file and carry on.
+    Skip,
+
+    /// Leave the file, and every later file failing the same way, without asking again.
+    SkipSimilar,
+
+    /// Stop the run.
+    Abort,
+}
+
+/// Asks what to do about a file that couldn't be removed, until a choice is recognized.
+/// The run is aborted if standard input ends.
+pub fn ask(filename: &str, err: &dyn Error) -> io::Result<Recovery> {
+    let mut stdout = io::stdout();
+    let mut stdin = io::stdin().lock();
+    loop {
+        write!(
+            stdout,
+            "{}\n{} ",
+            i18n::tf(
+                "Unable to remove {path}: {error}",
+                &[("path", &filename), ("error", &err)]
+            ),
+            i18n::t("Retry (r), skip (s), skip all similar (a) or abort (q)?")
+        )?;
+        stdout.flush()?;
+
+        let mut answer = String::new();
+        if stdin.read_line(&mut answer)? == 0 {
+            writeln!(stdout)?;
+            return Ok(Recovery::Abort);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "r" | "retry" => return Ok(Recovery::Retry),
+            "s" | "skip" => return Ok(Recovery::Skip),
+            "a" | "all" => return Ok(Recovery::SkipSimilar),
+            "q" | "abort" => return Ok(Recovery::Abort),
+            _ => {}
+        }
+    } // loop
+}
+
+/// What makes two failures similar, for "skip all similar": the kind of I/O error (e.g.
+/// permission denied), or the message for other errors.
+pub fn similarity(err: &(dyn Error + 'static)) -> String {
+    match err.downcast_ref::<io::Error>() {
+        Some(err) => format!("{:?}", err.kind()),
+        None => err.to_string(),
+    }
+}
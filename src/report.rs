@@ -0,0 +1,107 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufWriter;
+use std::{error::Error, path::Path};
+
+use crate::deleter::Summary;
+use crate::in_use::Holder;
+
+/// What happened to a file during the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// The file was removed.
+    Removed,
+
+    /// The file would have been removed, but this is a dry-run.
+    WouldRemove,
+
+    /// The file was deliberately left alone.
+    Skipped,
+
+    /// The file could not be removed.
+    Failed,
+}
+
+impl Outcome {
+    /// The name of the outcome, as it appears in the report.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Removed => "removed",
+            Self::WouldRemove => "would-remove",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// The details of a single file processed.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    /// When the file was processed, as set with `--time-format` and `--utc`.
+    pub time: String,
+    pub path: String,
+    pub size: u64,
+    pub outcome: Outcome,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched: Option<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub held_by: Vec<Holder>,
+}
+
+impl FileReport {
+    /// Creates a new entry timestamped with the current time, with no reason, checksum, match or
+    /// processes holding the file.
+    pub fn new(path: &str, size: u64, outcome: Outcome) -> Self {
+        Self {
+            time: crate::timestamp::now(),
+            path: path.to_string(),
+            size,
+            outcome,
+            reason: None,
+            checksum: None,
+            matched: None,
+            held_by: Vec::new(),
+        }
+    }
+}
+
+/// A report of everything that happened during the run, written as JSON when the run finishes.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub files: Vec<FileReport>,
+}
+
+impl Report {
+    /// Adds a file to the report.
+    pub fn add(&mut self, file: FileReport) {
+        self.files.push(file);
+    }
+
+    /// Writes the report along with the summary to `path`.
+    pub fn write(&self, path: &Path, summary: &Summary) -> Result<(), Box<dyn Error>> {
+        #[derive(Serialize)]
+        struct Document<'a> {
+            summary: &'a Summary,
+            files: &'a [FileReport],
+        }
+
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(
+            writer,
+            &Document {
+                summary,
+                files: &self.files,
+            },
+        )?;
+        Ok(())
+    }
+}
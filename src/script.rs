@@ -0,0 +1,119 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The kind of script written by `--emit-script`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// A POSIX shell script using `rm` and `rmdir`.
+    Sh,
+
+    /// A PowerShell script using `Remove-Item`.
+    PowerShell,
+}
+
+impl Shell {
+    /// Converts the command line value into a shell.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "powershell" => Self::PowerShell,
+            _ => Self::Sh,
+        }
+    }
+}
+
+/// How an entry is removed by the script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    /// A file, symbolic link or special file.
+    File,
+
+    /// An empty directory.
+    EmptyDir,
+
+    /// A directory along with everything in it.
+    Tree,
+}
+
+/// A script that removes the files a dry-run found, for `--emit-script`, so the plan that was
+/// reviewed can be carried out later where rdel isn't installed. Only local files are written;
+/// the rest are noted in comments.
+pub struct Script {
+    shell: Shell,
+    out: BufWriter<File>,
+}
+
+impl Script {
+    /// Creates the script, replacing it if it already exists, and writes its header.
+    pub fn create(path: &Path, shell: Shell) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(path)
+            .map_err(|err| format!("Unable to create script {}: {err}", path.display()))?;
+        let mut script = Self {
+            shell,
+            out: BufWriter::new(file),
+        };
+
+        let header = match shell {
+            Shell::Sh => "#!/bin/sh\n",
+            Shell::PowerShell => "",
+        };
+        write!(
+            script.out,
+            "{header}# Written by rdel {} from a dry-run. Review it before running it.\n\n",
+            clap::crate_version!()
+        )?;
+        Ok(script)
+    }
+
+    /// Adds the command removing a local file, given by its absolute path.
+    pub fn add(&mut self, filename: &str, removal: Removal) -> Result<(), Box<dyn Error>> {
+        let command = match (self.shell, removal) {
+            (Shell::Sh, Removal::File) => format!("rm -f -- {}", sh_quote(filename)),
+            (Shell::Sh, Removal::EmptyDir) => format!("rmdir -- {}", sh_quote(filename)),
+            (Shell::Sh, Removal::Tree) => format!("rm -rf -- {}", sh_quote(filename)),
+            (Shell::PowerShell, Removal::File | Removal::EmptyDir) => format!(
+                "Remove-Item -LiteralPath {} -Force",
+                powershell_quote(filename)
+            ),
+            (Shell::PowerShell, Removal::Tree) => format!(
+                "Remove-Item -LiteralPath {} -Recurse -Force",
+                powershell_quote(filename)
+            ),
+        };
+        writeln!(self.out, "{command}")?;
+        Ok(())
+    }
+
+    /// Notes an entry the script can't remove, such as an object in S3.
+    pub fn skip(&mut self, name: &str, reason: &str) -> Result<(), Box<dyn Error>> {
+        // A newline in the name would end the comment
+        writeln!(self.out, "# Skipped {}: {reason}", name.replace('\n', "?"))?;
+        Ok(())
+    }
+
+    /// Finishes writing the script.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Quotes a path for a POSIX shell, in single quotes with any inside it written as `'\''`.
+fn sh_quote(path: &str) -> String {
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
+
+/// Quotes a path for PowerShell, in single quotes with any inside it doubled. PowerShell also
+/// takes the typographic single quotes as quotes, so those are doubled too.
+fn powershell_quote(path: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in path.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    } // for c
+    quoted.push('\'');
+    quoted
+}
@@ -0,0 +1,167 @@
+use serde::Serialize;
+use std::path::Path;
+
+/// Who could get at a file just before it was removed, for the audit log, so the access to
+/// data that is gone can still be reconstructed. Whatever can't be read is left out.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Security {
+    /// The owner's user ID. Unix only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+
+    /// The group's ID. Unix only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+
+    /// The permission bits, in octal, e.g. `0640`. Unix only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+
+    /// The entries of the POSIX access ACL, as `getfacl -n` writes them, e.g. `user:1000:rw-`.
+    /// Only set when there is more to the ACL than the permission bits. Linux only.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub acl: Vec<String>,
+
+    /// The entries of the default ACL that new files in a directory get. Linux only.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub default_acl: Vec<String>,
+
+    /// The SELinux security context, e.g. `unconfined_u:object_r:user_home_t:s0`. Linux only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selinux_context: Option<String>,
+
+    /// The owner, group and DACL in SDDL form, e.g. `O:BAG:SYD:(A;;FA;;;SY)(A;;FA;;;BA)`.
+    /// Windows only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sddl: Option<String>,
+}
+
+/// Reads the ownership, permissions, ACLs and security context of a file, without following
+/// a symbolic link.
+pub fn capture(path: &Path) -> Security {
+    let mut security = Security::default();
+
+    #[cfg(unix)]
+    if let Ok(metadata) = path.symlink_metadata() {
+        use std::os::unix::fs::MetadataExt;
+
+        security.uid = Some(metadata.uid());
+        security.gid = Some(metadata.gid());
+        security.mode = Some(format!("{:04o}", metadata.mode() & 0o7777));
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let read = |name| crate::xattr::get(path, name).ok().flatten();
+        security.acl = read(c"system.posix_acl_access").map_or_else(Vec::new, |acl| entries(&acl));
+        security.default_acl =
+            read(c"system.posix_acl_default").map_or_else(Vec::new, |acl| entries(&acl));
+        security.selinux_context = read(c"security.selinux").map(|context| {
+            String::from_utf8_lossy(&context)
+                .trim_end_matches('\0')
+                .to_string()
+        });
+    }
+
+    #[cfg(windows)]
+    match sddl(path) {
+        Ok(sddl) => security.sddl = Some(sddl),
+        Err(err) => log::debug!("Unable to read the security of {}: {err}", path.display()),
+    }
+
+    security
+}
+
+/// Decodes an ACL as Linux stores it in an extended attribute: a version number, followed by
+/// entries of a tag, the permissions and (for named users and groups) an ID, all little-endian.
+#[cfg(target_os = "linux")]
+fn entries(acl: &[u8]) -> Vec<String> {
+    const USER_OBJ: u16 = 0x01;
+    const USER: u16 = 0x02;
+    const GROUP_OBJ: u16 = 0x04;
+    const GROUP: u16 = 0x08;
+    const MASK: u16 = 0x10;
+    const OTHER: u16 = 0x20;
+
+    acl.get(4..)
+        .unwrap_or_default()
+        .chunks_exact(8)
+        .filter_map(|entry| {
+            let tag = u16::from_le_bytes([entry[0], entry[1]]);
+            let perm = u16::from_le_bytes([entry[2], entry[3]]);
+            let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+            let perms: String = [(4, 'r'), (2, 'w'), (1, 'x')]
+                .iter()
+                .map(|&(bit, letter)| if perm & bit != 0 { letter } else { '-' })
+                .collect();
+            let qualifier = match tag {
+                USER_OBJ => "user:".to_string(),
+                USER => format!("user:{id}"),
+                GROUP_OBJ => "group:".to_string(),
+                GROUP => format!("group:{id}"),
+                MASK => "mask:".to_string(),
+                OTHER => "other:".to_string(),
+                _ => return None,
+            };
+            Some(format!("{qualifier}:{perms}"))
+        })
+        .collect()
+}
+
+/// Returns the owner, group and DACL of a file in SDDL form.
+#[cfg(windows)]
+fn sddl(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::{PCWSTR, PWSTR};
+    use windows::Win32::Foundation::{LocalFree, ERROR_SUCCESS, HLOCAL};
+    use windows::Win32::Security::Authorization::{
+        ConvertSecurityDescriptorToStringSecurityDescriptorW, GetNamedSecurityInfoW,
+        SDDL_REVISION_1, SE_FILE_OBJECT,
+    };
+    use windows::Win32::Security::{
+        DACL_SECURITY_INFORMATION, GROUP_SECURITY_INFORMATION, OWNER_SECURITY_INFORMATION,
+        PSECURITY_DESCRIPTOR,
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let information =
+        OWNER_SECURITY_INFORMATION | GROUP_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION;
+    let mut descriptor = PSECURITY_DESCRIPTOR::default();
+    let err = unsafe {
+        GetNamedSecurityInfoW(
+            PCWSTR(wide.as_ptr()),
+            SE_FILE_OBJECT,
+            information,
+            None,
+            None,
+            None,
+            None,
+            &mut descriptor,
+        )
+    };
+    if err != ERROR_SUCCESS {
+        return Err(format!("error {}", err.0).into());
+    }
+
+    let mut text = PWSTR::null();
+    let converted = unsafe {
+        ConvertSecurityDescriptorToStringSecurityDescriptorW(
+            descriptor,
+            SDDL_REVISION_1,
+            information,
+            &mut text,
+            None,
+        )
+    };
+    let sddl = match converted {
+        Ok(()) => unsafe { text.to_string() }.map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
+    // Both were allocated by the system for the caller to free
+    unsafe {
+        LocalFree(Some(HLOCAL(text.0.cast())));
+        LocalFree(Some(HLOCAL(descriptor.0)));
+    }
+    Ok(sddl?)
+}
@@ -0,0 +1,314 @@
+use rand::distr::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, RngExt, SeedableRng};
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// How much is written at a time.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// The fixed patterns of Peter Gutmann's 35-pass method (passes 5 to 31), each repeated
+/// throughout the file.
+const GUTMANN_PATTERNS: [&[u8]; 27] = [
+    &[0x55],
+    &[0xAA],
+    &[0x92, 0x49, 0x24],
+    &[0x49, 0x24, 0x92],
+    &[0x24, 0x92, 0x49],
+    &[0x00],
+    &[0x11],
+    &[0x22],
+    &[0x33],
+    &[0x44],
+    &[0x55],
+    &[0x66],
+    &[0x77],
+    &[0x88],
+    &[0x99],
+    &[0xAA],
+    &[0xBB],
+    &[0xCC],
+    &[0xDD],
+    &[0xEE],
+    &[0xFF],
+    &[0x92, 0x49, 0x24],
+    &[0x49, 0x24, 0x92],
+    &[0x24, 0x92, 0x49],
+    &[0x6D, 0xB6, 0xDB],
+    &[0xB6, 0xDB, 0x6D],
+    &[0xDB, 0x6D, 0xB6],
+];
+
+/// How files are overwritten before they are deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// A single pass of zeros.
+    Zeros,
+
+    /// A single pass of random data.
+    Random,
+
+    /// DoD 5220.22-M: zeros, ones and then random data.
+    Dod,
+
+    /// The 27 fixed patterns of the Gutmann method, without its random passes.
+    Gutmann,
+}
+
+/// One pass over the file.
+#[derive(Debug, Clone, Copy)]
+enum Pass {
+    /// The pattern repeated throughout the file.
+    Pattern(&'static [u8]),
+
+    /// Random data, generated from the seed so it can be generated again for verification.
+    Random(u64),
+}
+
+impl Method {
+    /// Converts the command line argument into a method.
+    pub fn from_arg(arg: &str) -> Self {
+        match arg {
+            "random" => Self::Random,
+            "dod" => Self::Dod,
+            "gutmann" => Self::Gutmann,
+            _ => Self::Zeros,
+        }
+    }
+
+    /// The name of the method, as recorded in the audit log.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zeros => "zeros",
+            Self::Random => "random",
+            Self::Dod => "dod-5220.22-m",
+            Self::Gutmann => "gutmann",
+        }
+    }
+
+    /// The passes made over the file, in order.
+    fn passes(self) -> Vec<Pass> {
+        match self {
+            Self::Zeros => vec![Pass::Pattern(&[0x00])],
+            Self::Random => vec![Pass::Random(rand::random())],
+            Self::Dod => vec![
+                Pass::Pattern(&[0x00]),
+                Pass::Pattern(&[0xFF]),
+                Pass::Random(rand::random()),
+            ],
+            Self::Gutmann => GUTMANN_PATTERNS.into_iter().map(Pass::Pattern).collect(),
+        }
+    }
+}
+
+impl Pass {
+    /// Returns a generator for the data written in this pass.
+    fn data(self) -> PassData {
+        match self {
+            Self::Pattern(pattern) => PassData::Pattern(pattern),
+            Self::Random(seed) => PassData::Random(Box::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+}
+
+/// Generates the data for a pass, block by block.
+enum PassData {
+    Pattern(&'static [u8]),
+    Random(Box<StdRng>),
+}
+
+impl PassData {
+    /// Fills `buffer` with the data for the file starting at `offset`. Random data must be
+    /// generated for the same blocks in the same order to get the same data again.
+    fn fill(&mut self, buffer: &mut [u8], offset: u64) {
+        match self {
+            Self::Pattern(pattern) => {
+                let phase = (offset % pattern.len() as u64) as usize;
+                for (byte, value) in buffer.iter_mut().zip(pattern.iter().cycle().skip(phase)) {
+                    *byte = *value;
+                }
+            }
+            Self::Random(rng) => rng.fill_bytes(buffer),
+        }
+    }
+}
+
+/// Overwrites the data in a file using `method`, flushing each pass to the disk, so the contents
+/// can't be recovered after the file is deleted. If `verify` is set, the last pass is read back
+/// and compared. Holes in sparse files are skipped, as there is nothing on the disk to
+/// overwrite. Files with other hard links are refused, as the other links would lose their
+/// data too. Returns the number of bytes written in each pass.
+pub fn shred(path: &Path, method: Method, verify: bool) -> Result<u64, Box<dyn Error>> {
+    let mut file = open(path)?;
+    let metadata = file.metadata()?;
+    #[cfg(unix)]
+    if std::os::unix::fs::MetadataExt::nlink(&metadata) > 1 {
+        return Err(format!(
+            "{} has other hard links, whose data would be overwritten too. Not shredding it.",
+            path.display()
+        )
+        .into());
+    }
+    let size = metadata.len();
+    let regions = data_regions(&file, size)?;
+    let written = regions.iter().map(|(start, end)| end - start).sum();
+
+    let passes = method.passes();
+    let mut block = vec![0; BLOCK_SIZE];
+    for pass in &passes {
+        let mut data = pass.data();
+        for_each_block(&regions, |offset, length| {
+            data.fill(&mut block[..length], offset);
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&block[..length])
+        })?;
+        file.sync_data()?;
+    } // for pass
+
+    if let (true, Some(last)) = (verify, passes.last()) {
+        log::debug!("Verifying {}", path.display());
+        let mut data = last.data();
+        let mut written_block = vec![0; BLOCK_SIZE];
+        for_each_block(&regions, |offset, length| {
+            data.fill(&mut block[..length], offset);
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut written_block[..length])?;
+            if block[..length] == written_block[..length] {
+                Ok(())
+            } else {
+                Err(std::io::Error::other(format!(
+                    "Verification failed: the data read back near offset {offset} differs from what was written."
+                )))
+            }
+        })?;
+    }
+
+    log::debug!(
+        "Shredded {written} of {size} bytes in {} with {} passes ({})",
+        path.display(),
+        passes.len(),
+        method.name()
+    );
+    Ok(written)
+}
+
+/// How many random names are tried for each length before giving up on that length.
+const NAME_ATTEMPTS: usize = 10;
+
+/// Renames a file to random names of the same length as its name, then one character shorter
+/// each time down to a single character, so the original name can't easily be recovered from
+/// the directory. An existing file is never replaced. Returns the file's final path.
+pub fn scramble_name(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let length = path.file_name().map_or(1, |name| name.len().max(1));
+    let mut rng = rand::rng();
+
+    let mut current = path.to_path_buf();
+    for length in (1..=length).rev() {
+        for _ in 0..NAME_ATTEMPTS {
+            let name: String = (&mut rng)
+                .sample_iter(Alphanumeric)
+                .take(length)
+                .map(char::from)
+                .collect();
+            let target = current.with_file_name(name);
+            match rename_no_replace(&current, &target) {
+                Ok(()) => {
+                    current = target;
+                    break;
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err.into()),
+            }
+        } // for attempt
+    } // for length
+
+    log::debug!("Renamed {} to {}", path.display(), current.display());
+    Ok(current)
+}
+
+/// Renames a file, failing if the target already exists.
+#[cfg(target_os = "linux")]
+fn rename_no_replace(from: &Path, to: &Path) -> std::io::Result<()> {
+    use rustix::fs::{renameat_with, RenameFlags, CWD};
+    Ok(renameat_with(CWD, from, CWD, to, RenameFlags::NOREPLACE)?)
+}
+
+/// Renames a file, failing if the target already exists.
+#[cfg(not(target_os = "linux"))]
+fn rename_no_replace(from: &Path, to: &Path) -> std::io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(std::io::ErrorKind::AlreadyExists.into());
+    }
+    std::fs::rename(from, to)
+}
+
+/// Calls `f` with the offset and length of each block in the regions, in order.
+fn for_each_block<F>(regions: &[(u64, u64)], mut f: F) -> std::io::Result<()>
+where
+    F: FnMut(u64, usize) -> std::io::Result<()>,
+{
+    for &(start, end) in regions {
+        let mut offset = start;
+        while offset < end {
+            let length = (end - offset).min(BLOCK_SIZE as u64) as usize;
+            f(offset, length)?;
+            offset += length as u64;
+        } // while offset
+    } // for region
+
+    Ok(())
+}
+
+/// Opens a file for reading and writing without truncating it, or following a symbolic link.
+#[cfg(unix)]
+fn open(path: &Path) -> Result<File, Box<dyn Error>> {
+    use rustix::fs::{Mode, OFlags};
+    let fd = rustix::fs::open(
+        path,
+        OFlags::RDWR | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+    Ok(File::from(fd))
+}
+
+/// Opens a file for reading and writing without truncating it.
+#[cfg(not(unix))]
+fn open(path: &Path) -> Result<File, Box<dyn Error>> {
+    Ok(std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?)
+}
+
+/// Returns the start and end of each part of the file that holds data, using SEEK_DATA and
+/// SEEK_HOLE. File systems that don't track holes report the whole file as data.
+#[cfg(target_os = "linux")]
+fn data_regions(file: &File, size: u64) -> Result<Vec<(u64, u64)>, Box<dyn Error>> {
+    use rustix::fs::{seek, SeekFrom};
+    use rustix::io::Errno;
+
+    let mut regions = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        let start = match seek(file, SeekFrom::Data(offset)) {
+            Ok(start) => start,
+            // No data after the offset
+            Err(Errno::NXIO) => break,
+            Err(Errno::INVAL | Errno::OPNOTSUPP) => return Ok(vec![(0, size)]),
+            Err(err) => return Err(err.into()),
+        };
+        let end = seek(file, SeekFrom::Hole(start))?.min(size);
+        regions.push((start, end));
+        offset = end;
+    } // while offset
+
+    Ok(regions)
+}
+
+/// Returns the whole file as a single part holding data.
+#[cfg(not(target_os = "linux"))]
+fn data_regions(_file: &File, size: u64) -> Result<Vec<(u64, u64)>, Box<dyn Error>> {
+    Ok(vec![(0, size)])
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::thread::JoinHandle;
+
+use crate::deleter::Summary;
+use crate::observer::Observer;
+use crate::report::FileReport;
+
+/// How many files can wait to be taken from the stream before the run waits for them to be.
+pub(crate) const QUEUE_SIZE: usize = 1024;
+
+/// What happened to a file, as handed back by `Deleter::stream`.
+pub type FileOutcome = FileReport;
+
+/// How a run on another thread ended.
+type Finished = Result<Summary, Box<dyn Error + Send + Sync>>;
+
+/// The files dealt with by a run on another thread, as they are done. Iterate over it to get
+/// each file, then call `finish` for the summary, or the error that stopped the run.
+pub struct Stream {
+    files: Receiver<FileOutcome>,
+    worker: JoinHandle<Finished>,
+}
+
+impl Stream {
+    pub(crate) fn new(files: Receiver<FileOutcome>, worker: JoinHandle<Finished>) -> Self {
+        Self { files, worker }
+    }
+
+    /// Waits for the run to end and returns its summary. Files not yet taken from the stream
+    /// are dropped.
+    pub fn finish(self) -> Finished {
+        drop(self.files);
+        self.worker
+            .join()
+            .unwrap_or_else(|_| Err("The run stopped unexpectedly.".into()))
+    }
+}
+
+impl Iterator for Stream {
+    type Item = FileOutcome;
+
+    fn next(&mut self) -> Option<FileOutcome> {
+        self.files.recv().ok()
+    }
+}
+
+/// Sends each file dealt with down the stream.
+pub(crate) struct Sender(pub SyncSender<FileOutcome>);
+
+impl Observer for Sender {
+    fn on_file_done(&mut self, file: &FileReport) {
+        // Nobody is listening once the stream has been finished
+        let _ = self.0.send(file.clone());
+    }
+}
@@ -0,0 +1,41 @@
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Tells whether the program is already running as root, when there is nothing to gain from
+/// `sudo`.
+pub fn is_root() -> bool {
+    rustix::process::geteuid().is_root()
+}
+
+/// Removes `paths` again through a single run of this program under `sudo`, fed the paths on
+/// standard input, so the password is asked for once at most. `options` are passed on to it,
+/// e.g. `--recursive`. Which of the files are gone has to be checked afterwards, as some may
+/// have been removed even if the run as a whole failed.
+pub fn retry(paths: &[&str], options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let program = std::env::current_exe()
+        .map_err(|err| format!("Unable to find the program to run with sudo: {err}"))?;
+    let mut child = Command::new("sudo")
+        .arg("--")
+        .arg(program)
+        .args(options)
+        .args(["--null", "--quiet", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Unable to run sudo: {err}"))?;
+
+    // Writing fails if sudo gives up, e.g. on a wrong password, but then its exit status says more
+    let written = child.stdin.take().map_or(Ok(()), |mut stdin| {
+        paths.iter().try_for_each(|path| {
+            stdin.write_all(path.as_bytes())?;
+            stdin.write_all(b"\0")
+        })
+    });
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("The run with sudo failed ({status}).").into());
+    }
+    written.map_err(|err| format!("Unable to pass the files on to sudo: {err}"))?;
+
+    Ok(())
+}
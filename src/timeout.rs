@@ -0,0 +1,70 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// An operation that didn't finish in time, e.g. on a hung NFS server.
+#[derive(Debug, Clone, Copy)]
+pub struct TimedOut {
+    pub limit: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Timed out after {}.",
+            humantime::format_duration(self.limit)
+        )
+    }
+}
+
+impl Error for TimedOut {}
+
+/// Runs `f`, giving up on it after `limit` if one is given. File system calls can't be
+/// interrupted, so `f` runs on a thread of its own, which is left behind if it doesn't finish.
+/// Without a limit, `f` simply runs on the current thread.
+pub fn run<T, F>(limit: Option<Duration>, f: F) -> Result<T, Box<dyn Error>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Box<dyn Error>> + Send + 'static,
+{
+    let Some(limit) = limit else {
+        return f();
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // Nobody is listening any more if the operation took too long
+        let _ = sender.send(f().map_err(sendable));
+    });
+
+    match receiver.recv_timeout(limit) {
+        Ok(result) => result.map_err(|err| err as Box<dyn Error>),
+        Err(RecvTimeoutError::Timeout) => Err(Box::new(TimedOut { limit })),
+        Err(RecvTimeoutError::Disconnected) => Err("The operation failed unexpectedly.".into()),
+    }
+}
+
+/// Converts an error so it can be sent back from another thread. I/O errors are kept as they
+/// are, so their kind isn't lost, as are files found to be immutable; anything else is kept as
+/// its message.
+pub fn sendable(err: Box<dyn Error>) -> Box<dyn Error + Send + Sync> {
+    let err = match err.downcast::<std::io::Error>() {
+        Ok(err) => return err,
+        Err(err) => err,
+    };
+    #[cfg(unix)]
+    let err = match err.downcast::<rustix::io::Errno>() {
+        Ok(errno) => return Box::new(std::io::Error::from(*errno)),
+        Err(err) => err,
+    };
+    #[cfg(unix)]
+    let err = match err.downcast::<crate::immutable::Locked>() {
+        Ok(locked) => return locked,
+        Err(err) => err,
+    };
+
+    err.to_string().into()
+}
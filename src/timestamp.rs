@@ -0,0 +1,82 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+/// The format used when none is given: RFC 3339, to the second, with the offset from UTC.
+const DEFAULT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// How the timestamps in the logs and reports are written.
+#[derive(Debug, Clone)]
+struct Style {
+    format: String,
+    utc: bool,
+}
+
+/// The style in use, once it has been picked.
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+/// Picks how timestamps are written: with `format` (a strftime-style format such as
+/// `%d.%m.%Y %H:%M`) if given, or else as RFC 3339, and in UTC rather than local time if `utc`
+/// is set. Only the first call counts.
+pub fn init(format: Option<&str>, utc: bool) {
+    let _ = STYLE.set(Style {
+        format: format.unwrap_or(DEFAULT_FORMAT).to_string(),
+        utc,
+    });
+}
+
+/// Checks a strftime-style format given on the command line.
+pub fn parse_format(format: &str) -> Result<String, String> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(format!("{format} is not a valid time format"));
+    }
+    Ok(format.to_string())
+}
+
+/// Returns the current time, formatted.
+pub fn now() -> String {
+    format(SystemTime::now())
+}
+
+/// Returns the current time in RFC 3339, for the records read back by programs: the audit
+/// trail, the journal and the trash records. Only `--utc` changes how it's written.
+pub fn now_rfc3339() -> String {
+    write(SystemTime::now(), DEFAULT_FORMAT, is_utc())
+}
+
+/// Formats a point in time.
+pub fn format(time: SystemTime) -> String {
+    let format = STYLE
+        .get()
+        .map_or(DEFAULT_FORMAT, |style| style.format.as_str());
+    write(time, format, is_utc())
+}
+
+/// Whether timestamps are written in UTC rather than local time.
+fn is_utc() -> bool {
+    STYLE.get().is_some_and(|style| style.utc)
+}
+
+/// Writes a point in time with `format`, in UTC or in local time.
+fn write(time: SystemTime, format: &str, utc: bool) -> String {
+    let time = DateTime::<Utc>::from(time);
+    if utc {
+        // RFC 3339 times in UTC are written with a Z
+        let format = if format == DEFAULT_FORMAT {
+            "%Y-%m-%dT%H:%M:%SZ"
+        } else {
+            format
+        };
+        time.format(format).to_string()
+    } else {
+        time.with_timezone(&Local).format(format).to_string()
+    }
+}
+
+/// Formats a timestamp read back from a record, e.g. the audit log. Timestamps that aren't RFC
+/// 3339 are returned as they are.
+pub fn reformat(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map_or_else(|_| timestamp.to_string(), |time| format(time.into()))
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+/// How many removals are timed before any of them is flagged as an outlier.
+const MIN_SAMPLES: u32 = 10;
+
+/// Keeps track of how long removals take, for `--timings`, to spot the ones that take much
+/// longer than the rest, e.g. on a slow network mount or while a virus scanner looks at them.
+#[derive(Debug, Default)]
+pub struct Timings {
+    count: u32,
+    mean: f64,
+    m2: f64,
+}
+
+impl Timings {
+    /// Adds how long a removal took. Returns the average so far if it's an outlier: more than
+    /// three standard deviations above the average, and at least twice as long.
+    pub fn add(&mut self, took: Duration) -> Option<Duration> {
+        let secs = took.as_secs_f64();
+        let average = self.mean;
+        let outlier = self.count >= MIN_SAMPLES && {
+            let deviation = (self.m2 / f64::from(self.count - 1)).sqrt();
+            secs > average + 3.0 * deviation && secs > 2.0 * average
+        };
+
+        // Welford's algorithm, so the times don't have to be kept
+        self.count += 1;
+        let delta = secs - self.mean;
+        self.mean += delta / f64::from(self.count);
+        self.m2 += delta * (secs - self.mean);
+
+        outlier.then(|| Duration::from_secs_f64(average))
+    }
+}
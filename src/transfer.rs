@@ -0,0 +1,340 @@
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::{error::Error, ops::AddAssign};
+
+use crate::checksum;
+
+/// The size of the buffer used when copying files.
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// The least time between progress messages while a file is copied.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What had to be copied to another file system when moving an entry, rather than renamed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Copied {
+    /// The files, symbolic links and directories copied.
+    pub file_count: usize,
+
+    /// The bytes copied. They took up space on both file systems until the originals were
+    /// deleted.
+    pub size: u64,
+}
+
+impl AddAssign for Copied {
+    fn add_assign(&mut self, other: Self) {
+        self.file_count += other.file_count;
+        self.size += other.size;
+    }
+}
+
+/// Moves a file, symbolic link or directory tree from `from` to `to`, which must not exist. It's
+/// renamed if it can be; if `to` is on another file system, it's copied instead, each file
+/// checked against the SHA-256 digest of the original, and only then is the original deleted.
+/// If the copy fails, what was copied is removed again and the original is left as it was.
+/// Returns what had to be copied, which is nothing if the entry was renamed.
+pub fn move_across(from: &Path, to: &Path) -> Result<Copied, Box<dyn Error>> {
+    match fs::rename(from, to) {
+        Ok(()) => return Ok(Copied::default()),
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    log::debug!(
+        "{} is on another file system than {}, so it's copied and then deleted",
+        from.display(),
+        to.display()
+    );
+    copy_across(from, to)
+}
+
+/// Copies an entry from `from` to `to` and deletes the original, for [`move_across`] when it
+/// can't be renamed. A partial copy is removed again if the copy fails.
+fn copy_across(from: &Path, to: &Path) -> Result<Copied, Box<dyn Error>> {
+    let mut copied = Copied::default();
+    if let Err(err) = copy_entry(from, to, &mut copied) {
+        // Something already at `to` isn't the copy's to remove
+        let taken = err
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == ErrorKind::AlreadyExists);
+        if !taken {
+            if let Err(cleanup) = remove_entry(to) {
+                log::warn!(
+                    "Unable to remove the partial copy {}: {cleanup}",
+                    to.display()
+                );
+            }
+        }
+        return Err(format!(
+            "Unable to copy {} to {}: {err}",
+            from.display(),
+            to.display()
+        )
+        .into());
+    }
+
+    remove_entry(from).map_err(|err| {
+        format!(
+            "Copied {} to {}, but unable to delete the original: {err}",
+            from.display(),
+            to.display()
+        )
+    })?;
+    Ok(copied)
+}
+
+/// Copies an entry, and everything in it if it's a directory, keeping the permissions,
+/// modification times and extended attributes.
+fn copy_entry(from: &Path, to: &Path, copied: &mut Copied) -> Result<(), Box<dyn Error>> {
+    let metadata = fs::symlink_metadata(from)?;
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        copy_link(from, to)?;
+    } else if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for dir_entry in fs::read_dir(from)? {
+            let dir_entry = dir_entry?;
+            copy_entry(&dir_entry.path(), &to.join(dir_entry.file_name()), copied)?;
+        } // for dir_entry
+        fs::set_permissions(to, metadata.permissions())?;
+        // Directories can't be opened like files everywhere, so their times are kept if they can be
+        let kept = File::open(to).and_then(|dir| dir.set_modified(metadata.modified()?));
+        if let Err(err) = kept {
+            log::debug!(
+                "Unable to keep the modification time of {}: {err}",
+                to.display()
+            );
+        }
+    } else if file_type.is_file() {
+        copy_file(from, to, metadata.len())?;
+        fs::set_permissions(to, metadata.permissions())?;
+        copied.size += metadata.len();
+    } else {
+        return Err(format!(
+            "{} is a special file, which can't be copied.",
+            from.display()
+        )
+        .into());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    crate::xattr::copy(from, to)?;
+    copied.file_count += 1;
+    Ok(())
+}
+
+/// Copies the contents of a regular file of `size` bytes to a new file, and checks the copy
+/// against the SHA-256 digest of what was read, once it's on the disk. Progress is logged for
+/// files that take a while.
+fn copy_file(from: &Path, to: &Path, size: u64) -> Result<(), Box<dyn Error>> {
+    let mut source = File::open(from)?;
+    let mut target = OpenOptions::new().write(true).create_new(true).open(to)?;
+
+    let (started, mut reported) = (Instant::now(), Instant::now());
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0_u8; BUFFER_SIZE];
+    let mut done = 0_u64;
+    loop {
+        let bytes_read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(bytes_read) => bytes_read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        hasher.update(&buffer[..bytes_read]);
+        target.write_all(&buffer[..bytes_read])?;
+        done += bytes_read as u64;
+
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            log::info!(
+                "Copying {} to another file system: {} of {} bytes ({:.0}%)",
+                from.display(),
+                crate::utils::thousand_separated(done),
+                crate::utils::thousand_separated(size),
+                done as f64 * 100.0 / size.max(1) as f64
+            );
+        }
+    } // loop
+    target.set_modified(source.metadata()?.modified()?)?;
+    target.sync_all()?;
+    drop(target);
+
+    verify(to, &checksum::to_hex(&hasher.finalize()))?;
+    if started.elapsed() >= PROGRESS_INTERVAL {
+        log::info!(
+            "Copied {} to another file system, {} bytes.",
+            from.display(),
+            crate::utils::thousand_separated(done)
+        );
+    }
+    Ok(())
+}
+
+/// Checks a copy on the disk against `expected`, the SHA-256 digest of what was copied.
+fn verify(to: &Path, expected: &str) -> Result<(), Box<dyn Error>> {
+    let digest = checksum::sha256(to)?;
+    if digest != expected {
+        return Err(
+            format!("the copy has SHA-256 {digest}, but the original has {expected}.").into(),
+        );
+    }
+    Ok(())
+}
+
+/// Makes a symbolic link pointing where `from` does.
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    let points_to = fs::read_link(from)?;
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(points_to, to)
+    }
+    #[cfg(windows)]
+    {
+        if fs::metadata(from).is_ok_and(|metadata| metadata.is_dir()) {
+            std::os::windows::fs::symlink_dir(points_to, to)
+        } else {
+            std::os::windows::fs::symlink_file(points_to, to)
+        }
+    }
+}
+
+/// Removes a file, symbolic link or directory tree.
+fn remove_entry(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    /// Makes an empty directory for a test to copy from and to.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rdel-transfer-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("from")).unwrap();
+        dir
+    }
+
+    /// Makes a directory holding a file, a subdirectory with another file, and a symbolic link.
+    fn source_tree(dir: &Path) -> PathBuf {
+        let from = dir.join("from").join("tree");
+        fs::create_dir_all(from.join("sub")).unwrap();
+        fs::write(from.join("file"), b"hello").unwrap();
+        fs::write(from.join("sub").join("other"), b"world!").unwrap();
+        std::os::unix::fs::symlink("file", from.join("link")).unwrap();
+        from
+    }
+
+    #[test]
+    fn renamed_on_the_same_file_system() {
+        let dir = test_dir("rename");
+        let from = source_tree(&dir);
+        let to = dir.join("to");
+        assert_eq!(move_across(&from, &to).unwrap(), Copied::default());
+        assert!(!from.exists());
+        assert_eq!(fs::read(to.join("file")).unwrap(), b"hello");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn copies_keep_permissions_and_times() {
+        let dir = test_dir("metadata");
+        let from = source_tree(&dir);
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let file = File::options().write(true).open(from.join("file")).unwrap();
+        file.set_modified(modified).unwrap();
+        drop(file);
+        fs::set_permissions(from.join("file"), fs::Permissions::from_mode(0o640)).unwrap();
+        fs::set_permissions(from.join("sub"), fs::Permissions::from_mode(0o750)).unwrap();
+
+        let to = dir.join("to");
+        let copied = copy_across(&from, &to).unwrap();
+        assert_eq!(
+            copied,
+            Copied {
+                file_count: 5,
+                size: 11
+            }
+        );
+        assert!(!from.exists());
+        assert_eq!(fs::read(to.join("file")).unwrap(), b"hello");
+        assert_eq!(fs::read(to.join("sub").join("other")).unwrap(), b"world!");
+        assert_eq!(fs::read_link(to.join("link")).unwrap(), Path::new("file"));
+
+        let metadata = fs::metadata(to.join("file")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(metadata.modified().unwrap(), modified);
+        let metadata = fs::metadata(to.join("sub")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o750);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mismatched_copy_rejected() {
+        let dir = test_dir("mismatch");
+        let from = dir.join("from").join("file");
+        fs::write(&from, b"hello").unwrap();
+        let to = dir.join("to");
+        copy_file(&from, &to, 5).unwrap();
+        let expected = checksum::sha256(&from).unwrap();
+        verify(&to, &expected).unwrap();
+
+        // As a disk going bad might leave it
+        OpenOptions::new()
+            .append(true)
+            .open(&to)
+            .unwrap()
+            .write_all(b"!")
+            .unwrap();
+        let err = verify(&to, &expected).unwrap_err().to_string();
+        assert!(err.contains("the copy has SHA-256"), "{err}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partial_copy_removed() {
+        let dir = test_dir("partial");
+        let from = source_tree(&dir);
+        let fifo = from.join("sub").join("fifo");
+        rustix::fs::mknodat(
+            rustix::fs::CWD,
+            &fifo,
+            rustix::fs::FileType::Fifo,
+            rustix::fs::Mode::from(0o600),
+            0,
+        )
+        .unwrap();
+        let to = dir.join("to");
+        let err = copy_across(&from, &to).unwrap_err().to_string();
+        assert!(err.contains("special file"), "{err}");
+        assert!(!to.exists());
+        assert_eq!(fs::read(from.join("file")).unwrap(), b"hello");
+        assert!(fs::symlink_metadata(&fifo).unwrap().file_type().is_fifo());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_target_left_alone() {
+        let dir = test_dir("existing");
+        let from = dir.join("from").join("file");
+        fs::write(&from, b"new").unwrap();
+        let to = dir.join("to");
+        fs::write(&to, b"old").unwrap();
+        assert!(copy_across(&from, &to).is_err());
+        assert_eq!(fs::read(&to).unwrap(), b"old");
+        assert_eq!(fs::read(&from).unwrap(), b"new");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
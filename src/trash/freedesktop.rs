@@ -0,0 +1,393 @@
+use std::env;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::TrashMetadata;
+use crate::expand;
+use crate::transfer::{self, Copied};
+
+/// The sticky bit, which the shared `.Trash` directory of a file system must have.
+const STICKY: u32 = 0o1000;
+
+/// Moves a file or directory into the freedesktop.org trash for the file system it's on: the
+/// home trash if that's on the same file system, or else the `.Trash/$uid` (or `.Trash-$uid`)
+/// directory at the top of the file system, so nothing has to be copied between file systems
+/// (e.g. from an external drive to the home partition). If the file system has no trash that
+/// can be used, e.g. as it's mounted from a share the user can't create it on, the file is
+/// copied to the home trash instead and then deleted. Returns where in the trash the file
+/// ended up, and what had to be copied.
+pub fn delete(filename: &str) -> Result<(PathBuf, Copied), Box<dyn Error>> {
+    let path = absolute(Path::new(filename))?;
+    let device = fs::symlink_metadata(&path)?.dev();
+
+    let home_trash = home_trash()?;
+    if device_of(&home_trash)? == device {
+        return move_to_trash(&path, &home_trash, &path);
+    }
+
+    // The trash at the top of the file system records the paths relative to the top
+    let topdir = topdir(&path, device)?;
+    match volume_trash(&topdir) {
+        Ok(trash_dir) => {
+            let relative = path.strip_prefix(&topdir).unwrap_or(&path);
+            move_to_trash(&path, &trash_dir, relative)
+        }
+        Err(err) => {
+            log::warn!(
+                "Unable to use the trash on {}: {err}. Copying {} to the home trash instead.",
+                topdir.display(),
+                path.display()
+            );
+            move_to_trash(&path, &home_trash, &path)
+        }
+    }
+}
+
+/// Returns the absolute path of a file, with the directories it's in resolved but not the file
+/// itself, so a symbolic link is trashed rather than what it points to.
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::other(format!("{} can't be trashed.", path.display())))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(fs::canonicalize(parent)?.join(name))
+}
+
+/// Returns the home trash: `$XDG_DATA_HOME/Trash`, or `~/.local/share/Trash`.
+fn home_trash() -> Result<PathBuf, Box<dyn Error>> {
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ => expand::home_dir()
+            .ok_or("Unable to find the home directory for the trash.")?
+            .join(".local/share"),
+    };
+    Ok(data_home.join("Trash"))
+}
+
+/// Returns the device of the file system a path is on, or will be on once it's created.
+fn device_of(path: &Path) -> io::Result<u64> {
+    let mut path = path;
+    loop {
+        match fs::metadata(path) {
+            Ok(metadata) => return Ok(metadata.dev()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match path.parent() {
+                Some(parent) => path = parent,
+                None => return Err(err),
+            },
+            Err(err) => return Err(err),
+        }
+    } // loop
+}
+
+/// Returns the top directory of the file system a file is on, i.e. where it's mounted.
+fn topdir(path: &Path, device: u64) -> io::Result<PathBuf> {
+    let mut dir = path.parent().unwrap_or(path);
+    while let Some(parent) = dir.parent() {
+        if fs::metadata(parent)?.dev() != device {
+            break;
+        }
+        dir = parent;
+    } // while parent
+    Ok(dir.to_path_buf())
+}
+
+/// Returns the user's trash at the top of a file system: `.Trash/$uid` if the administrator has
+/// set up a shared `.Trash` directory, or else `.Trash-$uid`, which is created if need be.
+fn volume_trash(topdir: &Path) -> io::Result<PathBuf> {
+    let uid = rustix::process::getuid().as_raw();
+
+    // The shared directory must be a real, sticky directory, or it's not to be trusted
+    let shared = topdir.join(".Trash");
+    match fs::symlink_metadata(&shared) {
+        Ok(metadata) if metadata.is_dir() && metadata.permissions().mode() & STICKY != 0 => {
+            let dir = shared.join(uid.to_string());
+            match private_dir(&dir, uid) {
+                Ok(()) => return Ok(dir),
+                Err(err) => log::debug!("Unable to use {}: {err}", dir.display()),
+            }
+        }
+        Ok(_) => log::warn!(
+            "Ignoring {}: not a directory with the sticky bit set.",
+            shared.display()
+        ),
+        Err(_) => {}
+    }
+
+    let dir = topdir.join(format!(".Trash-{uid}"));
+    private_dir(&dir, uid)?;
+    Ok(dir)
+}
+
+/// Creates a directory only the user can get into, or checks that an existing one is a real
+/// directory belonging to the user.
+fn private_dir(dir: &Path, uid: u32) -> io::Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err),
+    }
+
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(io::Error::other(format!(
+            "{} is not a directory belonging to the user.",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Moves a file into the `files` directory of a trash directory, under a name not already in
+/// use, and writes the `.trashinfo` file recording `original` (the path to restore it to) and
+/// when it was deleted. A trash directory on another file system gets a copy of the file.
+fn move_to_trash(
+    path: &Path,
+    trash_dir: &Path,
+    original: &Path,
+) -> Result<(PathBuf, Copied), Box<dyn Error>> {
+    let files = trash_dir.join("files");
+    let info = trash_dir.join("info");
+    for dir in [&files, &info] {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+
+    let name = path.file_name().unwrap_or_default();
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode(original),
+        chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+
+    // Creating the info file first claims the name, as the spec asks
+    let mut counter = 0;
+    loop {
+        counter += 1;
+        let mut trash_name = name.to_os_string();
+        if counter > 1 {
+            trash_name.push(format!(".{counter}"));
+        }
+        let mut info_name = trash_name.clone();
+        info_name.push(".trashinfo");
+        let info_file = info.join(&info_name);
+
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_file)
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let target = files.join(&trash_name);
+        let moved = if target.symlink_metadata().is_ok() {
+            None
+        } else {
+            Some(
+                file.write_all(contents.as_bytes())
+                    .map_err(Into::into)
+                    .and_then(|()| transfer::move_across(path, &target)),
+            )
+        };
+        match moved {
+            Some(Ok(copied)) => {
+                // The file is in the trash by now, so only the extra details can be missing
+                if let Err(err) = write_metadata(trash_dir, &trash_name, path, &target) {
+                    log::warn!(
+                        "Unable to record the details of {} in the trash: {err}",
+                        path.display()
+                    );
+                }
+                return Ok((target, copied));
+            }
+            Some(Err(err)) => {
+                let _ = fs::remove_file(&info_file);
+                return Err(err);
+            }
+            // Left over from something else, so the name is taken after all
+            None => {
+                let _ = fs::remove_file(&info_file);
+            }
+        }
+    } // loop
+}
+
+/// Writes the sidecar file recording the details of a file just moved to the trash from `path`
+/// to `target`.
+fn write_metadata(
+    trash_dir: &Path,
+    trash_name: &OsStr,
+    path: &Path,
+    target: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let dir = trash_dir.join("rdel");
+    DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+
+    let stat = fs::symlink_metadata(target)?;
+    let metadata = TrashMetadata {
+        original_path: path.to_path_buf(),
+        deleted_at: crate::timestamp::now_rfc3339(),
+        user: user_name(),
+        command_line: env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+        mode: Some(stat.permissions().mode() & 0o7777),
+        modified: stat
+            .modified()
+            .ok()
+            .map(|modified| humantime::format_rfc3339_nanos(modified).to_string()),
+    };
+    let mut file = fs::File::create(sidecar(&dir, trash_name))?;
+    serde_json::to_writer_pretty(&mut file, &metadata)?;
+    file.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Returns the path of the sidecar file for a file in the trash.
+fn sidecar(dir: &Path, trash_name: &OsStr) -> PathBuf {
+    let mut name = trash_name.to_os_string();
+    name.push(".json");
+    dir.join(name)
+}
+
+/// Splits the path of a `.trashinfo` file into the trash directory it's in and the name of the
+/// file it describes.
+fn parts(info_file: &Path) -> Option<(&Path, &OsStr)> {
+    let trash_dir = info_file.parent()?.parent()?;
+    Some((trash_dir, info_file.file_stem()?))
+}
+
+/// Reads the details rdel recorded about the file a `.trashinfo` file describes, if it was
+/// moved to the trash by rdel.
+pub fn metadata(info_file: &Path) -> Option<TrashMetadata> {
+    let (trash_dir, name) = parts(info_file)?;
+    let text = fs::read_to_string(sidecar(&trash_dir.join("rdel"), name)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Removes the details recorded about a file that is no longer in the trash.
+pub fn forget(info_file: &Path) {
+    if let Some((trash_dir, name)) = parts(info_file) {
+        let _ = fs::remove_file(sidecar(&trash_dir.join("rdel"), name));
+    }
+}
+
+/// Moves the file a `.trashinfo` file describes to `target`, creating the directories it was in
+/// if they have gone, and removes it from the trash. The permissions and modification time are
+/// put back as they were recorded in `details`. Fails if something is already at `target`.
+pub fn restore(
+    info_file: &Path,
+    target: &Path,
+    details: Option<&TrashMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    let (trash_dir, name) = parts(info_file).ok_or("Not a file in the trash.")?;
+    if target.symlink_metadata().is_ok() {
+        return Err(format!("{} already exists.", target.display()).into());
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    transfer::move_across(&trash_dir.join("files").join(name), target)?;
+    fs::remove_file(info_file)?;
+    forget(info_file);
+
+    // Symbolic links have no permissions or times of their own to put back. The time goes
+    // first, as the permissions may not let the file be opened again
+    if let Some(details) = details.filter(|_| !target.is_symlink()) {
+        if let Some(modified) = &details.modified {
+            set_modified(target, humantime::parse_rfc3339(modified)?)?;
+        }
+        if let Some(mode) = details.mode {
+            fs::set_permissions(target, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    Ok(())
+}
+
+/// Sets the modification time of a file or directory by its path, leaving the access time
+/// alone, so it doesn't need to be opened.
+fn set_modified(path: &Path, modified: SystemTime) -> Result<(), Box<dyn Error>> {
+    use rustix::fs::{AtFlags, Timestamps, CWD, UTIME_OMIT};
+    use rustix::time::Timespec;
+
+    let since = modified.duration_since(UNIX_EPOCH)?;
+    let times = Timestamps {
+        last_access: Timespec {
+            tv_sec: 0,
+            tv_nsec: UTIME_OMIT,
+        },
+        last_modification: Timespec {
+            tv_sec: since.as_secs().try_into()?,
+            tv_nsec: since.subsec_nanos().into(),
+        },
+    };
+    rustix::fs::utimensat(CWD, path, &times, AtFlags::empty())?;
+    Ok(())
+}
+
+/// Returns the name of the current user, from the password file or else the environment.
+fn user_name() -> String {
+    let uid = rustix::process::getuid().as_raw();
+
+    // name:password:uid:gid:gecos:home:shell
+    fs::read_to_string("/etc/passwd")
+        .ok()
+        .and_then(|passwd| {
+            passwd
+                .lines()
+                .map(|line| line.split(':').collect::<Vec<_>>())
+                .find(|fields| fields.len() >= 3 && fields[2] == uid.to_string())
+                .map(|fields| fields[0].to_string())
+        })
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+/// Percent-encodes a path for the `Path` key of a `.trashinfo` file.
+fn encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    } // for byte
+    encoded
+}
+
+/// Returns the size of everything in the trash entry that `info_file` describes, e.g. a
+/// directory and all the files in it.
+pub fn trashed_size(info_file: &Path) -> u64 {
+    parts(info_file).map_or(0, |(trash_dir, name)| {
+        tree_size(&trash_dir.join("files").join(name))
+    })
+}
+
+/// Adds up the sizes of a file, or of a directory and everything below it.
+fn tree_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path).map_or(0, |entries| {
+        entries
+            .filter_map(Result::ok)
+            .map(|entry| tree_size(&entry.path()))
+            .sum()
+    })
+}
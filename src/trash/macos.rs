@@ -0,0 +1,33 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use trash::macos::{DeleteMethod, TrashContextExtMacos};
+use trash::TrashContext;
+
+/// Set once the Finder has failed to move a file, so it isn't asked again for every file.
+static FINDER_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Moves a file or directory to the user's Trash by asking the Finder to delete it, so the
+/// Finder records where it came from and "Put Back" restores it. The Finder can't be asked
+/// without a login session or the automation permission (e.g. over SSH or from cron), in which
+/// case the file is moved with `NSFileManager` instead, which macOS doesn't offer "Put Back" for.
+pub fn delete(filename: &str) -> Result<(), Box<dyn Error>> {
+    let mut context = TrashContext::default();
+    if !FINDER_FAILED.load(Ordering::Relaxed) {
+        context.set_delete_method(DeleteMethod::Finder);
+        match context.delete(filename) {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                log::warn!(
+                    "The Finder couldn't move {filename} to the Trash ({err}). Files are moved without Put Back from now on."
+                );
+                FINDER_FAILED.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    context.set_delete_method(DeleteMethod::NsFileManager);
+    context
+        .delete(filename)
+        .map_err(|err| format!("Unable to move {filename} to the Trash: {err}").into())
+}
@@ -0,0 +1,387 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+#[cfg(not(target_os = "macos"))]
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+#[cfg(not(target_os = "macos"))]
+use std::{fs, io};
+
+use crate::quarantine::Purged;
+#[cfg(not(target_os = "macos"))]
+use crate::{confirm, i18n};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub mod freedesktop;
+#[cfg(target_os = "macos")]
+pub mod macos;
+
+/// What rdel records about each file it moves to the trash, in a sidecar file next to the
+/// trash's own records, so restores put files back exactly where they were and audits can tell
+/// who deleted them and how. Only kept where rdel manages the trash itself (the freedesktop.org
+/// trash on Linux and the BSDs).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashMetadata {
+    /// The absolute path the file was deleted from.
+    pub original_path: PathBuf,
+
+    /// When the file was deleted, in RFC 3339 format, in local time or in UTC with `--utc`.
+    pub deleted_at: String,
+
+    /// The user who deleted the file.
+    pub user: String,
+
+    /// The command line of the rdel run that deleted the file.
+    pub command_line: Vec<String>,
+
+    /// The file's permissions when it was deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+
+    /// When the file was last modified before it was deleted, in RFC 3339 format (UTC).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
+
+/// What to do when restoring a file whose original path has been taken by another file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Leave the file in the trash.
+    Skip,
+
+    /// Remove the file in the way and restore the one from the trash.
+    Overwrite,
+
+    /// Restore the file under a numbered name next to the one in the way.
+    Rename,
+
+    /// Ask whether to overwrite the file in the way, and skip it if not.
+    Ask,
+}
+
+impl OnConflict {
+    /// Converts the command line value into a setting.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "overwrite" => Self::Overwrite,
+            "rename" => Self::Rename,
+            "ask" => Self::Ask,
+            _ => Self::Skip,
+        }
+    }
+}
+
+/// What happened when restoring files from the trash.
+#[derive(Debug, Default)]
+pub struct Restored {
+    pub file_count: usize,
+    pub skipped_count: usize,
+    pub failed_count: usize,
+}
+
+/// Moves the files in the trash whose original paths match any of the patterns back to where
+/// they were deleted from. When another file has taken a file's place, `on_conflict` says what
+/// to do; without it, the file is left in the trash and counted as failed, as are files that
+/// can't be restored for other reasons.
+#[cfg(not(target_os = "macos"))]
+pub fn restore(
+    patterns: &[glob::Pattern],
+    on_conflict: Option<OnConflict>,
+    dry_run: bool,
+) -> Result<Restored, Box<dyn Error>> {
+    let mut restored = Restored::default();
+
+    for item in list()? {
+        let details = details_of(&item);
+        let original = details.as_ref().map_or_else(
+            || item.original_path(),
+            |details| details.original_path.clone(),
+        );
+        if !patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(&original))
+        {
+            continue;
+        }
+
+        let deleted_by = details.as_ref().map_or_else(String::new, |details| {
+            format!(
+                ", deleted by {} at {} with: {}",
+                details.user,
+                details.deleted_at,
+                details.command_line.join(" ")
+            )
+        });
+
+        let (mut target, mut overwrite) = (original.clone(), false);
+        if original.symlink_metadata().is_ok() {
+            match on_conflict {
+                None => {
+                    log::warn!(
+                        "Unable to restore {}: it already exists. Use --on-conflict to say what to do.",
+                        original.display()
+                    );
+                    restored.failed_count += 1;
+                    continue;
+                }
+                Some(OnConflict::Rename) => target = free_name(&original),
+                Some(OnConflict::Overwrite) => overwrite = true,
+                Some(OnConflict::Ask) if dry_run => {
+                    log::info!(
+                        "Would ask whether to overwrite {}{deleted_by}",
+                        original.display()
+                    );
+                    restored.file_count += 1;
+                    continue;
+                }
+                Some(OnConflict::Ask) => {
+                    overwrite = confirm::ask(&i18n::tf(
+                        "{path} already exists. Overwrite it?",
+                        &[("path", &original.display())],
+                    ))?;
+                }
+                Some(OnConflict::Skip) => {}
+            }
+            if !overwrite && target == original {
+                log::info!("Skipping {}: it already exists.", original.display());
+                restored.skipped_count += 1;
+                continue;
+            }
+        }
+
+        if dry_run {
+            let overwriting = if overwrite {
+                ", overwriting the file there"
+            } else {
+                ""
+            };
+            log::info!(
+                "Would restore {}{overwriting}{deleted_by}",
+                target.display()
+            );
+            restored.file_count += 1;
+            continue;
+        }
+
+        let result = match overwrite {
+            true => remove_existing(&target).map_err(Box::<dyn Error>::from),
+            false => Ok(()),
+        };
+        match result.and_then(|()| restore_item(item, &target, details.as_ref())) {
+            Ok(()) => {
+                log::info!("Restored {}{deleted_by}", target.display());
+                restored.file_count += 1;
+            }
+            Err(err) => {
+                log::warn!("Unable to restore {}: {err}", original.display());
+                restored.failed_count += 1;
+            }
+        }
+    } // for item
+
+    Ok(restored)
+}
+
+/// Returns the first numbered name next to `path` that isn't taken, e.g. `report.pdf.1`.
+#[cfg(not(target_os = "macos"))]
+fn free_name(path: &Path) -> PathBuf {
+    let mut counter = 1;
+    loop {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{counter}"));
+        let target = path.with_file_name(name);
+        if target.symlink_metadata().is_err() {
+            return target;
+        }
+        counter += 1;
+    } // loop
+}
+
+/// Removes the file or directory in the way of a file being restored.
+#[cfg(not(target_os = "macos"))]
+fn remove_existing(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Returns the details rdel recorded about a file when moving it to the trash, if it did.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn details_of(item: &::trash::TrashItem) -> Option<TrashMetadata> {
+    freedesktop::metadata(Path::new(&item.id))
+}
+
+#[cfg(windows)]
+fn details_of(_item: &::trash::TrashItem) -> Option<TrashMetadata> {
+    None
+}
+
+/// Moves a file in the trash to `target`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn restore_item(
+    item: ::trash::TrashItem,
+    target: &Path,
+    details: Option<&TrashMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    freedesktop::restore(Path::new(&item.id), target, details)
+}
+
+#[cfg(windows)]
+fn restore_item(
+    item: ::trash::TrashItem,
+    target: &Path,
+    _details: Option<&TrashMetadata>,
+) -> Result<(), Box<dyn Error>> {
+    if target != item.original_path() {
+        return Err("Restoring under another name is not supported on Windows.".into());
+    }
+    Ok(::trash::os_limited::restore_all([item])?)
+}
+
+/// Permanently removes files from the trash, along with the details recorded about them.
+#[cfg(not(target_os = "macos"))]
+fn purge_items(items: Vec<::trash::TrashItem>) -> Result<(), Box<dyn Error>> {
+    #[cfg(unix)]
+    let ids: Vec<_> = items.iter().map(|item| item.id.clone()).collect();
+    ::trash::os_limited::purge_all(items)?;
+    #[cfg(unix)]
+    for id in ids {
+        freedesktop::forget(Path::new(&id));
+    } // for id
+    Ok(())
+}
+
+/// Lists the files in the trash.
+#[cfg(not(target_os = "macos"))]
+fn list() -> Result<Vec<::trash::TrashItem>, Box<dyn Error>> {
+    ::trash::os_limited::list().map_err(|err| format!("Unable to list the trash: {err}").into())
+}
+
+#[cfg(target_os = "macos")]
+pub fn restore(
+    _patterns: &[glob::Pattern],
+    _on_conflict: Option<OnConflict>,
+    _dry_run: bool,
+) -> Result<Restored, Box<dyn Error>> {
+    Err("Restoring files from the trash is not supported on macOS.".into())
+}
+
+/// Permanently removes the files deleted to the trash longer than `retain` before `now`.
+#[cfg(not(target_os = "macos"))]
+pub fn purge(retain: Duration, now: SystemTime, dry_run: bool) -> Result<Purged, Box<dyn Error>> {
+    let cutoff = cutoff(now, retain);
+
+    let mut purged = Purged::default();
+    let mut expired = Vec::new();
+    for item in list()? {
+        if item.time_deleted >= cutoff {
+            continue;
+        }
+
+        let size = size_of(&item);
+        log::info!(
+            "Purging from the trash: {} ({size} bytes).",
+            item.original_path().display()
+        );
+
+        purged.file_count += 1;
+        purged.size += size;
+        expired.push(item);
+    } // for item
+
+    if !dry_run && !expired.is_empty() {
+        purge_items(expired)?;
+    }
+
+    Ok(purged)
+}
+
+/// Permanently removes the oldest files from the trash until none were deleted longer than
+/// `max_age` before `now`, and the rest take up no more than `max_size` bytes.
+#[cfg(not(target_os = "macos"))]
+pub fn enforce_limits(
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+    now: SystemTime,
+    dry_run: bool,
+) -> Result<Purged, Box<dyn Error>> {
+    let cutoff = max_age.map(|max_age| cutoff(now, max_age));
+
+    let mut items: Vec<_> = list()?
+        .into_iter()
+        .map(|item| {
+            let size = size_of(&item);
+            (item, size)
+        })
+        .collect();
+    items.sort_by_key(|(item, _)| item.time_deleted);
+    let mut total: u64 = items.iter().map(|(_, size)| size).sum();
+
+    let mut purged = Purged::default();
+    let mut expired = Vec::new();
+    for (item, size) in items {
+        // Oldest first, so once a file can stay, so can all the newer ones
+        let too_old = cutoff.is_some_and(|cutoff| item.time_deleted < cutoff);
+        let too_large = max_size.is_some_and(|max_size| total > max_size);
+        if !too_old && !too_large {
+            break;
+        }
+
+        log::info!(
+            "Purging from the trash to keep within its limits: {} ({size} bytes).",
+            item.original_path().display()
+        );
+        total -= size;
+        purged.file_count += 1;
+        purged.size += size;
+        expired.push(item);
+    } // for item
+
+    if !dry_run && !expired.is_empty() {
+        purge_items(expired)?;
+    }
+
+    Ok(purged)
+}
+
+/// Returns the deletion time, in seconds since the epoch, before which files are older than
+/// `age` at `now`.
+#[cfg(not(target_os = "macos"))]
+fn cutoff(now: SystemTime, age: Duration) -> i64 {
+    now.checked_sub(age)
+        .and_then(|cutoff| cutoff.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map_or(0, |cutoff| cutoff.as_secs() as i64)
+}
+
+/// Returns the size of a file in the trash, or of everything in a directory where the trash
+/// can be looked into (i.e. not on Windows, where directories count as empty).
+#[cfg(not(target_os = "macos"))]
+fn size_of(item: &::trash::TrashItem) -> u64 {
+    match ::trash::os_limited::metadata(item).map(|metadata| metadata.size) {
+        Ok(::trash::TrashItemSize::Bytes(size)) => size,
+        #[cfg(unix)]
+        Ok(::trash::TrashItemSize::Entries(_)) => freedesktop::trashed_size(Path::new(&item.id)),
+        _ => 0,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn purge(
+    _retain: Duration,
+    _now: SystemTime,
+    _dry_run: bool,
+) -> Result<Purged, Box<dyn Error>> {
+    Err("Purging the trash is not supported on macOS.".into())
+}
+
+#[cfg(target_os = "macos")]
+pub fn enforce_limits(
+    _max_size: Option<u64>,
+    _max_age: Option<Duration>,
+    _now: SystemTime,
+    _dry_run: bool,
+) -> Result<Purged, Box<dyn Error>> {
+    Err("Limiting the size of the trash is not supported on macOS.".into())
+}
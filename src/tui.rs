@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::i18n;
+use crate::observer::Observer;
+use crate::report::{FileReport, Outcome};
+use crate::utils;
+
+/// The least time between redraws, so the terminal keeps up with fast runs.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many of the latest warnings and errors are shown.
+const TICKER_LINES: usize = 5;
+
+/// Whether the dashboard is on the screen, in place of the log.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether the dashboard has been taken down for good, at the end of the run.
+static CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// The latest warnings and errors logged while the dashboard is up.
+static TICKER: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Tells whether the dashboard is on the screen, in which case the log isn't written.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Adds a warning or error logged while the dashboard is up to the ones it shows.
+pub fn tick(message: String) {
+    if let Ok(mut ticker) = TICKER.lock() {
+        if ticker.len() == TICKER_LINES {
+            ticker.pop_front();
+        }
+        ticker.push_back(message);
+    }
+}
+
+/// Takes the dashboard down and goes back to the normal screen and the log, e.g. before the
+/// summary is printed. It isn't put up again.
+pub fn close() {
+    CLOSED.store(true, Ordering::Relaxed);
+    if ACTIVE.swap(false, Ordering::Relaxed) {
+        // Shows the cursor again and leaves the alternate screen
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// A full-screen dashboard shown in place of the scrolling log for `--tui`: the overall
+/// progress, the file being removed, the bytes freed so far and the latest warnings and errors.
+/// It's put up once the first files are found, so questions asked before then (e.g. about
+/// removing the home directory) can still be seen.
+#[derive(Debug)]
+pub struct Dashboard {
+    started: Instant,
+    drawn: Option<Instant>,
+    found: usize,
+    found_bytes: u64,
+    done: usize,
+    done_bytes: u64,
+    freed: u64,
+    skipped: usize,
+    failed: usize,
+    current: Option<String>,
+}
+
+impl Default for Dashboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dashboard {
+    /// Creates the dashboard. It isn't put up until the first files are found.
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            drawn: None,
+            found: 0,
+            found_bytes: 0,
+            done: 0,
+            done_bytes: 0,
+            freed: 0,
+            skipped: 0,
+            failed: 0,
+            current: None,
+        }
+    }
+
+    /// Puts the dashboard up, unless it's already up or has been taken down.
+    fn open(&self) {
+        if CLOSED.load(Ordering::Relaxed) || ACTIVE.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        // Switches to the alternate screen and hides the cursor
+        print!("\x1b[?1049h\x1b[?25l");
+    }
+
+    /// Redraws the dashboard, if it's up and it hasn't just been drawn.
+    fn draw(&mut self) {
+        if !is_active()
+            || self
+                .drawn
+                .is_some_and(|drawn| drawn.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.drawn = Some(Instant::now());
+
+        let width = terminal_size::terminal_size()
+            .map_or(80, |(terminal_size::Width(width), _)| usize::from(width));
+        let total = self.found.max(self.done);
+        let total_bytes = self.found_bytes.max(self.done_bytes);
+        let fraction = if total == 0 {
+            0.0
+        } else {
+            self.done as f64 / total as f64
+        };
+        let bar_width = width.saturating_sub(40).clamp(10, 50);
+        let filled = (fraction * bar_width as f64) as usize;
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        let mut line = |text: String| {
+            screen.push_str(&truncate(&text, width));
+            screen.push_str("\r\n");
+        };
+        line(format!(
+            "rdel  {}",
+            humantime::format_duration(Duration::from_secs(self.started.elapsed().as_secs()))
+        ));
+        line(String::new());
+        line(format!(
+            "{:<14}[{}{}] {:>3.0}%  {} / {}",
+            i18n::t("Files:"),
+            "#".repeat(filled),
+            "-".repeat(bar_width - filled),
+            fraction * 100.0,
+            utils::thousand_separated(self.done),
+            utils::thousand_separated(total)
+        ));
+        line(format!(
+            "{:<14}{} / {}",
+            i18n::t("Bytes:"),
+            utils::thousand_separated(self.done_bytes),
+            utils::thousand_separated(total_bytes)
+        ));
+        line(format!(
+            "{:<14}{}",
+            i18n::t("Freed:"),
+            utils::thousand_separated(self.freed)
+        ));
+        line(format!(
+            "{:<14}{}    {} {}",
+            i18n::t("Skipped:"),
+            utils::thousand_separated(self.skipped),
+            i18n::t("Failed:"),
+            utils::thousand_separated(self.failed)
+        ));
+        line(format!(
+            "{:<14}{}",
+            i18n::t("Removing:"),
+            self.current.as_deref().unwrap_or_default()
+        ));
+        line(String::new());
+        line(i18n::t("Latest warnings and errors:").to_string());
+        if let Ok(ticker) = TICKER.lock() {
+            for message in ticker.iter() {
+                line(format!("  {message}"));
+            } // for message
+        }
+
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(screen.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+/// Shortens text to fit the width of the terminal, keeping the end, as that's the part of a
+/// path that tells files apart.
+fn truncate(text: &str, width: usize) -> String {
+    let length = text.chars().count();
+    if length <= width {
+        return text.to_string();
+    }
+    let kept: String = text
+        .chars()
+        .skip(length - width.saturating_sub(1))
+        .collect();
+    format!("…{kept}")
+}
+
+impl Observer for Dashboard {
+    fn on_found(&mut self, count: usize, size: u64) {
+        self.open();
+        self.found += count;
+        self.found_bytes += size;
+        self.draw();
+    }
+
+    fn on_file_start(&mut self, path: &str, _size: u64) {
+        self.open();
+        self.current = Some(path.to_string());
+        self.draw();
+    }
+
+    fn on_file_done(&mut self, file: &FileReport) {
+        match file.outcome {
+            Outcome::Removed => {
+                self.done += 1;
+                self.done_bytes += file.size;
+                self.freed += file.size;
+            }
+            Outcome::WouldRemove => {
+                self.done += 1;
+                self.done_bytes += file.size;
+            }
+            Outcome::Failed => {
+                self.done += 1;
+                self.done_bytes += file.size;
+                self.failed += 1;
+            }
+            Outcome::Skipped => self.skipped += 1,
+        }
+        self.draw();
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        close();
+    }
+}
@@ -1,6 +1,9 @@
 use clap::parser::ValueSource;
 use env_logger::{Builder, Target};
 use log::LevelFilter;
+use std::io::Write;
+
+use crate::timestamp;
 
 pub fn log_build(cli_args: &clap::ArgMatches) -> Builder {
     // create a log builder
@@ -10,16 +13,119 @@ pub fn log_build(cli_args: &clap::ArgMatches) -> Builder {
     if cli_args.value_source("quiet") == Some(ValueSource::CommandLine) {
         logbuilder.filter_level(LevelFilter::Off);
     } else {
+        // Like rm, --rm-compat only shows errors unless asked for more
+        let rm_quiet = cli_args.value_source("rm-compat") == Some(ValueSource::CommandLine)
+            && !["verbose", "dry-run", "print-summary"]
+                .iter()
+                .any(|id| cli_args.value_source(id) == Some(ValueSource::CommandLine));
         match cli_args.get_count("debug") {
+            0 if rm_quiet => logbuilder.filter_level(LevelFilter::Warn),
             0 => logbuilder.filter_level(LevelFilter::Info),
             1 => logbuilder.filter_level(LevelFilter::Debug),
             _ => logbuilder.filter_level(LevelFilter::Trace),
         };
     }
 
+    // Stamp the messages with the times as set with --time-format and --utc
+    logbuilder.format(|buf, record| {
+        // The --tui dashboard takes the place of the log, and shows the warnings and errors itself
+        if crate::tui::is_active() {
+            if record.level() <= log::Level::Warn {
+                crate::tui::tick(record.args().to_string());
+            }
+            return Ok(());
+        }
+
+        let style = buf.default_level_style(record.level());
+        writeln!(
+            buf,
+            "[{} {style}{:<5}{style:#} {}] {}",
+            timestamp::now(),
+            record.level(),
+            record.target(),
+            record.args()
+        )
+    });
+
     // Initialize logging
     logbuilder.target(Target::Stdout).init();
 
     // return the log builder
     logbuilder
 }
+
+/// Pretty-prints integer values;
+/// Examples:
+///
+/// ```
+/// # use rdel::utils::thousand_separated;
+/// assert_eq!(thousand_separated(10000), "10,000".to_string());
+/// assert_eq!(thousand_separated(10000000), "10,000,000".to_string());
+/// ```
+pub fn thousand_separated<T>(val: T) -> String
+where
+    T: std::fmt::Display,
+{
+    let s = val.to_string();
+    let bytes: Vec<_> = s.bytes().rev().collect();
+    let chunks: Vec<_> = bytes
+        .chunks(3)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or("0"))
+        .collect();
+    let result: Vec<_> = chunks.join(",").bytes().rev().collect();
+    let default = String::from("NaN");
+    String::from_utf8(result).unwrap_or(default)
+}
+
+/// Parses a human-readable size such as `512`, `100K`, `64MB` or `2GiB` into a number of bytes.
+/// Suffixes are case insensitive and use powers of 1024.
+///
+/// ```
+/// # use rdel::utils::parse_size;
+/// assert_eq!(parse_size("64M"), Ok(67_108_864));
+/// assert_eq!(parse_size("1kb"), Ok(1024));
+/// ```
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {value}"))?;
+
+    let multiplier: u64 = match suffix.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("Invalid size suffix: {value}")),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size too large: {value}"))
+}
+
+/// Parses a number of files such as `10000` or `10000files`.
+///
+/// ```
+/// # use rdel::utils::parse_file_count;
+/// assert_eq!(parse_file_count("10000files"), Ok(10_000));
+/// assert_eq!(parse_file_count("25"), Ok(25));
+/// ```
+pub fn parse_file_count(value: &str) -> Result<usize, String> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("files")
+        .or_else(|| value.strip_suffix("file"))
+        .unwrap_or(value);
+
+    number
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid number of files: {value}"))
+}
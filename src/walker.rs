@@ -0,0 +1,1089 @@
+use std::error::Error;
+use std::path::Path;
+
+/// What was found (or removed) in a directory tree.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TreeStats {
+    /// Everything but the directories, including the symbolic links and special files.
+    pub file_count: usize,
+    pub dir_count: usize,
+    pub symlink_count: usize,
+
+    /// Devices, sockets and named pipes.
+    pub special_count: usize,
+
+    /// The special files left where they were, along with the directories they are in.
+    pub special_kept_count: usize,
+
+    /// The links to directories left where they were, with `--dir-links skip`.
+    pub links_kept_count: usize,
+
+    /// The directories left where they were, top of the tree included, as special files or links
+    /// to directories were kept in them.
+    pub dirs_kept_count: usize,
+    pub size: u64,
+}
+
+/// What is done with the devices, sockets and named pipes in a directory tree being removed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Special {
+    /// Leave them, along with the directories they are in, with a warning.
+    #[default]
+    Skip,
+
+    /// Remove them like any other file.
+    Delete,
+}
+
+impl Special {
+    /// Converts the command line value into a `Special`. Unknown values skip.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "delete" => Self::Delete,
+            _ => Self::Skip,
+        }
+    }
+}
+
+/// What is done with the links to directories (directory symbolic links and junctions) in a
+/// directory tree being removed on Windows. On Unix, symbolic links are always removed, never
+/// followed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DirLinks {
+    /// Remove the link, leaving the directory it points to alone.
+    #[default]
+    Remove,
+
+    /// Leave the link, along with the directories it's in, with a warning.
+    Skip,
+
+    /// Remove what is in the directory the link points to as well, then the link.
+    Follow,
+}
+
+impl DirLinks {
+    /// Converts the command line value into a `DirLinks`. Unknown values remove the links.
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "skip" => Self::Skip,
+            "follow" => Self::Follow,
+            _ => Self::Remove,
+        }
+    }
+}
+
+/// Tells whether a file is a link to a directory: a directory symbolic link or junction on
+/// Windows, which is removed as a directory, without touching what it points to. Other systems
+/// have no such links.
+pub fn is_dir_link(metadata: &std::fs::Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+        metadata.is_symlink() && metadata.file_attributes() & FILE_ATTRIBUTE_DIRECTORY != 0
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+/// Adds up the files and directories below `dir`, without changing anything. The directories
+/// links point to are included if `dir_links` says to follow them.
+pub fn measure(dir: &Path, dir_links: DirLinks) -> Result<TreeStats, Box<dyn Error>> {
+    walk(dir, false, Special::Delete, dir_links)
+}
+
+/// Removes `dir` along with everything in it. Symbolic links to files are removed, never
+/// followed; links to directories are dealt with as `dir_links` says. Special files are dealt
+/// with as `special` says.
+pub fn remove_tree(
+    dir: &Path,
+    special: Special,
+    dir_links: DirLinks,
+) -> Result<TreeStats, Box<dyn Error>> {
+    walk(dir, true, special, dir_links)
+}
+
+/// Removes the directory `name` below the open directory `parent`, along with everything in it.
+/// `path` is the full path of the directory, used in error messages.
+#[cfg(unix)]
+pub fn remove_tree_at(
+    parent: std::os::fd::BorrowedFd,
+    name: &Path,
+    path: &Path,
+    special: Special,
+) -> Result<TreeStats, Box<dyn Error>> {
+    parallel::walk_at(parent, name, path, true, special)
+}
+
+#[cfg(unix)]
+fn walk(
+    dir: &Path,
+    remove: bool,
+    special: Special,
+    _dir_links: DirLinks,
+) -> Result<TreeStats, Box<dyn Error>> {
+    parallel::walk_at(rustix::fs::CWD, dir, dir, remove, special)
+}
+
+/// The Unix walker works relative to open directory handles (`openat`, `fstatat` and `unlinkat`),
+/// so no path longer than a single file name is ever passed to the kernel. Trees of any depth
+/// can be removed without hitting PATH_MAX, and the kernel doesn't have to look up every
+/// directory on the way down again for each file. On Linux, directories are read with
+/// `getdents64` into a large buffer, so one with millions of entries takes a few hundred calls
+/// rather than one per handful of entries.
+#[cfg(unix)]
+mod unix {
+    use super::{Special, TreeStats};
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    use rustix::fs::Dir;
+    use rustix::fs::{self as rfs, AtFlags, FileType, Mode, OFlags, Stat};
+    use rustix::io::Errno;
+    use std::error::Error;
+    use std::ffi::{CStr, CString};
+    use std::os::fd::{BorrowedFd, OwnedFd};
+    use std::path::{Path, PathBuf};
+
+    /// How many directory handles are kept open at once at most. Handles further up the tree
+    /// are closed while deep directories are being worked on, and reopened on the way back up.
+    pub(super) const MAX_OPEN_DIRS: usize = 64;
+
+    /// The size of the buffer directories are read into on Linux, enough for several thousand
+    /// entries at a time. It's shared by all the directories in a walk.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const READ_BUFFER_SIZE: usize = 256 << 10;
+
+    /// A directory on the way down from the top of the tree.
+    struct Level {
+        /// The open directory, unless it was closed to save file descriptors.
+        fd: Option<OwnedFd>,
+
+        /// The device and inode numbers, used to make sure the same directory is reopened.
+        id: (u64, u64),
+
+        /// The name of the directory in its parent.
+        name: CString,
+
+        /// The subdirectories still to be visited.
+        pending: Vec<CString>,
+
+        /// Whether special files were left in the directory (or below it), so it can't be
+        /// removed.
+        kept: bool,
+    }
+
+    /// Walks the tree below the directory `name` in `parent`, removing it and everything in it
+    /// if `remove` is set, apart from the special files `special` says to keep. `dir` is the full
+    /// path of the directory, for error messages. No more than `max_open` handles are kept open,
+    /// and one more for a moment on the way back up. Also returns whether special files were
+    /// kept, in which case the directory is still there.
+    pub fn walk_tree<P: rustix::path::Arg + Copy>(
+        parent: BorrowedFd,
+        name: P,
+        dir: &Path,
+        remove: bool,
+        special: Special,
+        max_open: usize,
+    ) -> Result<(TreeStats, bool), Box<dyn Error>> {
+        let mut stats = TreeStats::default();
+        let mut buf = read_buffer();
+        let root = open_dir(parent, name).map_err(|err| context(dir, &[], None, err))?;
+        let mut stack = vec![enter(
+            root,
+            CString::default(),
+            remove,
+            special,
+            &mut buf,
+            &mut stats,
+        )
+        .map_err(|err| context(dir, &[], None, err))?];
+        let mut kept = false;
+
+        while let Some(level) = stack.last_mut() {
+            // Go down into the next subdirectory
+            if let Some(child) = level.pending.pop() {
+                let fd = level.fd.as_ref().ok_or("Directory handle closed.")?;
+                let child_fd = match open_dir(fd, &child) {
+                    Ok(child_fd) => child_fd,
+                    Err(Errno::NOENT) => continue,
+                    Err(err) => return Err(context(dir, &stack, Some(&child), err)),
+                };
+                let level = enter(
+                    child_fd,
+                    child.clone(),
+                    remove,
+                    special,
+                    &mut buf,
+                    &mut stats,
+                )
+                .map_err(|err| context(dir, &stack, Some(&child), err))?;
+                stack.push(level);
+
+                if let Some(level) = stack.iter_mut().rev().nth(max_open.max(1)) {
+                    level.fd = None;
+                }
+                continue;
+            }
+
+            // Done with this directory, so go back up and remove it from its parent
+            let level = stack.pop().ok_or("Directory stack is empty.")?;
+            let Some(parent) = stack.last_mut() else {
+                // The top of the tree is removed from the directory it's in below
+                kept = level.kept;
+                break;
+            };
+            stats.dir_count += 1;
+
+            // A directory with special files left in it stays, and so do the ones above it
+            if level.kept {
+                stats.dirs_kept_count += 1;
+                parent.kept = true;
+                continue;
+            }
+
+            let child_fd = level.fd.as_ref().ok_or("Directory handle closed.")?;
+            if parent.fd.is_none() {
+                parent.fd = Some(
+                    reopen_parent(child_fd, parent.id)
+                        .map_err(|err| context(dir, &[], None, err))?,
+                );
+            }
+            if remove {
+                let parent_fd = parent.fd.as_ref().ok_or("Directory handle closed.")?;
+                match rfs::unlinkat(parent_fd, level.name.as_c_str(), AtFlags::REMOVEDIR) {
+                    Ok(()) | Err(Errno::NOENT) => {}
+                    Err(err) => return Err(context(dir, &stack, Some(&level.name), err)),
+                }
+            }
+        } // while level
+
+        if kept {
+            stats.dirs_kept_count += 1;
+        } else if remove {
+            rfs::unlinkat(parent, name, AtFlags::REMOVEDIR)
+                .map_err(|err| context(dir, &[], None, err))?;
+        }
+
+        Ok((stats, kept))
+    }
+
+    /// Opens a directory below `parent` without following symbolic links.
+    pub(super) fn open_dir<Fd: std::os::fd::AsFd, P: rustix::path::Arg>(
+        parent: Fd,
+        name: P,
+    ) -> Result<OwnedFd, Errno> {
+        rfs::openat(
+            parent,
+            name,
+            OFlags::RDONLY | OFlags::DIRECTORY | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+            Mode::empty(),
+        )
+    }
+
+    /// Returns the buffer the directories in a walk are read into.
+    pub(super) fn read_buffer() -> Vec<u8> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return Vec::with_capacity(READ_BUFFER_SIZE);
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        Vec::new()
+    }
+
+    /// Calls `f` with the name and type of each entry in a directory, apart from `.` and `..`.
+    /// On Linux, the entries are read into `buf` with `getdents64`, as many at a time as fit;
+    /// elsewhere, with `readdir`, and `buf` isn't used.
+    pub(super) fn read_dir(
+        fd: &OwnedFd,
+        buf: &mut Vec<u8>,
+        mut f: impl FnMut(&CStr, FileType) -> Result<(), Errno>,
+    ) -> Result<(), Errno> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let mut entries = rfs::RawDir::new(fd, buf.spare_capacity_mut());
+            while let Some(entry) = entries.next() {
+                let entry = entry?;
+                let file_name = entry.file_name();
+                if file_name != c"." && file_name != c".." {
+                    f(file_name, entry.file_type())?;
+                }
+            } // while entry
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let _ = buf;
+            for entry in Dir::read_from(fd)? {
+                let entry = entry?;
+                let file_name = entry.file_name();
+                if file_name != c"." && file_name != c".." {
+                    f(file_name, entry.file_type())?;
+                }
+            } // for entry
+        }
+
+        Ok(())
+    }
+
+    /// Reads a directory, counting (and removing if asked) everything but its subdirectories,
+    /// which are returned to be visited.
+    fn enter(
+        fd: OwnedFd,
+        name: CString,
+        remove: bool,
+        special: Special,
+        buf: &mut Vec<u8>,
+        stats: &mut TreeStats,
+    ) -> Result<Level, Errno> {
+        let stat = rfs::fstat(&fd)?;
+        let mut pending = Vec::new();
+        let mut kept = false;
+
+        read_dir(&fd, buf, |file_name, file_type| {
+            let file_type = match file_type {
+                FileType::Unknown => FileType::from_raw_mode(
+                    rfs::statat(&fd, file_name, AtFlags::SYMLINK_NOFOLLOW)?.st_mode as _,
+                ),
+                file_type => file_type,
+            };
+            if file_type == FileType::Directory {
+                pending.push(file_name.to_owned());
+                return Ok(());
+            }
+
+            let is_special = !matches!(file_type, FileType::RegularFile | FileType::Symlink);
+            if remove && is_special && special == Special::Skip {
+                log::debug!(
+                    "Leaving special file {} alone.",
+                    file_name.to_string_lossy()
+                );
+                stats.special_kept_count += 1;
+                kept = true;
+                return Ok(());
+            }
+
+            let size = rfs::statat(&fd, file_name, AtFlags::SYMLINK_NOFOLLOW)?.st_size;
+            if remove {
+                rfs::unlinkat(&fd, file_name, AtFlags::empty())?;
+            }
+            stats.file_count += 1;
+            match file_type {
+                FileType::RegularFile => {}
+                FileType::Symlink => stats.symlink_count += 1,
+                _ => stats.special_count += 1,
+            }
+            stats.size += u64::try_from(size).unwrap_or_default();
+            Ok(())
+        })?;
+
+        Ok(Level {
+            fd: Some(fd),
+            id: id_of(&stat),
+            name,
+            pending,
+            kept,
+        })
+    }
+
+    /// Reopens the parent of an open directory, checking that it is still the directory
+    /// that was there on the way down.
+    fn reopen_parent(child: &OwnedFd, id: (u64, u64)) -> Result<OwnedFd, Errno> {
+        let fd = open_dir(child, c"..")?;
+        let stat = rfs::fstat(&fd)?;
+        if id_of(&stat) != id {
+            return Err(Errno::STALE);
+        }
+        Ok(fd)
+    }
+
+    /// Returns the device and inode numbers from a directory's metadata.
+    // The field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    fn id_of(stat: &Stat) -> (u64, u64) {
+        (stat.st_dev as u64, stat.st_ino as u64)
+    }
+
+    /// Describes an error with the path it happened at, built from the directory names.
+    fn context(root: &Path, stack: &[Level], name: Option<&CStr>, err: Errno) -> Box<dyn Error> {
+        let mut path = PathBuf::from(root);
+        for level in stack.iter().skip(1) {
+            path.push(level.name.to_string_lossy().as_ref());
+        }
+        if let Some(name) = name {
+            path.push(name.to_string_lossy().as_ref());
+        }
+        Box::new(describe(&path, err))
+    }
+
+    /// Describes an error with the path it happened at, keeping its kind so e.g. permission
+    /// being denied can still be told apart.
+    pub(super) fn describe(path: &Path, err: Errno) -> std::io::Error {
+        let err = std::io::Error::from(err);
+        std::io::Error::new(err.kind(), format!("{}: {err}", path.display()))
+    }
+}
+
+/// The parallel walker spreads the directories of a tree over several threads. Each thread
+/// takes the next directory from its own queue, stealing from the others when it runs out, and
+/// hands the files it finds to the calling thread through a bounded channel to be removed. The
+/// files are removed while the rest of the tree is still being read, and each directory is
+/// removed as soon as everything in it is gone. Threads are only started as there is work
+/// waiting for them, so small trees are walked by one.
+///
+/// Each directory stays open until everything in it is removed, so the handles are counted: the
+/// walk takes up no more than half of the file descriptors the process may have open. Below
+/// `MAX_PARALLEL_DEPTH`, or once half of those handles are open, each subtree is left to the
+/// sequential walker, which makes do with its share of the other half.
+#[cfg(unix)]
+mod parallel {
+    use super::unix::{self, describe};
+    use super::{Special, TreeStats};
+    use rustix::fs::{self as rfs, AtFlags, FileType};
+    use rustix::io::Errno;
+    use std::collections::VecDeque;
+    use std::error::Error;
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::mpsc::{self, SyncSender};
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::thread::{self, Scope};
+    use std::time::Duration;
+
+    /// The most threads reading directories at once.
+    const MAX_THREADS: usize = 8;
+
+    /// How many directories a thread has waiting before another one is started to help.
+    const SPAWN_BACKLOG: usize = 8;
+
+    /// How far below the top of the tree directories are spread over the threads.
+    const MAX_PARALLEL_DEPTH: usize = 16;
+
+    /// How many files found can be waiting to be removed.
+    const QUEUE_SIZE: usize = 4096;
+
+    /// How long a thread without work waits before looking for some again.
+    const IDLE_WAIT: Duration = Duration::from_micros(100);
+
+    /// The most directory handles a walk keeps open, however many file descriptors it may use.
+    const MAX_HANDLES: usize = 8192;
+
+    /// A directory that has been opened. It's removed from its parent once everything in it is.
+    struct Node {
+        fd: OwnedFd,
+
+        /// The count of open handles of the walk, which this one is taken off when it's closed.
+        open: Arc<AtomicUsize>,
+
+        parent: Option<Arc<Node>>,
+        name: CString,
+        depth: usize,
+
+        /// What is left to do before the directory can be removed: reading it, and removing
+        /// each of the files and subdirectories found in it.
+        pending: AtomicUsize,
+
+        /// Whether special files were left in the directory (or below it), so it can't be
+        /// removed.
+        kept: AtomicBool,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// A directory waiting to be read.
+    enum Work {
+        /// The top of the tree, which is already open.
+        Top(Arc<Node>),
+
+        /// A subdirectory found in a directory that was read.
+        Below(Arc<Node>, CString),
+    }
+
+    /// A file found, to be removed by the calling thread.
+    struct Found {
+        dir: Arc<Node>,
+        name: CString,
+    }
+
+    /// What the threads walking a tree share.
+    struct Walk {
+        dir: PathBuf,
+        remove: bool,
+        special: Special,
+
+        /// The directories waiting to be read by each thread.
+        queues: Vec<Mutex<VecDeque<Work>>>,
+
+        /// The directories waiting or being read. The walk is over when there are none.
+        outstanding: AtomicUsize,
+
+        /// How many threads have been started.
+        threads: AtomicUsize,
+
+        /// How many directories are open, and how many may be before subtrees are left to the
+        /// sequential walker.
+        open: Arc<AtomicUsize>,
+        max_open: usize,
+
+        /// How many handles each thread's sequential walker may keep open.
+        max_open_below: usize,
+
+        /// The first error, which stops the walk.
+        error: Mutex<Option<io::Error>>,
+        failed: AtomicBool,
+        stats: Mutex<TreeStats>,
+
+        /// The directories left as special files were kept in them, which are counted as they
+        /// are finished with rather than by the thread that read them.
+        dirs_kept: AtomicUsize,
+    }
+
+    /// Walks the tree below the directory `name` in `parent` like `unix::walk_tree`, removing
+    /// it and everything in it if `remove` is set, apart from the special files `special` says
+    /// to keep. `dir` is the full path of the directory, for error messages.
+    pub fn walk_at<P: rustix::path::Arg + Copy>(
+        parent: BorrowedFd,
+        name: P,
+        dir: &Path,
+        remove: bool,
+        special: Special,
+    ) -> Result<TreeStats, Box<dyn Error>> {
+        walk_within(parent, name, dir, remove, special, handles_allowed())
+    }
+
+    /// Walks the tree like `walk_at`, keeping no more than `handles` directory handles open.
+    pub(super) fn walk_within<P: rustix::path::Arg + Copy>(
+        parent: BorrowedFd,
+        name: P,
+        dir: &Path,
+        remove: bool,
+        special: Special,
+        handles: usize,
+    ) -> Result<TreeStats, Box<dyn Error>> {
+        let fd = unix::open_dir(parent, name).map_err(|err| describe(dir, err))?;
+        let open = Arc::new(AtomicUsize::new(1));
+        let top = Arc::new(Node {
+            fd,
+            open: Arc::clone(&open),
+            parent: None,
+            name: CString::default(),
+            depth: 0,
+            pending: AtomicUsize::new(1),
+            kept: AtomicBool::new(false),
+        });
+
+        let threads = thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .clamp(1, MAX_THREADS);
+        let walk = Walk {
+            dir: dir.to_path_buf(),
+            remove,
+            special,
+            queues: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            outstanding: AtomicUsize::new(1),
+            threads: AtomicUsize::new(1),
+            open,
+            max_open: (handles / 2).max(1),
+            // A sequential walker needs one more handle than it keeps open, on the way back up
+            max_open_below: (handles / 2 / threads)
+                .saturating_sub(1)
+                .clamp(1, unix::MAX_OPEN_DIRS),
+            error: Mutex::new(None),
+            failed: AtomicBool::new(false),
+            stats: Mutex::new(TreeStats::default()),
+            dirs_kept: AtomicUsize::new(0),
+        };
+        walk.queue(0).push_back(Work::Top(Arc::clone(&top)));
+
+        let (sender, found) = mpsc::sync_channel(QUEUE_SIZE);
+        thread::scope(|scope| {
+            let walk = &walk;
+            scope.spawn(move || work(scope, walk, 0, sender));
+
+            // The files are removed here as they are found, until every thread is done
+            for Found { dir, name } in found {
+                if walk.failed.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let removed = rfs::unlinkat(&dir.fd, name.as_c_str(), AtFlags::empty())
+                    .map_err(|err| walk.context(&dir, Some(&name), err))
+                    .and_then(|()| walk.release(&dir));
+                if let Err(err) = removed {
+                    walk.fail(err);
+                }
+            } // for found
+        });
+
+        if let Some(err) = walk
+            .error
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            return Err(err.into());
+        }
+        let mut stats = walk
+            .stats
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        stats.dirs_kept_count += walk.dirs_kept.into_inner();
+        if top.kept.load(Ordering::SeqCst) {
+            stats.dirs_kept_count += 1;
+            log::warn!(
+                "Left {} special files (devices, sockets or named pipes) in {}, along with the directories they are in. Use --special delete to remove them.",
+                stats.special_kept_count,
+                dir.display()
+            );
+        } else if remove {
+            rfs::unlinkat(parent, name, AtFlags::REMOVEDIR).map_err(|err| describe(dir, err))?;
+        }
+
+        Ok(stats)
+    }
+
+    /// Reads directories until there are none left, starting more threads as the work piles up.
+    fn work<'scope>(
+        scope: &'scope Scope<'scope, '_>,
+        walk: &'scope Walk,
+        index: usize,
+        found: SyncSender<Found>,
+    ) {
+        let mut buf = unix::read_buffer();
+        let mut stats = TreeStats::default();
+        while !walk.failed.load(Ordering::SeqCst) {
+            let Some(next) = walk.next(index) else {
+                if walk.outstanding.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                thread::sleep(IDLE_WAIT);
+                continue;
+            };
+            if let Err(err) = walk.read(next, index, &found, &mut buf, &mut stats) {
+                walk.fail(err);
+            }
+            walk.outstanding.fetch_sub(1, Ordering::SeqCst);
+
+            if walk.queue(index).len() > SPAWN_BACKLOG {
+                let started =
+                    walk.threads
+                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |threads| {
+                            (threads < walk.queues.len()).then_some(threads + 1)
+                        });
+                if let Ok(helper) = started {
+                    let found = found.clone();
+                    scope.spawn(move || work(scope, walk, helper, found));
+                }
+            }
+        } // while work
+
+        let mut total = walk.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        total.file_count += stats.file_count;
+        total.dir_count += stats.dir_count;
+        total.symlink_count += stats.symlink_count;
+        total.special_count += stats.special_count;
+        total.special_kept_count += stats.special_kept_count;
+        total.dirs_kept_count += stats.dirs_kept_count;
+        total.size += stats.size;
+    }
+
+    impl Walk {
+        fn queue(&self, index: usize) -> std::sync::MutexGuard<'_, VecDeque<Work>> {
+            self.queues[index]
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+        }
+
+        /// Takes the directory a thread is to read next: the one it found last, so it works its
+        /// way down the tree, or else the one another thread found first.
+        fn next(&self, index: usize) -> Option<Work> {
+            if let Some(next) = self.queue(index).pop_back() {
+                return Some(next);
+            }
+            (1..self.queues.len())
+                .map(|offset| (index + offset) % self.queues.len())
+                .find_map(|other| self.queue(other).pop_front())
+        }
+
+        /// Opens and reads a directory, queueing its subdirectories and sending its files to be
+        /// removed.
+        fn read(
+            &self,
+            next: Work,
+            index: usize,
+            found: &SyncSender<Found>,
+            buf: &mut Vec<u8>,
+            stats: &mut TreeStats,
+        ) -> Result<(), io::Error> {
+            let node = match next {
+                Work::Top(node) => node,
+                Work::Below(parent, name)
+                    if parent.depth + 1 >= MAX_PARALLEL_DEPTH || !self.take_handle() =>
+                {
+                    return self.walk_below(&parent, &name, stats);
+                }
+                Work::Below(parent, name) => {
+                    let fd = match unix::open_dir(&parent.fd, name.as_c_str()) {
+                        Ok(fd) => fd,
+                        Err(err) => {
+                            self.open.fetch_sub(1, Ordering::SeqCst);
+                            return match err {
+                                Errno::NOENT => self.release(&parent),
+                                // Other files are open too, so the sequential walker is tried
+                                Errno::MFILE | Errno::NFILE => {
+                                    self.walk_below(&parent, &name, stats)
+                                }
+                                err => Err(self.context(&parent, Some(&name), err)),
+                            };
+                        }
+                    };
+                    stats.dir_count += 1;
+                    let depth = parent.depth + 1;
+                    Arc::new(Node {
+                        fd,
+                        open: Arc::clone(&self.open),
+                        parent: Some(parent),
+                        name,
+                        depth,
+                        pending: AtomicUsize::new(1),
+                        kept: AtomicBool::new(false),
+                    })
+                }
+            };
+
+            let mut kept = false;
+            unix::read_dir(&node.fd, buf, |file_name, file_type| {
+                let file_type = match file_type {
+                    FileType::Unknown => FileType::from_raw_mode(
+                        rfs::statat(&node.fd, file_name, AtFlags::SYMLINK_NOFOLLOW)?.st_mode as _,
+                    ),
+                    file_type => file_type,
+                };
+                if file_type == FileType::Directory {
+                    node.pending.fetch_add(1, Ordering::SeqCst);
+                    self.outstanding.fetch_add(1, Ordering::SeqCst);
+                    self.queue(index)
+                        .push_back(Work::Below(Arc::clone(&node), file_name.to_owned()));
+                    return Ok(());
+                }
+
+                let is_special = !matches!(file_type, FileType::RegularFile | FileType::Symlink);
+                if self.remove && is_special && self.special == Special::Skip {
+                    log::debug!(
+                        "Leaving special file {} alone.",
+                        file_name.to_string_lossy()
+                    );
+                    stats.special_kept_count += 1;
+                    kept = true;
+                    return Ok(());
+                }
+
+                let size = rfs::statat(&node.fd, file_name, AtFlags::SYMLINK_NOFOLLOW)?.st_size;
+                stats.file_count += 1;
+                match file_type {
+                    FileType::RegularFile => {}
+                    FileType::Symlink => stats.symlink_count += 1,
+                    _ => stats.special_count += 1,
+                }
+                stats.size += u64::try_from(size).unwrap_or_default();
+
+                if self.remove {
+                    node.pending.fetch_add(1, Ordering::SeqCst);
+                    let file = Found {
+                        dir: Arc::clone(&node),
+                        name: file_name.to_owned(),
+                    };
+                    // The calling thread has stopped taking files after an error
+                    found.send(file).map_err(|_| Errno::CANCELED)?;
+                }
+                Ok(())
+            })
+            .map_err(|err| self.context(&node, None, err))?;
+
+            if kept {
+                node.kept.store(true, Ordering::SeqCst);
+            }
+            self.release(&node)
+        }
+
+        /// Counts a directory handle about to be opened, unless as many are open as may be.
+        fn take_handle(&self) -> bool {
+            self.open
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                    (open < self.max_open).then_some(open + 1)
+                })
+                .is_ok()
+        }
+
+        /// Removes the subtree `name` in `parent` with the sequential walker, on this thread.
+        fn walk_below(
+            &self,
+            parent: &Arc<Node>,
+            name: &CStr,
+            stats: &mut TreeStats,
+        ) -> Result<(), io::Error> {
+            let path = self.path_of(parent, Some(name));
+            let (below, kept) = unix::walk_tree(
+                parent.fd.as_fd(),
+                name,
+                &path,
+                self.remove,
+                self.special,
+                self.max_open_below,
+            )
+            .map_err(shared)?;
+            stats.file_count += below.file_count;
+            stats.dir_count += below.dir_count + 1;
+            stats.symlink_count += below.symlink_count;
+            stats.special_count += below.special_count;
+            stats.special_kept_count += below.special_kept_count;
+            stats.dirs_kept_count += below.dirs_kept_count;
+            stats.size += below.size;
+            if kept {
+                parent.kept.store(true, Ordering::SeqCst);
+            }
+            // The subtree is gone, so it's done with as far as its parent goes
+            self.release(parent)
+        }
+
+        /// Counts one more thing done in a directory. Once everything is, the directory is
+        /// removed from its parent, unless special files were kept in it, and the same is
+        /// counted for the parent in turn. The top of the tree is removed by `walk_at`.
+        fn release(&self, node: &Arc<Node>) -> Result<(), io::Error> {
+            // Deep trees finish many directories at once, so this isn't done recursively
+            let mut node = Arc::clone(node);
+            loop {
+                if node.pending.fetch_sub(1, Ordering::SeqCst) != 1 {
+                    return Ok(());
+                }
+                let Some(parent) = node.parent.clone() else {
+                    return Ok(());
+                };
+                if node.kept.load(Ordering::SeqCst) {
+                    self.dirs_kept.fetch_add(1, Ordering::SeqCst);
+                    parent.kept.store(true, Ordering::SeqCst);
+                } else if self.remove {
+                    match rfs::unlinkat(&parent.fd, node.name.as_c_str(), AtFlags::REMOVEDIR) {
+                        Ok(()) | Err(Errno::NOENT) => {}
+                        Err(err) => return Err(self.context(&parent, Some(&node.name), err)),
+                    }
+                }
+                node = parent;
+            } // loop
+        }
+
+        /// Stops the walk, keeping the first error.
+        fn fail(&self, err: io::Error) {
+            let mut error = self.error.lock().unwrap_or_else(PoisonError::into_inner);
+            if error.is_none() {
+                *error = Some(err);
+            }
+            self.failed.store(true, Ordering::SeqCst);
+        }
+
+        /// Returns the full path of a directory being walked, or of an entry in it.
+        fn path_of(&self, node: &Node, name: Option<&CStr>) -> PathBuf {
+            let mut names = Vec::new();
+            let mut current = node;
+            while let Some(parent) = &current.parent {
+                names.push(current.name.to_string_lossy());
+                current = parent;
+            }
+            let mut path = self.dir.clone();
+            path.extend(names.iter().rev().map(|name| name.as_ref()));
+            if let Some(name) = name {
+                path.push(name.to_string_lossy().as_ref());
+            }
+            path
+        }
+
+        /// Describes an error with the path it happened at.
+        fn context(&self, node: &Node, name: Option<&CStr>, err: Errno) -> io::Error {
+            describe(&self.path_of(node, name), err)
+        }
+    }
+
+    /// Returns how many directory handles a walk may have open: half of the file descriptors
+    /// the process may have open, leaving the rest for everything else.
+    fn handles_allowed() -> usize {
+        let limit = rustix::process::getrlimit(rustix::process::Resource::Nofile).current;
+        limit
+            .and_then(|limit| usize::try_from(limit / 2).ok())
+            .unwrap_or(MAX_HANDLES)
+            .min(MAX_HANDLES)
+    }
+
+    /// Turns an error from the sequential walker into one the threads can share.
+    fn shared(err: Box<dyn Error>) -> io::Error {
+        match err.downcast::<io::Error>() {
+            Ok(err) => *err,
+            Err(err) => io::Error::other(err.to_string()),
+        }
+    }
+}
+
+/// Walks the tree using paths, on systems without directory handle based calls. There are no
+/// special files to keep on these, but there are links to directories, which are removed,
+/// skipped or followed as `dir_links` says. A link to a directory that has been walked already
+/// is never followed again, so a link back up the tree can't loop.
+#[cfg(not(unix))]
+fn walk(
+    dir: &Path,
+    remove: bool,
+    _special: Special,
+    dir_links: DirLinks,
+) -> Result<TreeStats, Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    let mut stats = TreeStats::default();
+    let mut dirs = vec![dir.to_path_buf()];
+    let mut walked = vec![std::fs::canonicalize(dir)?];
+
+    // Everything is removed once the walk is over: the files, then the links to directories, and
+    // then the directories (and links followed), innermost first
+    let mut found = Vec::new();
+    let mut files = Vec::new();
+    let mut links = Vec::new();
+    let mut kept: Vec<PathBuf> = Vec::new();
+    while let Some(current) = dirs.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            let metadata = path.symlink_metadata()?;
+            if metadata.is_dir() {
+                stats.dir_count += 1;
+                dirs.push(path);
+                continue;
+            }
+
+            stats.file_count += 1;
+            if !is_dir_link(&metadata) {
+                if metadata.is_symlink() {
+                    stats.symlink_count += 1;
+                } else if !metadata.is_file() {
+                    stats.special_count += 1;
+                }
+                stats.size += metadata.len();
+                files.push(path);
+                continue;
+            }
+
+            stats.symlink_count += 1;
+            match dir_links {
+                DirLinks::Skip => {
+                    log::debug!("Leaving the link {} alone.", path.display());
+                    stats.links_kept_count += 1;
+                    kept.push(path);
+                }
+                DirLinks::Follow => match std::fs::canonicalize(&path) {
+                    Ok(target)
+                        if !walked
+                            .iter()
+                            .any(|dir| target.starts_with(dir) || dir.starts_with(&target)) =>
+                    {
+                        log::debug!("Following {} to {}", path.display(), target.display());
+                        walked.push(target);
+                        dirs.push(path);
+                    }
+                    _ => links.push(path),
+                },
+                DirLinks::Remove => links.push(path),
+            }
+        } // for entry
+        found.push(current);
+    } // while current
+
+    if !kept.is_empty() {
+        log::warn!(
+            "Left {} links to directories in {}, along with the directories they are in. Use --dir-links remove to remove them.",
+            stats.links_kept_count,
+            dir.display()
+        );
+    }
+
+    if remove {
+        for file in files {
+            std::fs::remove_file(file)?;
+        } // for file
+        for link in links {
+            std::fs::remove_dir(link)?;
+        } // for link
+        for found_dir in found.iter().rev() {
+            if kept.iter().any(|link| link.starts_with(found_dir)) {
+                stats.dirs_kept_count += 1;
+            } else {
+                std::fs::remove_dir(found_dir)?;
+            }
+        } // for found_dir
+    }
+
+    Ok(stats)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Makes a directory with `count` subdirectories, each holding a few files, an empty
+    /// directory and a directory with another file.
+    fn wide_tree(name: &str, count: usize) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("rdel-{name}-{}", std::process::id()));
+        for index in 0..count {
+            let sub = dir.join(format!("d{index}"));
+            fs::create_dir_all(sub.join("empty")).unwrap();
+            fs::create_dir_all(sub.join("full")).unwrap();
+            fs::write(sub.join("full").join("f"), b"x").unwrap();
+            for file in 0..4 {
+                fs::write(sub.join(format!("f{file}")), b"x").unwrap();
+            } // for file
+        } // for index
+        dir
+    }
+
+    #[test]
+    fn wide_tree_within_few_handles() {
+        // The files waiting to be removed keep their directories open, so a wide tree used to
+        // run out of file descriptors; past the cap, subtrees are left to the sequential walker
+        let dir = wide_tree("wide", 100);
+        let removed = parallel::walk_within(rustix::fs::CWD, &dir, &dir, true, Special::Skip, 4);
+        if removed.is_err() {
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        let stats = removed.unwrap();
+        assert!(!dir.exists());
+        assert_eq!(stats.file_count, 100 * 5);
+        assert_eq!(stats.dir_count, 100 * 3);
+        assert_eq!(stats.size, 100 * 5);
+    }
+
+    #[test]
+    fn directories_kept_for_special_files() {
+        let dir = std::env::temp_dir().join(format!("rdel-kept-{}", std::process::id()));
+        fs::create_dir_all(dir.join("a").join("b")).unwrap();
+        fs::create_dir_all(dir.join("c")).unwrap();
+        rustix::fs::mknodat(
+            rustix::fs::CWD,
+            dir.join("a").join("b").join("fifo"),
+            rustix::fs::FileType::Fifo,
+            rustix::fs::Mode::from(0o600),
+            0,
+        )
+        .unwrap();
+
+        let removed = remove_tree(&dir, Special::Skip, DirLinks::Remove);
+        let kept = dir.join("a").join("b").join("fifo").exists();
+        let _ = fs::remove_dir_all(&dir);
+        let stats = removed.unwrap();
+        assert!(kept);
+        assert_eq!(stats.dir_count, 3);
+        assert_eq!(stats.dirs_kept_count, 3);
+        assert_eq!(stats.special_kept_count, 1);
+    }
+}
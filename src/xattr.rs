@@ -0,0 +1,97 @@
+use rustix::fs::{self as rfs, XattrFlags};
+use rustix::io::Errno;
+use std::ffi::CStr;
+use std::io;
+use std::path::Path;
+
+/// Returns the total size of the extended attributes of a file, without following a symbolic
+/// link. On macOS this includes the resource fork (`com.apple.ResourceFork`), Finder info and
+/// quarantine flags, which take up space the file's length doesn't show. File systems without
+/// extended attributes have none.
+pub fn size(path: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for name in names(path)? {
+        match rfs::lgetxattr(path, name.as_c_str(), &mut [0u8; 0][..]) {
+            Ok(len) => total += len as u64,
+            // The attribute was removed in the meantime
+            Err(Errno::NODATA) => {}
+            Err(err) => return Err(err.into()),
+        }
+    } // for name
+    Ok(total)
+}
+
+/// Copies the extended attributes of `from` onto `to`, e.g. after a file has been copied to
+/// another file system, so nothing is lost when it's restored. Attributes that can't be set
+/// (such as `security.*` ones without the privilege, or any on a file system without extended
+/// attributes) are left out with a warning.
+pub fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    for name in names(from)? {
+        let value = match read(from, &name) {
+            Ok(value) => value,
+            Err(Errno::NODATA) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if let Err(err) = rfs::lsetxattr(to, name.as_c_str(), &value, XattrFlags::empty()) {
+            log::warn!(
+                "Unable to copy the extended attribute {} to {}: {}",
+                name.to_string_lossy(),
+                to.display(),
+                io::Error::from(err)
+            );
+        }
+    } // for name
+    Ok(())
+}
+
+/// Returns the value of an extended attribute of a file, or `None` if it isn't set.
+pub fn get(path: &Path, name: &CStr) -> io::Result<Option<Vec<u8>>> {
+    match read(path, name) {
+        Ok(value) => Ok(Some(value)),
+        Err(Errno::NODATA | Errno::NOTSUP) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Lists the names of the extended attributes of a file.
+fn names(path: &Path) -> io::Result<Vec<std::ffi::CString>> {
+    let list = loop {
+        let len = match rfs::llistxattr(path, &mut [0u8; 0][..]) {
+            Ok(len) => len,
+            Err(Errno::NOTSUP) => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut list = vec![0; len];
+        match rfs::llistxattr(path, &mut list[..]) {
+            Ok(len) => {
+                list.truncate(len);
+                break list;
+            }
+            // An attribute was added in the meantime
+            Err(Errno::RANGE) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    };
+    Ok(list
+        .split_inclusive(|&byte| byte == 0)
+        .filter_map(|name| CStr::from_bytes_with_nul(name).ok())
+        .map(CStr::to_owned)
+        .collect())
+}
+
+/// Reads the value of an extended attribute.
+fn read(path: &Path, name: &CStr) -> Result<Vec<u8>, Errno> {
+    loop {
+        let len = rfs::lgetxattr(path, name, &mut [0u8; 0][..])?;
+        let mut value = vec![0; len];
+        match rfs::lgetxattr(path, name, &mut value[..]) {
+            Ok(len) => {
+                value.truncate(len);
+                return Ok(value);
+            }
+            // The value grew in the meantime
+            Err(Errno::RANGE) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}